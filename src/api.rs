@@ -9,10 +9,51 @@ pub mod gen {
 
     use iamgroot::jsonrpc;
 
+    // object: 'ACCOUNT_ACTIVITY'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct AccountActivity {
+        pub block_hash: BlockHash,
+        pub block_number: BlockNumber,
+        pub state_update: bool,
+        pub transactions: Vec<TxnHash>,
+    }
+
+    // object: 'ACCOUNT_ACTIVITY_CHUNK'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct AccountActivityChunk {
+        pub activity: Vec<AccountActivity>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub continuation_token: Option<String>,
+    }
+
+    // object: 'ACCOUNT_ACTIVITY_FILTER'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct AccountActivityFilter {
+        pub address: Address,
+        pub chunk_size: i64,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub continuation_token: Option<String>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub from_block: Option<BlockId>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub to_block: Option<BlockId>,
+    }
+
     // object: 'ADDRESS'
     #[derive(Clone, Debug, Deserialize, Serialize)]
     pub struct Address(pub Felt); // name != binding_name
 
+    // object: 'BINARY_NODE'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct BinaryNode {
+        pub left: Felt,
+        pub right: Felt,
+    }
+
     // object: 'BLOCK_BODY_WITH_TXS'
     #[derive(Clone, Debug, Deserialize, Serialize)]
     pub struct BlockBodyWithTxs {
@@ -277,6 +318,10 @@ pub mod gen {
         LibraryCall,
     }
 
+    // object: 'CASM_COMPILED_CONTRACT_CLASS'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct CasmCompiledContractClass(pub Value); // name != binding_name
+
     // object: 'CHAIN_ID'
     #[derive(Clone, Debug, Deserialize, Serialize)]
     // pub struct ChainId(pub String); // name == binding_name
@@ -321,6 +366,20 @@ pub mod gen {
         }
     }
 
+    // object: 'CLASS_DECLARATION'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct ClassDeclaration {
+        pub block_hash: BlockHash,
+        pub block_number: BlockNumber,
+        pub class_hash: Felt,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub compiled_class_hash: Option<Felt>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub transaction_hash: Option<TxnHash>,
+    }
+
     // object: 'COMMON_RECEIPT_PROPERTIES'
     #[derive(Clone, Debug, Deserialize, Serialize)]
     pub struct CommonReceiptProperties {
@@ -382,6 +441,16 @@ pub mod gen {
         pub l1_handler: Option<Vec<SierraEntryPoint>>,
     }
 
+    // object: 'CONTRACT_DATA'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct ContractData {
+        pub class_hash: Felt,
+        pub contract_state_hash_version: Felt,
+        pub nonce: Felt,
+        pub root: Felt,
+        pub storage_proofs: Vec<Vec<ProofNode>>,
+    }
+
     // object: 'CONTRACT_STORAGE_DIFF_ITEM'
     #[derive(Clone, Debug, Deserialize, Serialize)]
     pub struct ContractStorageDiffItem {
@@ -389,6 +458,23 @@ pub mod gen {
         pub storage_entries: Vec<StorageEntriesItem>,
     }
 
+    // object: 'DEAD_LETTER'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct DeadLetter {
+        pub attempts: i64,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub block_hash: Option<Felt>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub block_number: Option<BlockNumber>,
+        pub event: String,
+        pub failed_at: i64,
+        pub id: i64,
+        pub kind: String,
+        pub reason: String,
+    }
+
     // object: 'DECLARED_CLASSES_ITEM'
     #[derive(Clone, Debug, Deserialize, Serialize)]
     pub struct DeclaredClassesItem {
@@ -608,6 +694,20 @@ pub mod gen {
         pub l1_handler: Option<Vec<DeprecatedCairoEntryPoint>>,
     }
 
+    // object: 'EDGE_NODE'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct EdgeNode {
+        pub child: Felt,
+        pub path: EdgePath,
+    }
+
+    // object: 'EDGE_PATH'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct EdgePath {
+        pub len: i64,
+        pub value: Felt,
+    }
+
     // object: 'EMITTED_EVENT'
     #[derive(Clone, Debug, Deserialize, Serialize)]
     pub struct EmittedEvent {
@@ -1020,9 +1120,34 @@ pub mod gen {
         L1Handler,
     }
 
+    // object: 'L1_SETTLEMENT'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct L1Settlement {
+        pub block_number: BlockNumber,
+        pub l1_block_hash: NumAsHex,
+        pub l1_block_number: i64,
+        pub l1_transaction_hash: NumAsHex,
+        pub settled_block_number: BlockNumber,
+    }
+
+    // object: 'MESSAGE_STATUS'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct MessageStatus {
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub finality_status: Option<TxnStatus>,
+        pub message_hash: NumAsHex,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub transaction_hash: Option<TxnHash>,
+    }
+
     // object: 'MSG_TO_L1'
     #[derive(Clone, Debug, Deserialize, Serialize)]
     pub struct MsgToL1 {
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub from_address: Option<Felt>,
         pub payload: Vec<Felt>,
         pub to_address: Felt,
     }
@@ -1183,6 +1308,29 @@ pub mod gen {
         PendingDeployTxnReceipt(PendingDeployTxnReceipt),
     }
 
+    // object: 'PROOF'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct Proof {
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub class_commitment: Option<Felt>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub contract_data: Option<ContractData>,
+        pub contract_proof: Vec<ProofNode>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub state_commitment: Option<Felt>,
+    }
+
+    // object: 'PROOF_NODE'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    #[serde(untagged)]
+    pub enum ProofNode {
+        Binary { binary: BinaryNode },
+        Edge { edge: EdgeNode },
+    }
+
     // object: 'REPLACED_CLASSES_ITEM'
     #[derive(Clone, Debug, Deserialize, Serialize)]
     pub struct ReplacedClassesItem {
@@ -1590,6 +1738,10 @@ pub mod gen {
         DeprecatedContractClass(DeprecatedContractClass),
     }
 
+    // object: 'getDeadLetters_result'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct GetDeadLettersResult(pub Vec<DeadLetter>); // name == binding_name
+
     // object: 'getEvents_events'
     #[derive(Clone, Debug, Deserialize, Serialize)]
     pub struct GetEventsEvents {
@@ -1599,6 +1751,10 @@ pub mod gen {
         pub events: Vec<EmittedEvent>,
     }
 
+    // object: 'getMessagesStatus_result'
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct GetMessagesStatusResult(pub Vec<MessageStatus>); // name == binding_name
+
     // object: 'getNonce_result'
     #[derive(Clone, Debug, Deserialize, Serialize)]
     pub struct GetNonceResult(pub Felt); // name != binding_name
@@ -1984,6 +2140,97 @@ pub mod gen {
             &self,
             block_hash: BlockHash,
         ) -> std::result::Result<TraceBlockTransactionsTraces, jsonrpc::Error>;
+
+        /// Method: 'armada_getAccountActivity'
+        /// Summary: Returns blocks and transactions touching the given address
+        /// Description: Returns all blocks (in ascending order) where the given address sent a transaction, emitted an event or appeared in the state diff
+        ///
+        async fn getAccountActivity(
+            &self,
+            filter: AccountActivityFilter,
+        ) -> std::result::Result<AccountActivityChunk, jsonrpc::Error>;
+
+        /// Method: 'armada_getClassDeclaration'
+        /// Summary: Returns the declaration of the given class
+        /// Description: Returns the block and the transaction that declared the class, along with the compiled class hash (for Sierra classes)
+        ///
+        async fn getClassDeclaration(
+            &self,
+            class_hash: Felt,
+        ) -> std::result::Result<ClassDeclaration, jsonrpc::Error>;
+
+        /// Method: 'starknet_getCompiledCasm'
+        /// Summary: Get the CASM code resulting from compiling a given class
+        /// Description: Returns the compiled CASM of the Sierra class with the given hash
+        ///
+        async fn getCompiledCasm(
+            &self,
+            class_hash: Felt,
+        ) -> std::result::Result<CasmCompiledContractClass, jsonrpc::Error>;
+
+        /// Method: 'armada_getL1Settlement'
+        /// Summary: Returns the L1 block and transaction that settled the given block
+        /// Description: Returns the first L1 state update (LogStateUpdate event) that covers the given block
+        ///
+        async fn getL1Settlement(
+            &self,
+            block_id: BlockId,
+        ) -> std::result::Result<L1Settlement, jsonrpc::Error>;
+
+        /// Method: 'starknet_getMessagesStatus'
+        /// Summary: Given an L1 tx hash, returns the associated l1_handler tx hashes and statuses for all L1 -> L2 messages sent by the l1 transaction, ordered by the L1 transaction sending order
+        /// Description: Returns all L1->L2 messages sent by the L1 transaction, along with the L2 transactions that consumed them (if any)
+        ///
+        async fn getMessagesStatus(
+            &self,
+            transaction_hash: NumAsHex,
+        ) -> std::result::Result<GetMessagesStatusResult, jsonrpc::Error>;
+
+        /// Method: 'armada_getMessage'
+        /// Summary: Returns the L2 transaction that consumed or sent the given L1<->L2 message
+        /// Description: Looks up the message by its hash: L1->L2 messages resolve to the consuming L1 handler transaction, L2->L1 messages resolve to the sending transaction
+        ///
+        async fn getMessage(
+            &self,
+            message_hash: NumAsHex,
+        ) -> std::result::Result<MessageStatus, jsonrpc::Error>;
+
+        /// Method: 'armada_getDeadLetters'
+        /// Summary: Returns sync events that failed after all retry attempts
+        /// Description: Dead letters are kept until they are retried or discarded
+        ///
+        async fn getDeadLetters(
+            &self,
+        ) -> std::result::Result<GetDeadLettersResult, jsonrpc::Error>;
+
+        /// Method: 'armada_retryDeadLetter'
+        /// Summary: Removes the dead letter and sends its event to the sync again
+        /// Description: Returns false if there is no dead letter with the given id
+        ///
+        async fn retryDeadLetter(
+            &self,
+            id: i64,
+        ) -> std::result::Result<bool, jsonrpc::Error>;
+
+        /// Method: 'armada_discardDeadLetter'
+        /// Summary: Removes the dead letter without retrying its event
+        /// Description: Returns false if there is no dead letter with the given id
+        ///
+        async fn discardDeadLetter(
+            &self,
+            id: i64,
+        ) -> std::result::Result<bool, jsonrpc::Error>;
+
+        /// Method: 'armada_getProof'
+        /// Summary: Returns Merkle proofs of the contract state and storage at the block
+        /// Description: The contract proof leads from the contracts trie root to the contract leaf, storage proofs lead from the contract storage root to the values of the keys
+        ///
+        async fn getProof(
+            &self,
+            block_id: BlockId,
+            contract_address: Address,
+            keys: Vec<Felt>,
+        ) -> std::result::Result<Proof, jsonrpc::Error>;
     }
 
     async fn handle_starknet_getBlockWithTxHashes<RPC: Rpc>(
@@ -2958,6 +3205,390 @@ pub mod gen {
         }
     }
 
+    async fn handle_armada_getAccountActivity<RPC: Rpc>(
+        rpc: &RPC,
+        params: &Value,
+    ) -> jsonrpc::Response {
+        #[derive(Deserialize, Serialize)]
+        struct ArgByPos(AccountActivityFilter);
+
+        #[derive(Deserialize, Serialize)]
+        struct ArgByName {
+            filter: AccountActivityFilter,
+        }
+
+        let args =
+            serde_json::from_value::<ArgByName>(params.clone()).or_else(|_| {
+                serde_json::from_value::<ArgByPos>(params.clone()).map(
+                    |args_by_pos| {
+                        let ArgByPos(filter) = args_by_pos;
+                        ArgByName { filter }
+                    },
+                )
+            });
+
+        let args: ArgByName = match args {
+            Ok(args) => args,
+            Err(e) => {
+                return jsonrpc::Response::error(-32602, "Invalid params")
+            }
+        };
+
+        let ArgByName { filter } = args;
+
+        match rpc.getAccountActivity(filter).await {
+            Ok(ret) => match serde_json::to_value(ret) {
+                Ok(ret) => jsonrpc::Response::result(ret),
+                Err(e) => jsonrpc::Response::error(-32603, "Internal error"),
+            },
+            Err(e) => jsonrpc::Response::error(e.code, &e.message),
+        }
+    }
+
+    async fn handle_armada_getClassDeclaration<RPC: Rpc>(
+        rpc: &RPC,
+        params: &Value,
+    ) -> jsonrpc::Response {
+        #[derive(Deserialize, Serialize)]
+        struct ArgByPos(Felt);
+
+        #[derive(Deserialize, Serialize)]
+        struct ArgByName {
+            class_hash: Felt,
+        }
+
+        let args =
+            serde_json::from_value::<ArgByName>(params.clone()).or_else(|_| {
+                serde_json::from_value::<ArgByPos>(params.clone()).map(
+                    |args_by_pos| {
+                        let ArgByPos(class_hash) = args_by_pos;
+                        ArgByName { class_hash }
+                    },
+                )
+            });
+
+        let args: ArgByName = match args {
+            Ok(args) => args,
+            Err(e) => {
+                return jsonrpc::Response::error(-32602, "Invalid params")
+            }
+        };
+
+        let ArgByName { class_hash } = args;
+
+        match rpc.getClassDeclaration(class_hash).await {
+            Ok(ret) => match serde_json::to_value(ret) {
+                Ok(ret) => jsonrpc::Response::result(ret),
+                Err(e) => jsonrpc::Response::error(-32603, "Internal error"),
+            },
+            Err(e) => jsonrpc::Response::error(e.code, &e.message),
+        }
+    }
+
+    async fn handle_starknet_getCompiledCasm<RPC: Rpc>(
+        rpc: &RPC,
+        params: &Value,
+    ) -> jsonrpc::Response {
+        #[derive(Deserialize, Serialize)]
+        struct ArgByPos(Felt);
+
+        #[derive(Deserialize, Serialize)]
+        struct ArgByName {
+            class_hash: Felt,
+        }
+
+        let args =
+            serde_json::from_value::<ArgByName>(params.clone()).or_else(|_| {
+                serde_json::from_value::<ArgByPos>(params.clone()).map(
+                    |args_by_pos| {
+                        let ArgByPos(class_hash) = args_by_pos;
+                        ArgByName { class_hash }
+                    },
+                )
+            });
+
+        let args: ArgByName = match args {
+            Ok(args) => args,
+            Err(e) => {
+                return jsonrpc::Response::error(-32602, "Invalid params")
+            }
+        };
+
+        let ArgByName { class_hash } = args;
+
+        match rpc.getCompiledCasm(class_hash).await {
+            Ok(ret) => match serde_json::to_value(ret) {
+                Ok(ret) => jsonrpc::Response::result(ret),
+                Err(e) => jsonrpc::Response::error(-32603, "Internal error"),
+            },
+            Err(e) => jsonrpc::Response::error(e.code, &e.message),
+        }
+    }
+
+    async fn handle_armada_getL1Settlement<RPC: Rpc>(
+        rpc: &RPC,
+        params: &Value,
+    ) -> jsonrpc::Response {
+        #[derive(Deserialize, Serialize)]
+        struct ArgByPos(BlockId);
+
+        #[derive(Deserialize, Serialize)]
+        struct ArgByName {
+            block_id: BlockId,
+        }
+
+        let args =
+            serde_json::from_value::<ArgByName>(params.clone()).or_else(|_| {
+                serde_json::from_value::<ArgByPos>(params.clone()).map(
+                    |args_by_pos| {
+                        let ArgByPos(block_id) = args_by_pos;
+                        ArgByName { block_id }
+                    },
+                )
+            });
+
+        let args: ArgByName = match args {
+            Ok(args) => args,
+            Err(e) => {
+                return jsonrpc::Response::error(-32602, "Invalid params")
+            }
+        };
+
+        let ArgByName { block_id } = args;
+
+        match rpc.getL1Settlement(block_id).await {
+            Ok(ret) => match serde_json::to_value(ret) {
+                Ok(ret) => jsonrpc::Response::result(ret),
+                Err(e) => jsonrpc::Response::error(-32603, "Internal error"),
+            },
+            Err(e) => jsonrpc::Response::error(e.code, &e.message),
+        }
+    }
+
+    async fn handle_starknet_getMessagesStatus<RPC: Rpc>(
+        rpc: &RPC,
+        params: &Value,
+    ) -> jsonrpc::Response {
+        #[derive(Deserialize, Serialize)]
+        struct ArgByPos(NumAsHex);
+
+        #[derive(Deserialize, Serialize)]
+        struct ArgByName {
+            transaction_hash: NumAsHex,
+        }
+
+        let args =
+            serde_json::from_value::<ArgByName>(params.clone()).or_else(|_| {
+                serde_json::from_value::<ArgByPos>(params.clone()).map(
+                    |args_by_pos| {
+                        let ArgByPos(transaction_hash) = args_by_pos;
+                        ArgByName { transaction_hash }
+                    },
+                )
+            });
+
+        let args: ArgByName = match args {
+            Ok(args) => args,
+            Err(e) => {
+                return jsonrpc::Response::error(-32602, "Invalid params")
+            }
+        };
+
+        let ArgByName { transaction_hash } = args;
+
+        match rpc.getMessagesStatus(transaction_hash).await {
+            Ok(ret) => match serde_json::to_value(ret) {
+                Ok(ret) => jsonrpc::Response::result(ret),
+                Err(e) => jsonrpc::Response::error(-32603, "Internal error"),
+            },
+            Err(e) => jsonrpc::Response::error(e.code, &e.message),
+        }
+    }
+
+    async fn handle_armada_getMessage<RPC: Rpc>(
+        rpc: &RPC,
+        params: &Value,
+    ) -> jsonrpc::Response {
+        #[derive(Deserialize, Serialize)]
+        struct ArgByPos(NumAsHex);
+
+        #[derive(Deserialize, Serialize)]
+        struct ArgByName {
+            message_hash: NumAsHex,
+        }
+
+        let args =
+            serde_json::from_value::<ArgByName>(params.clone()).or_else(|_| {
+                serde_json::from_value::<ArgByPos>(params.clone()).map(
+                    |args_by_pos| {
+                        let ArgByPos(message_hash) = args_by_pos;
+                        ArgByName { message_hash }
+                    },
+                )
+            });
+
+        let args: ArgByName = match args {
+            Ok(args) => args,
+            Err(e) => {
+                return jsonrpc::Response::error(-32602, "Invalid params")
+            }
+        };
+
+        let ArgByName { message_hash } = args;
+
+        match rpc.getMessage(message_hash).await {
+            Ok(ret) => match serde_json::to_value(ret) {
+                Ok(ret) => jsonrpc::Response::result(ret),
+                Err(e) => jsonrpc::Response::error(-32603, "Internal error"),
+            },
+            Err(e) => jsonrpc::Response::error(e.code, &e.message),
+        }
+    }
+
+    async fn handle_armada_getDeadLetters<RPC: Rpc>(
+        rpc: &RPC,
+        _params: &Value,
+    ) -> jsonrpc::Response {
+        match rpc.getDeadLetters().await {
+            Ok(ret) => match serde_json::to_value(ret) {
+                Ok(ret) => jsonrpc::Response::result(ret),
+                Err(e) => jsonrpc::Response::error(1003, &format!("{e:?}")),
+            },
+            Err(e) => jsonrpc::Response::error(e.code, &e.message),
+        }
+    }
+
+    async fn handle_armada_retryDeadLetter<RPC: Rpc>(
+        rpc: &RPC,
+        params: &Value,
+    ) -> jsonrpc::Response {
+        #[derive(Deserialize, Serialize)]
+        struct ArgByPos(i64);
+
+        #[derive(Deserialize, Serialize)]
+        struct ArgByName {
+            id: i64,
+        }
+
+        let args =
+            serde_json::from_value::<ArgByName>(params.clone()).or_else(|_| {
+                serde_json::from_value::<ArgByPos>(params.clone()).map(
+                    |args_by_pos| {
+                        let ArgByPos(id) = args_by_pos;
+                        ArgByName { id }
+                    },
+                )
+            });
+
+        let args: ArgByName = match args {
+            Ok(args) => args,
+            Err(e) => {
+                return jsonrpc::Response::error(-32602, "Invalid params")
+            }
+        };
+
+        let ArgByName { id } = args;
+
+        match rpc.retryDeadLetter(id).await {
+            Ok(ret) => match serde_json::to_value(ret) {
+                Ok(ret) => jsonrpc::Response::result(ret),
+                Err(e) => jsonrpc::Response::error(-32603, "Internal error"),
+            },
+            Err(e) => jsonrpc::Response::error(e.code, &e.message),
+        }
+    }
+
+    async fn handle_armada_discardDeadLetter<RPC: Rpc>(
+        rpc: &RPC,
+        params: &Value,
+    ) -> jsonrpc::Response {
+        #[derive(Deserialize, Serialize)]
+        struct ArgByPos(i64);
+
+        #[derive(Deserialize, Serialize)]
+        struct ArgByName {
+            id: i64,
+        }
+
+        let args =
+            serde_json::from_value::<ArgByName>(params.clone()).or_else(|_| {
+                serde_json::from_value::<ArgByPos>(params.clone()).map(
+                    |args_by_pos| {
+                        let ArgByPos(id) = args_by_pos;
+                        ArgByName { id }
+                    },
+                )
+            });
+
+        let args: ArgByName = match args {
+            Ok(args) => args,
+            Err(e) => {
+                return jsonrpc::Response::error(-32602, "Invalid params")
+            }
+        };
+
+        let ArgByName { id } = args;
+
+        match rpc.discardDeadLetter(id).await {
+            Ok(ret) => match serde_json::to_value(ret) {
+                Ok(ret) => jsonrpc::Response::result(ret),
+                Err(e) => jsonrpc::Response::error(-32603, "Internal error"),
+            },
+            Err(e) => jsonrpc::Response::error(e.code, &e.message),
+        }
+    }
+
+    async fn handle_armada_getProof<RPC: Rpc>(
+        rpc: &RPC,
+        params: &Value,
+    ) -> jsonrpc::Response {
+        #[derive(Deserialize, Serialize)]
+        struct ArgByPos(BlockId, Address, Vec<Felt>);
+
+        #[derive(Deserialize, Serialize)]
+        struct ArgByName {
+            block_id: BlockId,
+            contract_address: Address,
+            keys: Vec<Felt>,
+        }
+
+        let args =
+            serde_json::from_value::<ArgByName>(params.clone()).or_else(|_| {
+                serde_json::from_value::<ArgByPos>(params.clone()).map(
+                    |args_by_pos| {
+                        let ArgByPos(block_id, contract_address, keys) =
+                            args_by_pos;
+                        ArgByName {
+                            block_id,
+                            contract_address,
+                            keys,
+                        }
+                    },
+                )
+            });
+
+        let args: ArgByName = match args {
+            Ok(args) => args,
+            Err(e) => {
+                return jsonrpc::Response::error(-32602, "Invalid params")
+            }
+        };
+
+        let ArgByName {
+            block_id,
+            contract_address,
+            keys,
+        } = args;
+
+        match rpc.getProof(block_id, contract_address, keys).await {
+            Ok(ret) => match serde_json::to_value(ret) {
+                Ok(ret) => jsonrpc::Response::result(ret),
+                Err(e) => jsonrpc::Response::error(-32603, "Internal error"),
+            },
+            Err(e) => jsonrpc::Response::error(e.code, &e.message),
+        }
+    }
+
     pub async fn handle<RPC: Rpc>(
         rpc: &RPC,
         req: &jsonrpc::Request,
@@ -3034,6 +3665,32 @@ pub mod gen {
             "starknet_traceBlockTransactions" => {
                 handle_starknet_traceBlockTransactions(rpc, params).await
             }
+            "armada_getAccountActivity" => {
+                handle_armada_getAccountActivity(rpc, params).await
+            }
+            "armada_getClassDeclaration" => {
+                handle_armada_getClassDeclaration(rpc, params).await
+            }
+            "starknet_getCompiledCasm" => {
+                handle_starknet_getCompiledCasm(rpc, params).await
+            }
+            "armada_getL1Settlement" => {
+                handle_armada_getL1Settlement(rpc, params).await
+            }
+            "starknet_getMessagesStatus" => {
+                handle_starknet_getMessagesStatus(rpc, params).await
+            }
+            "armada_getMessage" => handle_armada_getMessage(rpc, params).await,
+            "armada_getDeadLetters" => {
+                handle_armada_getDeadLetters(rpc, params).await
+            }
+            "armada_retryDeadLetter" => {
+                handle_armada_retryDeadLetter(rpc, params).await
+            }
+            "armada_discardDeadLetter" => {
+                handle_armada_discardDeadLetter(rpc, params).await
+            }
+            "armada_getProof" => handle_armada_getProof(rpc, params).await,
             _ => jsonrpc::Response::error(-32601, "Method not found"),
         };
 
@@ -3045,11 +3702,15 @@ pub mod gen {
     }
 
     pub mod error {
+        pub const ADMIN_DISABLED: Error =
+            Error(1000, "Admin methods are disabled");
         pub const BLOCK_NOT_FOUND: Error = Error(24, "Block not found");
         pub const CLASS_HASH_NOT_FOUND: Error =
             Error(28, "Class hash not found");
         pub const CONTRACT_ERROR: Error = Error(40, "Contract error");
         pub const CONTRACT_NOT_FOUND: Error = Error(20, "Contract not found");
+        pub const CONTRACT_STATE_MISSING: Error =
+            Error(1005, "Contract state is missing");
         pub const FAILED_TO_RECEIVE_TXN: Error =
             Error(1, "Failed to write transaction");
         pub const INVALID_BLOCK_HASH: Error = Error(24, "Invalid block hash");
@@ -3064,11 +3725,19 @@ pub mod gen {
             Error(25, "Invalid transaction hash");
         pub const INVALID_TXN_INDEX: Error =
             Error(27, "Invalid transaction index in a block");
+        pub const NOT_RETRIABLE: Error =
+            Error(1002, "Event is produced by polling and cannot be retried");
         pub const NO_BLOCKS: Error = Error(32, "There are no blocks");
         pub const NO_TRACE_AVAILABLE: Error =
             Error(10, "No trace available for transaction");
         pub const PAGE_SIZE_TOO_BIG: Error =
             Error(31, "Requested page size is too big");
+        pub const READ_ONLY: Error = Error(1001, "Storage is read-only");
+        pub const STATE_NOT_VERIFIED: Error = Error(
+            1004,
+            "State of the block is not verified: no proof available",
+        );
+        pub const SYNC_NOT_RUNNING: Error = Error(1003, "Sync is not running");
         pub const TOO_MANY_KEYS_IN_FILTER: Error =
             Error(34, "Too many keys provided in a filter");
         pub const TXN_HASH_NOT_FOUND: Error =
@@ -1,36 +1,489 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    str::FromStr,
+    time::Duration,
+};
 
-const ARMADA_INFURA_TOKEN: &str = "ARMADA_INFURA_TOKEN";
+use crate::cfg::{Direction, ProfileConfig};
+
+/// Every setting can be provided (in the order of precedence) as a
+/// command line option (`--eth-url=...`), an environment variable
+/// (`ARMADA_ETH_URL=...`) or a key in the config file (`eth_url = "..."`).
+const KEYS: &[&str] = &[
+    "data_dir",
+    "network",
+    "rpc_bind_addr",
+    "src_poll_delay",
+    "seq_poll_delay",
+    "eth_poll_delay",
+    "seq_url",
+    "eth_url",
+    "eth_auth",
+    "eth_quorum",
+    "eth_contract_address",
+    "eth_confirmations",
+    "eth_deploy_block",
+    "chain_id",
+    "genesis_hash",
+    "public_key",
+    "infura_token",
+    "cache_size",
+    "prune_keep",
+    "shutdown_timeout",
+    "retry_attempts",
+    "max_handlers",
+    "sync_from",
+    "sync_to",
+    "sync_direction",
+    "file",
+    "from",
+    "to",
+];
+
+/// Boolean settings: a bare `--flag` on the command line
+/// or `flag = true` in the config file.
+const FLAGS: &[&str] = &["metrics", "archive", "no_eth", "no_verify", "admin"];
+
+const ARMADA_CONFIG: &str = "ARMADA_CONFIG";
+
+pub const DEFAULT_RPC_BIND_ADDR: &str = "0.0.0.0:9000";
+pub const DEFAULT_SRC_POLL_DELAY: Duration = Duration::from_secs(1);
+pub const DEFAULT_SEQ_POLL_DELAY: Duration = Duration::from_secs(30);
+pub const DEFAULT_ETH_POLL_DELAY: Duration = Duration::from_secs(120);
+
+pub const USAGE: &str = r#"Usage: armada [COMMAND] <data-directory> <network> [OPTIONS]
+
+Commands:
+  run      Sync the chain and serve the RPC (default)
+  serve    Serve the RPC over the existing data directory (read-only)
+  reindex  Rebuild indices from stored blocks and states
+  verify   Check the stored chain for gaps and broken parent links
+  export   Write blocks and states to a JSON lines file (--file, --from, --to)
+  import   Read blocks and states from a JSON lines file (--file)
+  status   Print the sync status of the data directory
+
+Options (env: ARMADA_<OPTION>, config file: <option> = ...):
+  --config <path>                 TOML config file
+  --data-dir <path>               Data directory
+  --network <name>                Network profile
+  --rpc-bind-addr <addr>          RPC listen address (default: 0.0.0.0:9000)
+  --src-poll-delay <seconds>      Event source delay (default: 1)
+  --seq-poll-delay <seconds>      Gateway poll delay (default: 30)
+  --eth-poll-delay <seconds>      L1 poll delay (default: 120)
+  --seq-url <url>                 Gateway URL
+  --eth-url <url,...>             L1 JSON-RPC URLs (in failover order)
+  --eth-auth <value>              L1 `Authorization` header
+  --eth-quorum <n>                L1 providers that must agree on L1 data
+  --eth-contract-address <addr>   L1 core contract address
+  --eth-confirmations <n>         L1 confirmation depth (default: 12)
+  --eth-deploy-block <n>          L1 block to scan the core contract logs from
+  --chain-id <id>                 Chain id (hex or short string, e.g. SN_MAIN)
+  --genesis-hash <hash>           Expected hash of the genesis block
+  --public-key <key>              Sequencer public key (block signatures)
+  --infura-token <token>          Infura token (if no L1 URL is set)
+  --cache-size <n>                LRU cache size
+  --prune-keep <n>                Number of recent blocks to keep
+  --shutdown-timeout <seconds>    Time to finish in-flight work on exit (default: 30)
+  --retry-attempts <n>            Failures before a sync event is dead-lettered (default: 10)
+  --max-handlers <n>              Sync events handled concurrently (default: 16)
+  --sync-from <n>, --sync-to <n>  Sync only blocks in the range
+  --sync-direction <dir>          backward (from the head, default) or forward (from genesis)
+  --archive                       Archive pruned blocks instead of deleting
+  --metrics                       Report Prometheus metrics
+  --no-eth                        Disable L1 tracking
+  --no-verify                     Do not verify block and transaction hashes or signatures
+  --admin                         Serve the admin RPC methods (dead letters)
+
+Custom networks are defined in the config file as [profiles.<name>] tables
+with seq_url, eth_url, eth_contract_address, eth_deploy_block, chain_id,
+genesis_hash and public_key keys.
+"#;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Run,
+    Serve,
+    Reindex,
+    Verify,
+    Export {
+        file: String,
+        from: Option<u64>,
+        to: Option<u64>,
+    },
+    Import {
+        file: String,
+    },
+    Status,
+}
 
 pub struct Args {
+    pub command: Command,
     pub data_dir: String,
     pub network: String,
-    pub infura_token: String,
+    pub rpc_bind_addr: SocketAddr,
+    pub src_poll_delay: Duration,
+    pub seq_poll_delay: Duration,
+    pub eth_poll_delay: Duration,
+    pub seq_url: Option<String>,
+    pub infura_token: Option<String>,
+    /// L1 JSON-RPC provider URLs (comma-separated), in failover order.
+    pub eth_urls: Vec<String>,
+    /// Value of the `Authorization` header sent to L1 providers.
+    pub eth_auth: Option<String>,
+    /// Number of L1 providers that must agree on the L1 state.
+    pub eth_quorum: Option<usize>,
+    pub eth_contract_address: Option<String>,
+    pub cache_size: Option<usize>,
+    pub prune_keep: Option<u64>,
+    pub eth_confirmations: Option<u64>,
+    pub eth_deploy_block: Option<u64>,
+    pub shutdown_timeout: Option<Duration>,
+    pub retry_attempts: Option<u32>,
+    pub max_handlers: Option<usize>,
+    /// Range of blocks to sync (inclusive).
+    pub sync_from: Option<u64>,
+    pub sync_to: Option<u64>,
+    pub sync_direction: Option<Direction>,
+    pub chain_id: Option<String>,
+    pub genesis_hash: Option<String>,
+    pub public_key: Option<String>,
+    /// Custom network profiles from the config file.
+    pub profiles: HashMap<String, ProfileConfig>,
     pub flags: HashSet<String>,
 }
 
-fn get_pos(
+/// Raw settings before parsing: later sources override earlier ones.
+#[derive(Debug, Default)]
+struct Values {
+    values: HashMap<String, String>,
+    flags: HashSet<String>,
+    profiles: HashMap<String, ProfileConfig>,
+}
+
+impl Values {
+    fn get<T>(&self, key: &str) -> anyhow::Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.values
+            .get(key)
+            .map(|val| {
+                val.parse().map_err(|e| {
+                    anyhow::anyhow!("Invalid value of '{key}': '{val}' ({e})")
+                })
+            })
+            .transpose()
+    }
+
+    fn get_secs(&self, key: &str) -> anyhow::Result<Option<Duration>> {
+        Ok(self.get::<u64>(key)?.map(Duration::from_secs))
+    }
+
+    fn require(&self, key: &str) -> anyhow::Result<String> {
+        self.values.get(key).cloned().ok_or(anyhow::anyhow!(
+            "Missing required argument: '{}'\n\n{USAGE}",
+            key.replace('_', "-")
+        ))
+    }
+
+    fn set(&mut self, key: &str, val: String) -> anyhow::Result<()> {
+        if FLAGS.contains(&key) {
+            match val.as_str() {
+                "true" => self.flags.insert(key.replace('_', "-")),
+                "false" => self.flags.remove(&key.replace('_', "-")),
+                _ => anyhow::bail!("Invalid value of '{key}': '{val}'"),
+            };
+        } else if KEYS.contains(&key) {
+            self.values.insert(key.to_string(), val);
+        } else {
+            anyhow::bail!("Unknown setting: '{key}'\n\n{USAGE}");
+        }
+        Ok(())
+    }
+}
+
+fn from_file(values: &mut Values, path: &str) -> anyhow::Result<()> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read config '{path}': {e}"))?;
+    let table: toml::Table = toml::from_str(&text)?;
+    for (key, val) in table {
+        if key == "profiles" {
+            values.profiles = val.try_into().map_err(|e| {
+                anyhow::anyhow!("Invalid profiles in config '{path}': {e}")
+            })?;
+            continue;
+        }
+        let val = match val {
+            toml::Value::String(val) => val,
+            toml::Value::Array(items) => items
+                .into_iter()
+                .map(|item| match item {
+                    toml::Value::String(item) => item,
+                    item => item.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+            val => val.to_string(),
+        };
+        values.set(&key, val)?;
+    }
+    Ok(())
+}
+
+fn from_env(values: &mut Values) -> anyhow::Result<()> {
+    for key in KEYS.iter().chain(FLAGS) {
+        let var = format!("ARMADA_{}", key.to_uppercase());
+        if let Ok(val) = std::env::var(var) {
+            values.set(key, val)?;
+        }
+    }
+    Ok(())
+}
+
+/// Split command line arguments into the command, positional arguments
+/// and options (`--key value`, `--key=value` or a bare `--flag`).
+fn parse_cli(
     args: &[String],
-    idx: usize,
-    name: &'static str,
-) -> anyhow::Result<String> {
-    if let Some(val) = args.get(idx) {
-        return Ok(val.clone());
+) -> anyhow::Result<(Command, Vec<String>, Vec<(String, String)>)> {
+    let mut args = args.iter().skip(1).peekable();
+
+    let command = match args.peek().map(|arg| arg.as_str()) {
+        Some("run") => Some(Command::Run),
+        Some("serve") => Some(Command::Serve),
+        Some("reindex") => Some(Command::Reindex),
+        Some("verify") => Some(Command::Verify),
+        Some("export") => Some(Command::Export {
+            file: String::default(),
+            from: None,
+            to: None,
+        }),
+        Some("import") => Some(Command::Import {
+            file: String::default(),
+        }),
+        Some("status") => Some(Command::Status),
+        _ => None,
+    };
+    if command.is_some() {
+        args.next();
+    }
+
+    let mut positional = Vec::new();
+    let mut options = Vec::new();
+    while let Some(arg) = args.next() {
+        let option = match arg.strip_prefix("--") {
+            Some(option) => option.replace('-', "_"),
+            None => {
+                positional.push(arg.clone());
+                continue;
+            }
+        };
+        if let Some((key, val)) = option.split_once('=') {
+            options.push((key.to_string(), val.to_string()));
+        } else if FLAGS.contains(&option.as_str()) {
+            options.push((option, "true".to_string()));
+        } else if let Some(val) = args.next_if(|arg| !arg.starts_with("--")) {
+            options.push((option, val.clone()));
+        } else {
+            anyhow::bail!("Missing value of '--{option}'\n\n{USAGE}");
+        }
     }
-    anyhow::bail!("Missing required argument: '{name}' at position <{idx}>");
+
+    Ok((command.unwrap_or(Command::Run), positional, options))
 }
 
 pub fn resolve() -> anyhow::Result<Args> {
     let args = std::env::args().collect::<Vec<String>>();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{USAGE}");
+        std::process::exit(0);
+    }
+    resolve_from(&args)
+}
+
+pub fn resolve_from(args: &[String]) -> anyhow::Result<Args> {
+    let (command, positional, options) = parse_cli(args)?;
+
+    let config = options
+        .iter()
+        .find(|(key, _)| key == "config")
+        .map(|(_, path)| path.clone())
+        .or(std::env::var(ARMADA_CONFIG).ok());
+
+    let mut values = Values::default();
+    if let Some(path) = config {
+        from_file(&mut values, &path)?;
+    }
+    from_env(&mut values)?;
+    for (key, val) in positional
+        .into_iter()
+        .zip(["data_dir", "network"])
+        .map(|(val, key)| (key.to_string(), val))
+        .chain(options.into_iter().filter(|(key, _)| key != "config"))
+    {
+        values.set(&key, val)?;
+    }
+
+    let command = match command {
+        Command::Export { .. } => Command::Export {
+            file: values.require("file")?,
+            from: values.get("from")?,
+            to: values.get("to")?,
+        },
+        Command::Import { .. } => Command::Import {
+            file: values.require("file")?,
+        },
+        command => command,
+    };
 
     Ok(Args {
-        data_dir: get_pos(&args, 1, "data-directory")?,
-        network: get_pos(&args, 2, "network")?,
-        infura_token: std::env::var(ARMADA_INFURA_TOKEN)?,
-        flags: args
-            .into_iter()
-            .skip(3)
-            .filter_map(|arg| arg.strip_prefix("--").map(|x| x.to_string()))
-            .collect(),
+        command,
+        data_dir: values.require("data_dir")?,
+        network: values.require("network")?,
+        rpc_bind_addr: values
+            .get("rpc_bind_addr")?
+            .unwrap_or(DEFAULT_RPC_BIND_ADDR.parse()?),
+        src_poll_delay: values
+            .get_secs("src_poll_delay")?
+            .unwrap_or(DEFAULT_SRC_POLL_DELAY),
+        seq_poll_delay: values
+            .get_secs("seq_poll_delay")?
+            .unwrap_or(DEFAULT_SEQ_POLL_DELAY),
+        eth_poll_delay: values
+            .get_secs("eth_poll_delay")?
+            .unwrap_or(DEFAULT_ETH_POLL_DELAY),
+        seq_url: values.get("seq_url")?,
+        infura_token: values.get("infura_token")?,
+        eth_urls: values
+            .get::<String>("eth_url")?
+            .map(|urls| {
+                urls.split(',')
+                    .map(|url| url.trim().to_string())
+                    .filter(|url| !url.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        eth_auth: values.get("eth_auth")?,
+        eth_quorum: values.get("eth_quorum")?,
+        eth_contract_address: values.get("eth_contract_address")?,
+        cache_size: values.get("cache_size")?,
+        prune_keep: values.get("prune_keep")?,
+        eth_confirmations: values.get("eth_confirmations")?,
+        eth_deploy_block: values.get("eth_deploy_block")?,
+        shutdown_timeout: values.get_secs("shutdown_timeout")?,
+        retry_attempts: values.get("retry_attempts")?,
+        max_handlers: values.get("max_handlers")?,
+        sync_from: values.get("sync_from")?,
+        sync_to: values.get("sync_to")?,
+        sync_direction: values.get("sync_direction")?,
+        chain_id: values.get("chain_id")?,
+        genesis_hash: values.get("genesis_hash")?,
+        public_key: values.get("public_key")?,
+        profiles: values.profiles,
+        flags: values.flags,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_legacy_args() -> anyhow::Result<()> {
+        let args = resolve_from(&args("armada /tmp/data mainnet --metrics"))?;
+        assert_eq!(args.command, Command::Run);
+        assert_eq!(args.data_dir, "/tmp/data");
+        assert_eq!(args.network, "mainnet");
+        assert_eq!(args.rpc_bind_addr, DEFAULT_RPC_BIND_ADDR.parse()?);
+        assert!(args.flags.contains("metrics"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_with_options() -> anyhow::Result<()> {
+        let args = resolve_from(&args(
+            "armada export --data-dir=/tmp/data --network testnet \
+            --file out.jsonl --from 10 --no-eth --eth-url a,b",
+        ))?;
+        assert_eq!(
+            args.command,
+            Command::Export {
+                file: "out.jsonl".to_string(),
+                from: Some(10),
+                to: None,
+            }
+        );
+        assert_eq!(args.network, "testnet");
+        // The export range does not limit the sync range
+        assert_eq!(args.sync_from, None);
+        assert_eq!(args.eth_urls, vec!["a", "b"]);
+        assert!(args.flags.contains("no-eth"));
+
+        assert!(resolve_from(&args("armada export /tmp/data mainnet")).is_err());
+        assert!(resolve_from(&args("armada /tmp/data mainnet --x 1")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_file() -> anyhow::Result<()> {
+        let dir = tempdir::TempDir::new("armada")?;
+        let path = dir.path().join("armada.toml");
+        std::fs::write(
+            &path,
+            r#"
+            data_dir = "/tmp/data"
+            network = "mainnet"
+            rpc_bind_addr = "127.0.0.1:9090"
+            seq_poll_delay = 10
+            eth_url = ["http://localhost:8545", "http://localhost:8546"]
+            archive = true
+            "#,
+        )?;
+        let line = format!(
+            "armada serve --config {} --network testnet",
+            path.display()
+        );
+        let args = resolve_from(&args(&line))?;
+        assert_eq!(args.command, Command::Serve);
+        assert_eq!(args.data_dir, "/tmp/data");
+        assert_eq!(args.network, "testnet");
+        assert_eq!(args.rpc_bind_addr, "127.0.0.1:9090".parse()?);
+        assert_eq!(args.seq_poll_delay, Duration::from_secs(10));
+        assert_eq!(args.eth_urls.len(), 2);
+        assert!(args.flags.contains("archive"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_profiles() -> anyhow::Result<()> {
+        let dir = tempdir::TempDir::new("armada")?;
+        let path = dir.path().join("armada.toml");
+        std::fs::write(
+            &path,
+            r#"
+            data_dir = "/tmp/data"
+            network = "devnet"
+
+            [profiles.devnet]
+            seq_url = "http://localhost:5050"
+            eth_url = ["http://localhost:8545"]
+            eth_contract_address = "0x123"
+            chain_id = "SN_DEVNET"
+            "#,
+        )?;
+        let line =
+            format!("armada --config {} --genesis-hash 0x42", path.display());
+        let args = resolve_from(&args(&line))?;
+        assert_eq!(args.network, "devnet");
+        assert_eq!(args.genesis_hash, Some("0x42".to_string()));
+        let devnet = args.profiles.get("devnet").expect("devnet profile");
+        assert_eq!(devnet.seq_url.as_deref(), Some("http://localhost:5050"));
+        assert_eq!(devnet.chain_id.as_deref(), Some("SN_DEVNET"));
+        Ok(())
+    }
+}
@@ -1,181 +1,237 @@
-use std::{net::SocketAddr, time::Duration};
-
 use armada::{
-    arg::Args,
-    cfg::{Config, Profile},
-    ctx::{Context, Shared},
+    arg::{Args, Command},
+    cfg::{Config, Profile, ProfileConfig, Pruning, Retry, Urls},
+    cmd,
     db::Storage,
-    eth::EthClient,
+    eth::{EthClient, EthPool},
+    node::{self, Handle, Node},
     seq::SeqClient,
-    sync::{self, Event, Source},
-    util::U64,
 };
-use yakvdb::typed::DB;
-
-const SECOND: Duration = Duration::from_secs(1);
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
 
     let args: Args = armada::arg::resolve()?;
-    let token = &args.infura_token;
     let is_metrics_reporting_enabled = args.flags.contains("metrics");
 
-    let mainnet = Profile {
-        network: "mainnet".to_string(),
-        eth_url: format!("https://mainnet.infura.io/v3/{token}"),
-        seq_url: "https://alpha-mainnet.starknet.io".to_string(),
-        eth_contract_address: "0xc662c410C0ECf747543f5bA90660f6ABeBD9C8c4"
-            .to_string(),
+    let overrides = ProfileConfig {
+        seq_url: args.seq_url.clone(),
+        eth_url: (!args.eth_urls.is_empty())
+            .then(|| Urls::Many(args.eth_urls.clone())),
+        eth_contract_address: args.eth_contract_address.clone(),
+        chain_id: args.chain_id.clone(),
+        genesis_hash: args.genesis_hash.clone(),
+        public_key: args.public_key.clone(),
+        eth_deploy_block: args.eth_deploy_block,
     };
-
-    let testnet = Profile {
-        network: "testnet".to_string(),
-        eth_url: format!("https://goerli.infura.io/v3/{token}"),
-        seq_url: "https://alpha4.starknet.io".to_string(),
-        eth_contract_address: "0xde29d060D45901Fb19ED6C6e959EB22d8626708e"
-            .to_string(),
+    let profile = Profile::builtin(&args.network, args.infura_token.as_deref())
+        .unwrap_or_else(|| Profile::custom(&args.network));
+    let profile = match args.profiles.get(&args.network) {
+        Some(custom) => profile.with(custom)?,
+        None => profile,
     };
-
-    let integration = Profile {
-        network: "integration".to_string(),
-        eth_url: format!("https://goerli.infura.io/v3/{token}"),
-        seq_url: "https://external.integration.starknet.io".to_string(),
-        eth_contract_address: "0xd5c325D183C592C94998000C5e0EED9e6655c020"
-            .to_string(),
+    let profile = profile.with(&overrides)?;
+    let profile = if args.flags.contains("no-eth") {
+        Profile {
+            eth_urls: vec![],
+            ..profile
+        }
+    } else {
+        profile
     };
 
-    let profile = match args.network {
-        name if name == "mainnet" => mainnet,
-        name if name == "testnet" => testnet,
-        name if name == "integration" => integration,
-        name => {
-            anyhow::bail!(
-                "Unsupported network: {}. Supported networks: mainnet, testnet, integration.",
-                name
+    let storage_path = &format!("{}/{}", args.data_dir, profile.network);
+    let cache_size = args.cache_size.unwrap_or(armada::cache::DEFAULT_CAPACITY);
+
+    match &args.command {
+        Command::Run | Command::Serve => (),
+        Command::Status => {
+            let db = Storage::with_cache(storage_path, cache_size).await;
+            let status = cmd::status(&db, &profile.network).await?;
+            println!("{}", serde_json::to_string_pretty(&status)?);
+            return Ok(());
+        }
+        Command::Reindex => {
+            let db = Storage::with_cache(storage_path, cache_size).await;
+            let blocks = cmd::reindex(&db).await?;
+            tracing::info!(blocks, "Reindex done");
+            return Ok(());
+        }
+        Command::Verify => {
+            let db = Storage::with_cache(storage_path, cache_size).await;
+            let (gaps, broken) = cmd::verify(&db).await?;
+            tracing::info!(
+                gaps = gaps.len(),
+                broken = broken.len(),
+                "Verify done"
             );
+            if !gaps.is_empty() || !broken.is_empty() {
+                anyhow::bail!(
+                    "Missing blocks: {gaps:?}, broken links: {broken:?}"
+                );
+            }
+            return Ok(());
         }
-    };
+        Command::Export { file, from, to } => {
+            let db = Storage::with_cache(storage_path, cache_size).await;
+            let blocks = cmd::export(&db, file, *from, *to).await?;
+            tracing::info!(blocks, file, "Export done");
+            return Ok(());
+        }
+        Command::Import { file } => {
+            let db = Storage::with_cache(storage_path, cache_size).await;
+            let blocks = cmd::import(&db, file).await?;
+            tracing::info!(blocks, file, "Import done");
+            return Ok(());
+        }
+    }
 
-    let storage_path = &format!("{}/{}", args.data_dir, profile.network);
     tracing::info!(
         network = profile.network,
+        chain_id = profile.chain_id,
         storage = storage_path,
         "Armada is starting..."
     );
 
-    let rpc_bind_addr = "0.0.0.0:9000";
-    let eth_poll_delay = 120 * SECOND;
-    let seq_poll_delay = 30 * SECOND;
-
     let config = Config::new(
         profile.network.clone(),
-        rpc_bind_addr.parse()?,
-        SECOND,
-        seq_poll_delay,
-        eth_poll_delay,
+        args.rpc_bind_addr,
+        args.src_poll_delay,
+        args.seq_poll_delay,
+        args.eth_poll_delay,
         profile.eth_contract_address.to_string(),
-    );
-
-    let eth = EthClient::new(&profile.eth_url);
-    let seq = SeqClient::new(&profile.seq_url);
-    let db = Storage::new(storage_path).await;
-    let shared = Shared::default();
-
-    let ctx = Context::new(eth, seq, shared, db, config);
-    let ctx = if is_metrics_reporting_enabled {
-        let builder = metrics_exporter_prometheus::PrometheusBuilder::new();
-        let handle = builder
-            .add_global_label("app", "armada")
-            .add_global_label("network", &profile.network)
-            .install_recorder()
-            .expect("failed to install prometheus recorder");
-        ctx.with_metrics(handle)
+    )
+    .with_chain_id(profile.chain_id.clone());
+    let config = if let Some(hash) = profile.genesis_hash.clone() {
+        config.with_genesis_hash(hash)
     } else {
-        ctx
+        config
     };
-    let source = Source::new(ctx.clone());
-    source.add("uptime", sync::poll_uptime, SECOND).await;
-    source.add("gateway", sync::poll_seq, seq_poll_delay).await;
-    source.add("ethereum", sync::poll_eth, eth_poll_delay).await;
-    let tx = source.tx();
-    let syncer = armada::sync::sync(source, sync::handler).await;
-
-    let range = {
-        let idx = ctx.db.blocks_index.read().await;
-        let min = idx.min()?.map(|val| val.into_u64());
-        let max = idx.max()?.map(|val| val.into_u64());
-        min.zip(max)
+    let config = if let Some(key) = profile.public_key.clone() {
+        config.with_public_key(key)
+    } else {
+        config
     };
-    if let Some((lo, hi)) = range {
-        {
-            let sync = &mut ctx.shared.lock().await.sync;
-            sync.lo = Some(lo);
-            sync.hi = Some(hi);
-        }
-        if lo > 0 {
-            use armada::db::Repo;
-            let key = U64::from_u64(lo);
-            let lo_block_hash =
-                ctx.db.blocks_index.read().await.lookup(&key)?.unwrap();
-            let lo_block =
-                ctx.db.blocks.get(&lo_block_hash.into_str()).await?.unwrap();
-            let lo_parent_hash = lo_block.block_header.parent_hash.0;
-            tx.send(Event::PullBlock(lo - 1, lo_parent_hash)).await.ok();
-        }
-        tracing::info!(synced=?(lo, hi), "Sync running");
+    let config = if let Some(block) = profile.eth_deploy_block {
+        config.with_eth_deploy_block(block)
     } else {
-        tracing::info!("Sync running");
+        config
+    };
+    let config = if let Some(keep) = args.prune_keep {
+        tracing::info!(keep, "Pruning enabled");
+        config.with_pruning(Pruning {
+            keep,
+            archive: args.flags.contains("archive"),
+        })
+    } else {
+        config
+    };
+    let config = if let Some(depth) = args.eth_confirmations {
+        config.with_eth_confirmations(depth)
+    } else {
+        config
+    };
+    let config = if let Some(timeout) = args.shutdown_timeout {
+        config.with_shutdown_timeout(timeout)
+    } else {
+        config
+    };
+    if let (Some(from), Some(to)) = (args.sync_from, args.sync_to) {
+        if from > to {
+            anyhow::bail!("Invalid sync range: {from}..{to}");
+        }
     }
+    let config = config.with_sync_range(args.sync_from, args.sync_to);
+    let config = if let Some(direction) = args.sync_direction {
+        config.with_direction(direction)
+    } else {
+        config
+    };
+    let config = if args.flags.contains("no-verify") {
+        tracing::warn!("Block hash verification disabled");
+        config.with_verify(false)
+    } else {
+        config
+    };
+    let config = config.with_admin(args.flags.contains("admin"));
+    let config = if let Some(max_handlers) = args.max_handlers {
+        config.with_max_handlers(max_handlers)
+    } else {
+        config
+    };
+    let config = if let Some(attempts) = args.retry_attempts {
+        let retry = Retry {
+            attempts: attempts.max(1),
+            ..config.retry.clone()
+        };
+        config.with_retry(retry)
+    } else {
+        config
+    };
 
-    {
-        let ctx = ctx.clone();
-        let tx = tx.clone();
-        tokio::spawn(async move {
-            let missing = armada::util::detect_gaps(ctx).await?;
-            if !missing.is_empty() {
-                tracing::info!(total = missing.len(), "Sync gap detected");
-            }
-            let zero = armada::api::gen::Felt::try_new("0x0")?;
-            for number in missing {
-                let event = Event::PullBlock(number, zero.clone());
-                tx.send(event).await?;
-                tokio::time::sleep(SECOND).await;
-            }
-            Ok::<(), anyhow::Error>(())
-        });
+    if args.command == Command::Serve {
+        // Another process keeps syncing the directory: follow its progress
+        let db = Storage::read_only(storage_path, cache_size).await?;
+        let node = Node::new(config, db);
+        let node = if is_metrics_reporting_enabled {
+            node.with_metrics(install_metrics(&profile.network))
+        } else {
+            node
+        };
+        until_signal(node.start().await?).await?;
+        return Ok(());
     }
 
-    {
-        let ctx = ctx.clone();
-        let tx = tx.clone();
-        let len = 2000;
-        tokio::spawn(async move {
-            if let Some((number, hash)) =
-                armada::util::check_chain(ctx, len).await?
-            {
-                tracing::info!(at = number, "Broken chain detected");
-                let event = Event::PullBlock(
-                    number,
-                    armada::api::gen::Felt::try_new(&hash)?,
-                );
-                tx.send(event).await?;
+    profile.validate()?;
+    let eth = profile
+        .eth_urls
+        .iter()
+        .map(|url| {
+            let eth = EthClient::new(url);
+            if let Some(auth) = args.eth_auth.as_ref() {
+                eth.with_auth(auth)
             } else {
-                tracing::info!(
-                    length = len,
-                    "Chain head validated successfully"
-                );
+                eth
             }
-            Ok::<(), anyhow::Error>(())
-        });
-    }
+        })
+        .collect::<Vec<_>>();
+    let eth = EthPool::new(eth).with_quorum(args.eth_quorum.unwrap_or(1));
+    let seq = SeqClient::new(&profile.seq_url);
+    let db = Storage::with_cache(storage_path, cache_size).await;
 
-    let addr: SocketAddr = rpc_bind_addr.parse()?;
-    let (addr, server) = armada::rpc::serve(&addr, ctx).await;
-    tracing::info!(at=?addr, "RPC server listening");
+    let node = Node::new(config, db).with_seq(seq);
+    let node = if is_metrics_reporting_enabled {
+        node.with_metrics(install_metrics(&profile.network))
+    } else {
+        node
+    };
+    if eth.is_empty() {
+        tracing::warn!("No L1 provider configured, L1 tracking disabled");
+        until_signal(node.start().await?).await?;
+    } else {
+        let providers = profile.eth_urls.len();
+        tracing::info!(providers, "L1 providers configured");
+        until_signal(node.with_eth(eth).start().await?).await?;
+    }
+    Ok(())
+}
 
-    syncer.done().await;
-    server.done().await;
+/// Run the node until SIGINT/SIGTERM, then shut it down gracefully.
+async fn until_signal<ETH, SEQ>(
+    handle: Handle<ETH, SEQ>,
+) -> anyhow::Result<()> {
+    let signal = node::signal().await?;
+    tracing::info!(signal, "Shutting down...");
+    handle.shutdown().await;
     Ok(())
 }
+
+fn install_metrics(network: &str) -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .add_global_label("app", "armada")
+        .add_global_label("network", network)
+        .install_recorder()
+        .expect("failed to install prometheus recorder")
+}
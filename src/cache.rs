@@ -0,0 +1,309 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::Arc,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::Mutex;
+
+use crate::db::Repo;
+
+pub const DEFAULT_CAPACITY: usize = 128;
+
+pub struct Lru<V> {
+    capacity: usize,
+    clock: u64,
+    items: HashMap<String, (u64, V)>,
+    order: BTreeMap<u64, String>,
+}
+
+impl<V: Clone> Lru<V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            clock: 0,
+            items: HashMap::with_capacity(capacity),
+            order: BTreeMap::new(),
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<V> {
+        let tick = self.tick();
+        let (used, val) = self.items.get_mut(key)?;
+        self.order.remove(&*used);
+        *used = tick;
+        self.order.insert(tick, key.to_string());
+        Some(val.clone())
+    }
+
+    pub fn has(&self, key: &str) -> bool {
+        self.items.contains_key(key)
+    }
+
+    pub fn put(&mut self, key: &str, val: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(key);
+        let tick = self.tick();
+        self.items.insert(key.to_string(), (tick, val));
+        self.order.insert(tick, key.to_string());
+        while self.items.len() > self.capacity {
+            if let Some((_, key)) = self.order.pop_first() {
+                self.items.remove(&key);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<V> {
+        let (used, val) = self.items.remove(key)?;
+        self.order.remove(&used);
+        Some(val)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+/// Cached values along with the number of writes (puts and deletes) made
+/// through the cache: a value read from the repo on a miss is cached only if
+/// no write happened meanwhile, so a concurrent write cannot be overwritten
+/// by a stale read.
+struct Cache<V> {
+    lru: Lru<V>,
+    writes: u64,
+}
+
+/// Read-through (and write-through) cache of decoded values on top of a `Repo`.
+#[derive(Clone)]
+pub struct CachedRepo<T, R> {
+    repo: R,
+    name: String,
+    cache: Arc<Mutex<Cache<T>>>,
+}
+
+impl<T, R> CachedRepo<T, R>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync,
+    R: Repo<T> + Send + Sync,
+{
+    pub fn wrap(repo: R, name: &str, capacity: usize) -> Self {
+        Self {
+            repo,
+            name: name.to_string(),
+            cache: Arc::new(Mutex::new(Cache {
+                lru: Lru::new(capacity),
+                writes: 0,
+            })),
+        }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.repo
+    }
+
+    pub async fn evict(&self, key: &str) {
+        let mut cache = self.cache.lock().await;
+        cache.writes += 1;
+        if cache.lru.remove(key).is_some() {
+            metrics::counter!("cache_evict", 1, "repo" => self.name.clone());
+        }
+        metrics::gauge!("cache_size", cache.lru.len() as f64, "repo" => self.name.clone());
+    }
+}
+
+#[async_trait::async_trait]
+impl<T, R> Repo<T> for CachedRepo<T, R>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync,
+    R: Repo<T> + Send + Sync,
+{
+    async fn new(base: &Path) -> Self {
+        let name = base
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Self::wrap(R::new(base).await, &name, DEFAULT_CAPACITY)
+    }
+
+    async fn has(&self, key: &str) -> anyhow::Result<bool> {
+        if self.cache.lock().await.lru.has(key) {
+            return Ok(true);
+        }
+        self.repo.has(key).await
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<T>> {
+        let writes = {
+            let mut cache = self.cache.lock().await;
+            if let Some(val) = cache.lru.get(key) {
+                metrics::counter!("cache_hit", 1, "repo" => self.name.clone());
+                return Ok(Some(val));
+            }
+            cache.writes
+        };
+        metrics::counter!("cache_miss", 1, "repo" => self.name.clone());
+
+        let opt = self.repo.get(key).await?;
+        if let Some(val) = opt.as_ref() {
+            let mut cache = self.cache.lock().await;
+            if cache.writes == writes {
+                cache.lru.put(key, val.clone());
+            }
+            metrics::gauge!("cache_size", cache.lru.len() as f64, "repo" => self.name.clone());
+        }
+        Ok(opt)
+    }
+
+    async fn del(&self, key: &str) -> anyhow::Result<Option<T>> {
+        // Evicted after the delete: a miss in between reads the old value
+        let val = self.repo.del(key).await?;
+        self.evict(key).await;
+        Ok(val)
+    }
+
+    async fn put(&self, key: &str, val: T) -> anyhow::Result<()> {
+        self.repo.put(key, val.clone()).await?;
+        let mut cache = self.cache.lock().await;
+        cache.writes += 1;
+        cache.lru.put(key, val);
+        metrics::gauge!("cache_size", cache.lru.len() as f64, "repo" => self.name.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    /// In-memory repo, a `get` can be held after reading the value.
+    #[derive(Clone, Default)]
+    struct MemRepo {
+        items: Arc<std::sync::Mutex<HashMap<String, i64>>>,
+        hold: Arc<Mutex<Option<(oneshot::Sender<()>, oneshot::Receiver<()>)>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Repo<i64> for MemRepo {
+        async fn new(_base: &Path) -> Self {
+            Self::default()
+        }
+
+        async fn has(&self, key: &str) -> anyhow::Result<bool> {
+            Ok(self.items.lock().unwrap().contains_key(key))
+        }
+
+        async fn get(&self, key: &str) -> anyhow::Result<Option<i64>> {
+            let val = self.items.lock().unwrap().get(key).cloned();
+            let hold = self.hold.lock().await.take();
+            if let Some((reached, release)) = hold {
+                reached.send(()).ok();
+                release.await.ok();
+            }
+            Ok(val)
+        }
+
+        async fn del(&self, key: &str) -> anyhow::Result<Option<i64>> {
+            Ok(self.items.lock().unwrap().remove(key))
+        }
+
+        async fn put(&self, key: &str, val: i64) -> anyhow::Result<()> {
+            self.items.lock().unwrap().insert(key.to_string(), val);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_repo_put_get_del() -> anyhow::Result<()> {
+        let repo = MemRepo::default();
+        let cached = CachedRepo::wrap(repo.clone(), "test", 2);
+
+        cached.put("a", 1).await?;
+        assert_eq!(cached.get("a").await?, Some(1));
+        assert!(cached.has("a").await?);
+
+        // A miss reads through and caches the value
+        repo.put("b", 2).await?;
+        assert_eq!(cached.get("b").await?, Some(2));
+        repo.del("b").await?;
+        assert_eq!(cached.get("b").await?, Some(2));
+
+        cached.put("a", 10).await?;
+        assert_eq!(cached.get("a").await?, Some(10));
+        assert_eq!(repo.get("a").await?, Some(10));
+
+        assert_eq!(cached.del("a").await?, Some(10));
+        assert_eq!(cached.get("a").await?, None);
+        assert!(!cached.has("a").await?);
+        assert_eq!(cached.del("a").await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cached_repo_stale_miss() -> anyhow::Result<()> {
+        let repo = MemRepo::default();
+        let cached = CachedRepo::wrap(repo.clone(), "test", 2);
+        repo.put("a", 1).await?;
+
+        // The miss reads the value, then the value is deleted
+        let (reached_tx, reached_rx) = oneshot::channel();
+        let (release_tx, release_rx) = oneshot::channel();
+        *repo.hold.lock().await = Some((reached_tx, release_rx));
+        let get = tokio::spawn({
+            let cached = cached.clone();
+            async move { cached.get("a").await }
+        });
+        reached_rx.await?;
+        assert_eq!(cached.del("a").await?, Some(1));
+        release_tx.send(()).ok();
+        assert_eq!(get.await??, Some(1));
+
+        assert_eq!(cached.get("a").await?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut lru = Lru::new(2);
+        lru.put("a", 1);
+        lru.put("b", 2);
+        assert_eq!(lru.get("a"), Some(1));
+        lru.put("c", 3);
+        assert_eq!(lru.len(), 2);
+        assert_eq!(lru.get("b"), None);
+        assert_eq!(lru.get("a"), Some(1));
+        assert_eq!(lru.get("c"), Some(3));
+    }
+
+    #[test]
+    fn test_lru_overwrite_and_remove() {
+        let mut lru = Lru::new(2);
+        lru.put("a", 1);
+        lru.put("a", 10);
+        assert_eq!(lru.len(), 1);
+        assert_eq!(lru.get("a"), Some(10));
+        assert_eq!(lru.remove("a"), Some(10));
+        assert!(lru.is_empty());
+    }
+
+    #[test]
+    fn test_lru_zero_capacity() {
+        let mut lru = Lru::new(0);
+        lru.put("a", 1);
+        assert!(lru.is_empty());
+        assert_eq!(lru.get("a"), None);
+    }
+}
@@ -1,12 +1,258 @@
 use std::{net::SocketAddr, time::Duration};
 
+use serde::Deserialize;
+
+#[derive(Clone, Debug)]
 pub struct Profile {
     pub network: String,
-    pub eth_url: String,
+    /// L1 JSON-RPC provider URLs (empty if no L1 is available).
+    pub eth_urls: Vec<String>,
     pub seq_url: String,
     pub eth_contract_address: String,
+    /// Hex-encoded chain id (e.g. `0x534e5f4d41494e` for `SN_MAIN`).
+    pub chain_id: String,
+    /// Expected hash of the genesis block (if known).
+    pub genesis_hash: Option<String>,
+    /// Public key of the sequencer that signs the blocks (if known).
+    pub public_key: Option<String>,
+    /// L1 block the core contract was deployed at (or any block before
+    /// it): the L1 logs are scanned from there. Without it only the
+    /// recent L1 blocks are scanned.
+    pub eth_deploy_block: Option<u64>,
+}
+
+impl Profile {
+    /// Profiles of public networks. Infura URLs are used for L1
+    /// if the token is provided.
+    pub fn builtin(network: &str, infura_token: Option<&str>) -> Option<Self> {
+        let infura = |host: &str| -> Vec<String> {
+            infura_token
+                .iter()
+                .map(|token| format!("https://{host}.infura.io/v3/{token}"))
+                .collect()
+        };
+        let profile = match network {
+            "mainnet" => Self {
+                network: network.to_string(),
+                eth_urls: infura("mainnet"),
+                seq_url: "https://alpha-mainnet.starknet.io".to_string(),
+                eth_contract_address:
+                    "0xc662c410C0ECf747543f5bA90660f6ABeBD9C8c4".to_string(),
+                chain_id: encode_chain_id("SN_MAIN").ok()?,
+                genesis_hash: Some(
+                    "0x47c3637b57c2b079b93c61539950c17e868a28f46cdef28f88521067f21e943"
+                        .to_string(),
+                ),
+                public_key: Some(
+                    "0x48253ff2c3bed7af18bde0b611b083b39445959102d4947c51c4db6aa4f4e58"
+                        .to_string(),
+                ),
+                eth_deploy_block: Some(13_500_000),
+            },
+            "testnet" => Self {
+                network: network.to_string(),
+                eth_urls: infura("goerli"),
+                seq_url: "https://alpha4.starknet.io".to_string(),
+                eth_contract_address:
+                    "0xde29d060D45901Fb19ED6C6e959EB22d8626708e".to_string(),
+                chain_id: encode_chain_id("SN_GOERLI").ok()?,
+                genesis_hash: Some(
+                    "0x7d328a71faf48c5c3857e99f20a77b18522480956d1cd5bff1ff2df3c8b427b"
+                        .to_string(),
+                ),
+                public_key: None,
+                eth_deploy_block: Some(4_500_000),
+            },
+            "integration" => Self {
+                network: network.to_string(),
+                eth_urls: infura("goerli"),
+                seq_url: "https://external.integration.starknet.io".to_string(),
+                eth_contract_address:
+                    "0xd5c325D183C592C94998000C5e0EED9e6655c020".to_string(),
+                chain_id: encode_chain_id("SN_GOERLI").ok()?,
+                genesis_hash: None,
+                public_key: None,
+                eth_deploy_block: None,
+            },
+            _ => return None,
+        };
+        Some(profile)
+    }
+
+    /// Empty profile of a custom network: at least the gateway URL and
+    /// the chain id must be configured before the profile can be used.
+    pub fn custom(network: &str) -> Self {
+        Self {
+            network: network.to_string(),
+            eth_urls: vec![],
+            seq_url: String::default(),
+            eth_contract_address: String::default(),
+            chain_id: String::default(),
+            genesis_hash: None,
+            public_key: None,
+            eth_deploy_block: None,
+        }
+    }
+
+    /// Override the profile with all values present in the config.
+    pub fn with(self, config: &ProfileConfig) -> anyhow::Result<Self> {
+        let eth_urls = config.eth_url.clone().map(Vec::from);
+        Ok(Self {
+            eth_urls: eth_urls.unwrap_or(self.eth_urls),
+            seq_url: config.seq_url.clone().unwrap_or(self.seq_url),
+            eth_contract_address: config
+                .eth_contract_address
+                .clone()
+                .unwrap_or(self.eth_contract_address),
+            chain_id: config
+                .chain_id
+                .as_deref()
+                .map(encode_chain_id)
+                .transpose()?
+                .unwrap_or(self.chain_id),
+            genesis_hash: config.genesis_hash.clone().or(self.genesis_hash),
+            public_key: config.public_key.clone().or(self.public_key),
+            eth_deploy_block: config.eth_deploy_block.or(self.eth_deploy_block),
+            ..self
+        })
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.seq_url.is_empty() {
+            anyhow::bail!(
+                "Unsupported network: {}. Supported networks: mainnet, testnet, integration (or set 'seq_url' for a custom network).",
+                self.network
+            );
+        }
+        if self.chain_id.is_empty() {
+            anyhow::bail!("Missing 'chain_id' for network: {}", self.network);
+        }
+        if !self.eth_urls.is_empty() && self.eth_contract_address.is_empty() {
+            anyhow::bail!(
+                "Missing 'eth_contract_address' for network: {}",
+                self.network
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Network profile values that can be set in the config file
+/// (`[profiles.<network>]` table) or on the command line.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub seq_url: Option<String>,
+    pub eth_url: Option<Urls>,
+    pub eth_contract_address: Option<String>,
+    /// Either hex-encoded (`0x...`) or a short string (`SN_MAIN`).
+    pub chain_id: Option<String>,
+    pub genesis_hash: Option<String>,
+    pub public_key: Option<String>,
+    pub eth_deploy_block: Option<u64>,
+}
+
+/// Single URL or a list of URLs.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Urls {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<Urls> for Vec<String> {
+    fn from(urls: Urls) -> Self {
+        match urls {
+            Urls::One(url) => vec![url],
+            Urls::Many(urls) => urls,
+        }
+    }
+}
+
+/// Encode the chain id as a hex string: hex-encoded values are kept,
+/// short strings (up to 31 ASCII characters) are encoded as bytes.
+pub fn encode_chain_id(chain_id: &str) -> anyhow::Result<String> {
+    if let Some(hex) = chain_id.strip_prefix("0x") {
+        if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("Invalid chain id: '{chain_id}'");
+        }
+        return Ok(chain_id.to_string());
+    }
+    if chain_id.is_empty() || chain_id.len() > 31 || !chain_id.is_ascii() {
+        anyhow::bail!("Invalid chain id: '{chain_id}'");
+    }
+    Ok(format!("0x{}", hex::encode(chain_id)))
+}
+
+#[derive(Clone, Debug)]
+pub struct Pruning {
+    /// Number of most recent blocks to keep full history for.
+    pub keep: u64,
+    /// Move pruned block and state files to the archive instead of deleting.
+    pub archive: bool,
+}
+
+/// Default number of L1 blocks on top of the L1 state update log
+/// before it is considered final.
+pub const DEFAULT_ETH_CONFIRMATIONS: u64 = 12;
+
+/// Failed sync events are retried with exponential backoff (plus jitter)
+/// and moved to the dead-letter store after the last attempt.
+#[derive(Clone, Debug)]
+pub struct Retry {
+    pub attempts: u32,
+    /// Delay before the first retry (doubled on each next one).
+    pub delay: Duration,
+    pub max_delay: Duration,
+}
+
+pub const DEFAULT_RETRY_ATTEMPTS: u32 = 10;
+pub const DEFAULT_RETRY_MAX_DELAY: Duration = Duration::from_secs(3600);
+
+impl Retry {
+    /// Delay before the given (1-based) retry: half of it is fixed,
+    /// the other half is random to spread retries of concurrent failures.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(16);
+        let delay = self.delay.saturating_mul(factor).min(self.max_delay);
+        let jitter = {
+            use std::hash::{BuildHasher, Hasher};
+            let random = std::collections::hash_map::RandomState::new()
+                .build_hasher()
+                .finish();
+            (random % 1000) as u32
+        };
+        delay / 2 + delay / 2 * jitter / 1000
+    }
+}
+
+/// Order in which blocks are synced: from the chain head down to genesis
+/// (backward) or from genesis (or the highest stored block) up (forward).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    #[default]
+    Backward,
+    Forward,
 }
 
+impl std::str::FromStr for Direction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "backward" => Ok(Self::Backward),
+            "forward" => Ok(Self::Forward),
+            _ => anyhow::bail!("expected 'backward' or 'forward'"),
+        }
+    }
+}
+
+/// Maximum number of sync events handled concurrently.
+pub const DEFAULT_MAX_HANDLERS: usize = 16;
+
+/// Time given to in-flight sync handlers and RPC requests on shutdown.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub network: String,
@@ -15,6 +261,28 @@ pub struct Config {
     pub seq_poll_delay: Duration,
     pub eth_poll_delay: Duration,
     pub ethereum_contract_address: String,
+    pub pruning: Option<Pruning>,
+    pub eth_confirmations: u64,
+    pub chain_id: String,
+    pub genesis_hash: Option<String>,
+    pub shutdown_timeout: Duration,
+    pub retry: Retry,
+    pub max_handlers: usize,
+    /// Lowest block to sync (parent walking stops here).
+    pub sync_from: Option<u64>,
+    /// Highest block to sync (the head poller stops here).
+    pub sync_to: Option<u64>,
+    pub direction: Direction,
+    /// Recompute and check block and transaction hashes before saving,
+    /// maintain the state tries and check the state roots.
+    pub verify: bool,
+    /// Sequencer public key: when set (and verification is enabled),
+    /// blocks without a valid signature are refused.
+    pub public_key: Option<String>,
+    /// L1 block to scan the logs from when nothing is scanned yet.
+    pub eth_deploy_block: Option<u64>,
+    /// Serve the admin RPC methods (dead letter retry and discard).
+    pub admin: bool,
 }
 
 impl Config {
@@ -33,6 +301,189 @@ impl Config {
             seq_poll_delay,
             eth_poll_delay,
             ethereum_contract_address,
+            pruning: None,
+            eth_confirmations: DEFAULT_ETH_CONFIRMATIONS,
+            // Never derived from the network name: see `with_chain_id`
+            chain_id: String::default(),
+            genesis_hash: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            retry: Retry {
+                attempts: DEFAULT_RETRY_ATTEMPTS,
+                delay: 10 * src_poll_delay,
+                max_delay: DEFAULT_RETRY_MAX_DELAY,
+            },
+            max_handlers: DEFAULT_MAX_HANDLERS,
+            sync_from: None,
+            sync_to: None,
+            direction: Direction::default(),
+            verify: true,
+            public_key: None,
+            eth_deploy_block: None,
+            admin: false,
+        }
+    }
+
+    pub fn with_pruning(self, pruning: Pruning) -> Self {
+        Self {
+            pruning: Some(pruning),
+            ..self
+        }
+    }
+
+    pub fn with_eth_confirmations(self, eth_confirmations: u64) -> Self {
+        Self {
+            eth_confirmations,
+            ..self
+        }
+    }
+
+    /// Hex-encoded chain id: required to verify blocks.
+    pub fn with_chain_id(self, chain_id: String) -> Self {
+        Self { chain_id, ..self }
+    }
+
+    pub fn with_shutdown_timeout(self, shutdown_timeout: Duration) -> Self {
+        Self {
+            shutdown_timeout,
+            ..self
+        }
+    }
+
+    pub fn with_retry(self, retry: Retry) -> Self {
+        Self { retry, ..self }
+    }
+
+    pub fn with_verify(self, verify: bool) -> Self {
+        Self { verify, ..self }
+    }
+
+    pub fn with_admin(self, admin: bool) -> Self {
+        Self { admin, ..self }
+    }
+
+    pub fn with_max_handlers(self, max_handlers: usize) -> Self {
+        Self {
+            max_handlers: max_handlers.max(1),
+            ..self
+        }
+    }
+
+    pub fn with_sync_range(self, from: Option<u64>, to: Option<u64>) -> Self {
+        Self {
+            sync_from: from,
+            sync_to: to,
+            ..self
+        }
+    }
+
+    pub fn with_direction(self, direction: Direction) -> Self {
+        Self { direction, ..self }
+    }
+
+    pub fn with_genesis_hash(self, genesis_hash: String) -> Self {
+        Self {
+            genesis_hash: Some(genesis_hash),
+            ..self
+        }
+    }
+
+    pub fn with_public_key(self, public_key: String) -> Self {
+        Self {
+            public_key: Some(public_key),
+            ..self
+        }
+    }
+
+    pub fn with_eth_deploy_block(self, eth_deploy_block: u64) -> Self {
+        Self {
+            eth_deploy_block: Some(eth_deploy_block),
+            ..self
         }
     }
+
+    /// State tries are applied from genesis: they are maintained (and the
+    /// state roots checked) only if the synced range starts at genesis and
+    /// no state diffs are pruned.
+    pub fn state_tries(&self) -> bool {
+        self.verify
+            && self.sync_from.unwrap_or_default() == 0
+            && self.pruning.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_chain_id() -> anyhow::Result<()> {
+        assert_eq!(encode_chain_id("SN_MAIN")?, "0x534e5f4d41494e");
+        assert_eq!(encode_chain_id("SN_GOERLI")?, "0x534e5f474f45524c49");
+        assert_eq!(encode_chain_id("0x1234")?, "0x1234");
+        assert!(encode_chain_id("0xabcz").is_err());
+        assert!(encode_chain_id("").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let retry = Retry {
+            attempts: 5,
+            delay: Duration::from_secs(10),
+            max_delay: Duration::from_secs(60),
+        };
+        let first = retry.backoff(1);
+        assert!(first >= Duration::from_secs(5));
+        assert!(first <= Duration::from_secs(10));
+        let third = retry.backoff(3);
+        assert!(third >= Duration::from_secs(20));
+        assert!(third <= Duration::from_secs(40));
+        assert!(retry.backoff(100) <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_state_tries() {
+        let config = Config::new(
+            "test".to_string(),
+            ([127, 0, 0, 1], 0).into(),
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            "0x0".to_string(),
+        );
+        assert!(config.state_tries());
+        assert!(!config.clone().with_verify(false).state_tries());
+        assert!(!config.clone().with_sync_range(Some(10), None).state_tries());
+        assert!(config.with_sync_range(Some(0), None).state_tries());
+    }
+
+    #[test]
+    fn test_custom_profile() -> anyhow::Result<()> {
+        let config: ProfileConfig =
+            serde_json::from_value(serde_json::json!({
+                "seq_url": "http://localhost:5050",
+                "eth_url": "http://localhost:8545",
+                "eth_contract_address": "0x123",
+                "chain_id": "SN_DEVNET",
+                "public_key": "0x123",
+            }))?;
+        let profile = Profile::custom("devnet").with(&config)?;
+        profile.validate()?;
+        assert_eq!(profile.seq_url, "http://localhost:5050");
+        assert_eq!(profile.eth_urls, vec!["http://localhost:8545"]);
+        assert_eq!(profile.chain_id, encode_chain_id("SN_DEVNET")?);
+        assert_eq!(profile.public_key, Some("0x123".to_string()));
+
+        assert!(Profile::custom("devnet").validate().is_err());
+        // The chain id is never derived from the network name
+        let config = ProfileConfig {
+            seq_url: Some("http://localhost:5050".to_string()),
+            ..Default::default()
+        };
+        let profile = Profile::custom("SN_DEVNET").with(&config)?;
+        assert!(profile.validate().is_err());
+        assert!(Profile::builtin("mainnet", None).is_some());
+        assert!(Profile::builtin("devnet", None).is_none());
+        Ok(())
+    }
 }
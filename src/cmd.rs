@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use yakvdb::typed::DB;
+
+use crate::{
+    api::gen::{BlockWithTxs, Felt},
+    db::{status, Repo, Storage},
+    seq::dto,
+    sync::{save_block, save_state},
+    util::{U256, U64},
+};
+
+/// Line of the export file: the block and its state update (if stored).
+#[derive(Deserialize, Serialize)]
+pub struct Record {
+    pub block: BlockWithTxs,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<dto::StateUpdate>,
+}
+
+/// Stored range of blocks, along with the L1 progress.
+#[derive(Debug, Default, Serialize)]
+pub struct Status {
+    pub network: String,
+    pub lo: Option<u64>,
+    pub hi: Option<u64>,
+    pub accepted_on_l1: Option<u64>,
+    pub settled_on_l1: Option<u64>,
+}
+
+/// Numbers and hashes of all stored blocks in ascending order.
+async fn stored_blocks(
+    db: &Storage,
+    from: Option<u64>,
+    to: Option<u64>,
+) -> anyhow::Result<Vec<(u64, U256)>> {
+    let idx = db.blocks_index.read().await;
+    let lo = from.map(U64::from_u64).or(idx.min()?);
+    let hi = to.unwrap_or(u64::MAX);
+
+    let mut next = match lo {
+        Some(lo) if idx.lookup(&lo)?.is_some() => Some(lo),
+        Some(lo) => idx.above(&lo)?,
+        None => None,
+    };
+    let mut ret = Vec::new();
+    while let Some(key) = next {
+        let number = key.into_u64();
+        if number > hi {
+            break;
+        }
+        if let Some(hash) = idx.lookup(&key)? {
+            ret.push((number, hash));
+        }
+        next = idx.above(&key)?;
+    }
+    Ok(ret)
+}
+
+pub async fn status(db: &Storage, network: &str) -> anyhow::Result<Status> {
+    let lo = db.blocks_index.read().await.min()?.map(|lo| lo.into_u64());
+    let hi = db.blocks_index.read().await.max()?.map(|hi| hi.into_u64());
+    let accepted_on_l1 = {
+        let idx = db.statuses_index.read().await;
+        let max = idx.max()?;
+        let upgraded = max.as_ref().map(|key| idx.lookup(key)).transpose()?;
+        max.zip(upgraded.flatten())
+            .filter(|(_, flag)| flag.into_u64() == status::ACCEPTED_ON_L1)
+            .map(|(key, _)| key.into_u64())
+    };
+    let settled_on_l1 = db
+        .settlements_index
+        .read()
+        .await
+        .max()?
+        .map(|key| key.into_u64());
+    Ok(Status {
+        network: network.to_string(),
+        lo,
+        hi,
+        accepted_on_l1,
+        settled_on_l1,
+    })
+}
+
+/// Re-apply indexing of all stored blocks and states.
+/// Returns the number of re-indexed blocks.
+pub async fn reindex(db: &Storage) -> anyhow::Result<u64> {
+    let mut db = db.clone();
+    let blocks = stored_blocks(&db, None, None).await?;
+    let total = blocks.len();
+    let mut count = 0;
+    for (number, hash) in blocks {
+        let key = hash.into_str();
+        let block = match db.blocks.get(&key).await? {
+            Some(block) => block,
+            None => {
+                tracing::warn!(number, hash = key, "Block is missing");
+                continue;
+            }
+        };
+        let hash = Felt::try_new(&key)?;
+        db.hashes_index
+            .write()
+            .await
+            .insert(&U256::from_hex(&key)?, U64::from_u64(number))?;
+        save_block(&mut db, hash.clone(), block).await?;
+        if let Some(state) = db.states.get(&key).await? {
+            save_state(&mut db, hash, number, state).await?;
+        }
+
+        count += 1;
+        if count % 1000 == 0 {
+            tracing::info!(count, total, "Reindexing");
+        }
+    }
+    Ok(count)
+}
+
+/// Walk the stored chain and report missing blocks (gaps) and blocks
+/// which parent hash does not match the stored parent (broken links).
+pub async fn verify(db: &Storage) -> anyhow::Result<(Vec<u64>, Vec<u64>)> {
+    let blocks = stored_blocks(db, None, None).await?;
+    let mut gaps = Vec::new();
+    let mut broken = Vec::new();
+    for pair in blocks.windows(2) {
+        let (parent_number, parent_hash) = &pair[0];
+        let (number, hash) = &pair[1];
+        if number - parent_number > 1 {
+            gaps.extend(parent_number + 1..*number);
+            continue;
+        }
+        let block = db.blocks.get(&hash.into_str()).await?;
+        let parent = block.map(|block| block.block_header.parent_hash.0);
+        let linked = parent
+            .map(|parent| U256::from_hex(parent.as_ref()))
+            .transpose()?
+            .map(|parent| &parent == parent_hash)
+            .unwrap_or_default();
+        if !linked {
+            broken.push(*number);
+        }
+    }
+    Ok((gaps, broken))
+}
+
+/// Write stored blocks (and states) in the given range as JSON lines.
+/// Returns the number of exported blocks.
+pub async fn export(
+    db: &Storage,
+    path: &str,
+    from: Option<u64>,
+    to: Option<u64>,
+) -> anyhow::Result<u64> {
+    let file = tokio::fs::File::create(path).await?;
+    let mut out = BufWriter::new(file);
+    let mut count = 0;
+    for (number, hash) in stored_blocks(db, from, to).await? {
+        let key = hash.into_str();
+        let block = match db.blocks.get(&key).await? {
+            Some(block) => block,
+            None => {
+                tracing::warn!(number, hash = key, "Block is missing");
+                continue;
+            }
+        };
+        let state = db.states.get(&key).await?;
+        let line = serde_json::to_string(&Record { block, state })?;
+        out.write_all(line.as_bytes()).await?;
+        out.write_all(b"\n").await?;
+        count += 1;
+    }
+    out.flush().await?;
+    Ok(count)
+}
+
+/// Read blocks (and states) from JSON lines and store them.
+/// Returns the number of imported blocks.
+pub async fn import(db: &Storage, path: &str) -> anyhow::Result<u64> {
+    let mut db = db.clone();
+    let file = tokio::fs::File::open(path).await?;
+    let mut lines = BufReader::new(file).lines();
+    let mut count = 0;
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Record { block, state } = serde_json::from_str(&line)?;
+        let number = *block.block_header.block_number.as_ref() as u64;
+        let hash = block.block_header.block_hash.0.clone();
+
+        save_block(&mut db, hash.clone(), block).await?;
+        if let Some(state) = state {
+            save_state(&mut db, hash.clone(), number, state).await?;
+        }
+        let key = U64::from_u64(number);
+        let val = U256::from_hex(hash.as_ref())?;
+        db.blocks_index.write().await.insert(&key, val.clone())?;
+        db.hashes_index.write().await.insert(&val, key)?;
+        count += 1;
+    }
+    Ok(count)
+}
@@ -1,28 +1,49 @@
 use std::sync::Arc;
 
-use tokio::{sync::Mutex, time::Instant};
+use tokio::{
+    sync::{broadcast, mpsc, Mutex},
+    time::Instant,
+};
 use yakvdb::typed::DB;
 
 use crate::{
     api::gen::*,
     cfg::Config,
     db::{
-        AddressAndNumber, AddressWithKeyAndNumber, BlockAndIndex, Repo, Storage,
+        activity, status, AddressAndNumber, AddressWithKeyAndNumber,
+        BlockAndIndex, MessageAndTx, Repo, Storage, TrieNode, TxAndMessage,
     },
     eth::EthApi,
     seq::SeqApi,
-    util::{get_txn_receipt, map_class, map_state_update, tx_hash, U256, U64},
+    sub::{self, Notification},
+    sync::{self, dead_letter_ids, remove_dead_letter},
+    trie,
+    util::{
+        get_txn_receipt, map_class, map_state_update, tx_hash, tx_sender, U256,
+        U64,
+    },
 };
 
 #[derive(Clone, Debug, Default)]
 pub struct Sync {
     pub lo: Option<u64>,
     pub hi: Option<u64>,
+    pub pruning: bool,
+    /// Next L1 block to scan for state update logs.
+    pub eth: Option<u64>,
+    /// Latest block number reported by the gateway.
+    pub head: Option<u64>,
+    /// Height below which the history is pruned (if anything is pruned).
+    pub pruned: Option<u64>,
+    /// Stored block (number and hash) whose state root did not match.
+    pub mismatch: Option<(u64, U256)>,
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct Shared {
     pub sync: Sync,
+    /// Sender of sync events (if the sync is running).
+    pub events: Option<mpsc::Sender<sync::Event>>,
 }
 
 #[derive(Clone)]
@@ -34,6 +55,8 @@ pub struct Context<ETH, SEQ> {
     pub shared: Arc<Mutex<Shared>>,
     pub config: Config,
     pub metrics: Option<metrics_exporter_prometheus::PrometheusHandle>,
+    /// Notifications for the WebSocket subscriptions.
+    pub notify: broadcast::Sender<Notification>,
 }
 
 impl<ETH, SEQ> Context<ETH, SEQ>
@@ -56,6 +79,7 @@ where
             shared: Arc::new(Mutex::new(shared)),
             config,
             metrics: None,
+            notify: broadcast::channel(sub::CAPACITY).0,
         }
     }
 
@@ -73,6 +97,147 @@ where
         self.shared.clone()
     }
 
+    /// Publish the notification to the subscribers (if any).
+    pub fn notify(&self, notification: Notification) {
+        if self.notify.receiver_count() > 0 {
+            self.notify.send(notification).ok();
+        }
+    }
+
+    /// Admin methods change the sync state: they are served only when
+    /// enabled, and never over a read-only storage.
+    fn check_admin(&self) -> std::result::Result<(), iamgroot::jsonrpc::Error> {
+        if !self.config.admin {
+            return Err(crate::api::gen::error::ADMIN_DISABLED.into());
+        }
+        if self.db.read_only {
+            return Err(crate::api::gen::error::READ_ONLY.into());
+        }
+        Ok(())
+    }
+
+    /// Lowest block number with full history available (if pruning is on).
+    pub async fn horizon(&self) -> Option<u64> {
+        let pruning = self.config.pruning.as_ref()?;
+        let hi = self.shared.lock().await.sync.hi?;
+        Some(hi.saturating_sub(pruning.keep))
+    }
+
+    /// Lowest block to sync: the pruning horizon or the start of the
+    /// sync range (whichever is higher).
+    pub async fn floor(&self) -> Option<u64> {
+        self.horizon().await.max(self.config.sync_from)
+    }
+
+    async fn check_pruned(
+        &self,
+        block_number: u64,
+    ) -> std::result::Result<(), iamgroot::jsonrpc::Error> {
+        match self.shared.lock().await.sync.pruned {
+            Some(pruned) if block_number < pruned => {
+                Err(iamgroot::jsonrpc::Error::new(
+                    -65001,
+                    format!(
+                        "Block {block_number} is pruned, history is kept since block {pruned}"
+                    ),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Upgrade the status of the stored block if it is accepted on L1.
+    async fn apply_status(
+        &self,
+        block: &mut BlockWithTxs,
+    ) -> std::result::Result<(), iamgroot::jsonrpc::Error> {
+        let number = *block.block_header.block_number.as_ref() as u64;
+        let upgraded = self
+            .db
+            .statuses_index
+            .read()
+            .await
+            .lookup(&U64::from_u64(number))?
+            .map(|status| status.into_u64());
+        if upgraded == Some(status::ACCEPTED_ON_L1) {
+            block.status = BlockStatus::AcceptedOnL1;
+        }
+        Ok(())
+    }
+
+    /// Resolve the L2 transaction of the message (and its finality status).
+    async fn get_message_status(
+        &self,
+        message: U256,
+    ) -> std::result::Result<MessageStatus, iamgroot::jsonrpc::Error> {
+        let message_hash = NumAsHex::try_new(&message.into_str())?;
+        // The same message can be sent by more than one transaction:
+        // prefer the first one that is still stored.
+        let mut txs = Vec::new();
+        {
+            let db = self.db.messages_index.read().await;
+            let first = MessageAndTx::from(message.clone(), U256::default());
+            let mut next = if db.lookup(&first)?.is_some() {
+                Some(first)
+            } else {
+                db.above(&first)?
+            };
+            while let Some(key) = next {
+                if key.message() != message {
+                    break;
+                }
+                txs.push(key.tx());
+                next = db.above(&key)?;
+            }
+        }
+        if txs.is_empty() {
+            // L1->L2 message was not consumed on L2 (yet)
+            return Ok(MessageStatus {
+                finality_status: None,
+                message_hash,
+                transaction_hash: None,
+            });
+        }
+
+        let mut tx = txs[0].clone();
+        let mut block = None;
+        for candidate in txs {
+            let found = self.db.txs_index.read().await.lookup(&candidate)?;
+            if let Some(block_and_index) = found {
+                tx = candidate;
+                block = Some(block_and_index.block());
+                break;
+            }
+        }
+        let number = match block {
+            Some(block) => self.db.hashes_index.read().await.lookup(&block)?,
+            None => None,
+        };
+        let finality_status = match number {
+            Some(number) => {
+                let upgraded = self
+                    .db
+                    .statuses_index
+                    .read()
+                    .await
+                    .lookup(&number)?
+                    .map(|status| status.into_u64());
+                if upgraded == Some(status::ACCEPTED_ON_L1) {
+                    Some(TxnStatus::AcceptedOnL1)
+                } else {
+                    Some(TxnStatus::AcceptedOnL2)
+                }
+            }
+            None => None,
+        };
+
+        Ok(MessageStatus {
+            finality_status,
+            message_hash,
+            transaction_hash: Some(TxnHash(Felt::try_new(&tx.into_str())?)),
+        })
+    }
+
     async fn get_block_number(
         &self,
         block_id: BlockId,
@@ -83,10 +248,8 @@ where
             }
             BlockId::BlockHash { block_hash } => {
                 let key = block_hash.0.as_ref();
-                let block = self
-                    .db
-                    .blocks
-                    .get(key)
+                self.db
+                    .block_number(key)
                     .await
                     .map_err(|e| {
                         iamgroot::jsonrpc::Error::new(
@@ -94,8 +257,7 @@ where
                             format!("Failed to fetch block '{}': {:?}", key, e),
                         )
                     })?
-                    .ok_or(crate::api::gen::error::BLOCK_NOT_FOUND)?;
-                *block.block_header.block_number.as_ref() as u64
+                    .ok_or(crate::api::gen::error::BLOCK_NOT_FOUND)?
             }
             BlockId::BlockTag(BlockTag::Latest) => u64::MAX,
             _ => {
@@ -105,6 +267,7 @@ where
                 ));
             }
         };
+        self.check_pruned(block_number).await?;
         Ok(block_number)
     }
 }
@@ -153,7 +316,9 @@ where
         let hash = match block_id {
             BlockId::BlockHash { block_hash } => block_hash,
             BlockId::BlockNumber { block_number } => {
-                let key = U64::from_u64(*block_number.as_ref() as u64);
+                let number = *block_number.as_ref() as u64;
+                self.check_pruned(number).await?;
+                let key = U64::from_u64(number);
                 let idx = self.db.blocks_index.read().await;
                 let hash = idx
                     .lookup(&key)
@@ -181,6 +346,7 @@ where
             })?
             .ok_or(crate::api::gen::error::BLOCK_NOT_FOUND)?;
         block.receipts.clear(); // TODO FIXME: proper serialization of tx receipt!
+        self.apply_status(&mut block).await?;
 
         Ok(GetBlockWithTxsResult::BlockWithTxs(block))
     }
@@ -193,7 +359,9 @@ where
         let hash = match block_id {
             BlockId::BlockHash { block_hash } => block_hash,
             BlockId::BlockNumber { block_number } => {
-                let key = U64::from_u64(*block_number.as_ref() as u64);
+                let number = *block_number.as_ref() as u64;
+                self.check_pruned(number).await?;
+                let key = U64::from_u64(number);
                 let idx = self.db.blocks_index.read().await;
                 let hash = idx
                     .lookup(&key)
@@ -237,10 +405,8 @@ where
             }
             BlockId::BlockHash { block_hash } => {
                 let key = block_hash.0.as_ref();
-                let block = self
-                    .db
-                    .blocks
-                    .get(key)
+                self.db
+                    .block_number(key)
                     .await
                     .map_err(|e| {
                         iamgroot::jsonrpc::Error::new(
@@ -248,14 +414,14 @@ where
                             format!("Failed to fetch block '{}': {:?}", key, e),
                         )
                     })?
-                    .ok_or(crate::api::gen::error::BLOCK_NOT_FOUND)?;
-                *block.block_header.block_number.as_ref() as u64
+                    .ok_or(crate::api::gen::error::BLOCK_NOT_FOUND)?
             }
             BlockId::BlockTag(BlockTag::Latest) => u64::MAX,
             _ => {
                 return Err(crate::api::gen::error::BLOCK_NOT_FOUND.into());
             }
         };
+        self.check_pruned(block_number).await?;
 
         let address =
             U256::from_hex(contract_address.0.as_ref()).map_err(|e| {
@@ -376,7 +542,9 @@ where
         let hash = match block_id {
             BlockId::BlockHash { block_hash } => block_hash,
             BlockId::BlockNumber { block_number } => {
-                let key = U64::from_u64(*block_number.as_ref() as u64);
+                let number = *block_number.as_ref() as u64;
+                self.check_pruned(number).await?;
+                let key = U64::from_u64(number);
                 let idx = self.db.blocks_index.read().await;
                 let hash = idx
                     .lookup(&key)
@@ -442,7 +610,7 @@ where
         let tx_index = block_and_index.index().into_u64() as usize;
 
         let key = &block_hash.into_str();
-        let block: BlockWithTxs = self
+        let mut block: BlockWithTxs = self
             .db
             .blocks
             .get(key)
@@ -454,6 +622,7 @@ where
                 )
             })?
             .ok_or(crate::api::gen::error::BLOCK_NOT_FOUND)?;
+        self.apply_status(&mut block).await?;
 
         let txn_receipt = get_txn_receipt(block, tx_index);
         Ok(txn_receipt)
@@ -461,10 +630,26 @@ where
 
     async fn getClass(
         &self,
-        _block_id: BlockId,
+        block_id: BlockId,
         class_hash: Felt,
     ) -> std::result::Result<GetClassResult, iamgroot::jsonrpc::Error> {
-        // TODO: Class hash does not relly depend on block. Does it?
+        let block_number = match block_id {
+            BlockId::BlockTag(BlockTag::Pending) => u64::MAX,
+            block_id => self.get_block_number(block_id).await?,
+        };
+        let key = U256::from_hex(class_hash.as_ref()).map_err(|e| {
+            iamgroot::jsonrpc::Error::new(
+                -65000,
+                format!("Failed to read class hash: '{e}'"),
+            )
+        })?;
+        let declared = self.db.declarations_index.read().await.lookup(&key)?;
+        if let Some(declared) = declared {
+            // The class is not yet declared at the requested block
+            if declared.number().into_u64() > block_number {
+                return Err(crate::api::gen::error::CLASS_HASH_NOT_FOUND.into());
+            }
+        }
 
         let class = self
             .db
@@ -623,7 +808,7 @@ where
     async fn chainId(
         &self,
     ) -> std::result::Result<ChainId, iamgroot::jsonrpc::Error> {
-        ChainId::try_new("0xCAFEBABE") // TODO: make it right
+        ChainId::try_new(&self.config.chain_id)
     }
 
     async fn pendingTransactions(
@@ -672,13 +857,34 @@ where
         let hi_num = NumAsHex::try_new(&hi.into_str())?;
         let lo_hash = BlockHash(Felt::try_new(&lo_hash.into_str())?);
         let lo_num = NumAsHex::try_new(&lo.into_str())?;
+
+        // Progress is reported against the sync range (if limited): the
+        // hash of a range bound that is not synced yet is reported as zero.
+        let bound = |number: u64| async move {
+            let key = U64::from_u64(number);
+            let hash = self.db.blocks_index.read().await.lookup(&key)?;
+            let hash = hash.map(|hash| hash.into_str());
+            let hash = Felt::try_new(hash.as_deref().unwrap_or("0x0"))?;
+            let num = NumAsHex::try_new(&format!("{number:#x}"))?;
+            Ok::<_, iamgroot::jsonrpc::Error>((BlockHash(hash), num))
+        };
+        let (starting_block_hash, starting_block_num) =
+            match self.config.sync_from {
+                Some(from) => bound(from).await?,
+                None => (lo_hash, lo_num),
+            };
+        let (highest_block_hash, highest_block_num) = match self.config.sync_to
+        {
+            Some(to) => bound(to).await?,
+            None => (hi_hash.clone(), hi_num.clone()),
+        };
         Ok(SyncingSyncing::SyncStatus(SyncStatus {
-            current_block_hash: hi_hash.clone(),
-            current_block_num: hi_num.clone(),
-            highest_block_hash: hi_hash,
-            highest_block_num: hi_num,
-            starting_block_hash: lo_hash,
-            starting_block_num: lo_num,
+            current_block_hash: hi_hash,
+            current_block_num: hi_num,
+            highest_block_hash,
+            highest_block_num,
+            starting_block_hash,
+            starting_block_num,
         }))
     }
 
@@ -915,6 +1121,500 @@ where
     > {
         not_implemented()
     }
+
+    async fn getAccountActivity(
+        &self,
+        filter: AccountActivityFilter,
+    ) -> std::result::Result<AccountActivityChunk, iamgroot::jsonrpc::Error>
+    {
+        if filter.chunk_size < 1 {
+            return Err(iamgroot::jsonrpc::Error::new(
+                -1,
+                format!("Invalid chunk size: {}", filter.chunk_size),
+            ));
+        }
+        if filter.chunk_size > 1000 {
+            return Err(crate::api::gen::error::PAGE_SIZE_TOO_BIG.into());
+        }
+        let chunk_size = filter.chunk_size as usize;
+
+        let addr = U256::from_hex(filter.address.0.as_ref()).map_err(|e| {
+            iamgroot::jsonrpc::Error::new(
+                -65000,
+                format!("Failed to read address: '{e}'"),
+            )
+        })?;
+
+        let lo = if let Some(from_block) = filter.from_block {
+            self.get_block_number(from_block).await?
+        } else {
+            0
+        };
+
+        let hi = if let Some(to_block) = filter.to_block {
+            self.get_block_number(to_block).await?
+        } else {
+            u64::MAX
+        };
+
+        // The continuation token is the number of the next block to return
+        let lo = if let Some(token) = filter.continuation_token {
+            token
+                .parse::<u64>()
+                .ok()
+                .filter(|number| *number >= lo)
+                .ok_or(crate::api::gen::error::INVALID_CONTINUATION_TOKEN)?
+        } else {
+            lo
+        };
+
+        let mut found: Vec<(u64, u64)> = Vec::new();
+        {
+            let db = self.db.accounts_index.read().await;
+            let current =
+                AddressAndNumber::from(addr.clone(), U64::from_u64(lo));
+            let mut next = if db.lookup(&current)?.is_some() {
+                Some(current)
+            } else {
+                db.above(&current)?
+            };
+            while let Some(key) = next {
+                let number = key.number().into_u64();
+                if key.address() != addr || number > hi {
+                    break;
+                }
+                let flags = db.lookup(&key)?.map(|flags| flags.into_u64());
+                found.push((number, flags.unwrap_or_default()));
+                if found.len() > chunk_size {
+                    break;
+                }
+                next = db.above(&key)?;
+            }
+        }
+        tracing::debug!(
+            method = "getAccountActivity",
+            "Entries found: {}",
+            found.len()
+        );
+
+        let continuation_token = if found.len() > chunk_size {
+            found.pop().map(|(number, _)| number.to_string())
+        } else {
+            None
+        };
+
+        let mut items = Vec::with_capacity(found.len());
+        for (number, flags) in found {
+            let hash = self
+                .db
+                .blocks_index
+                .read()
+                .await
+                .lookup(&U64::from_u64(number))?
+                .ok_or(crate::api::gen::error::BLOCK_NOT_FOUND)?;
+
+            let mut transactions = Vec::new();
+            if flags & (activity::SENDER | activity::EVENT) > 0 {
+                let block = self
+                    .db
+                    .blocks
+                    .get(&hash.into_str())
+                    .await?
+                    .ok_or(crate::api::gen::error::BLOCK_NOT_FOUND)?;
+
+                let is_addr = |felt: &Felt| {
+                    U256::from_hex(felt.as_ref())
+                        .map(|felt| felt == addr)
+                        .unwrap_or_default()
+                };
+                let txs = block.block_body_with_txs.transactions.iter();
+                for (tx, receipt) in txs.zip(block.receipts.iter()) {
+                    let sent = tx_sender(tx).map(is_addr).unwrap_or_default();
+                    let emitted = receipt
+                        .events
+                        .iter()
+                        .any(|event| is_addr(&event.from_address.0));
+                    if sent || emitted {
+                        transactions.push(TxnHash(tx_hash(tx).clone()));
+                    }
+                }
+            }
+
+            items.push(AccountActivity {
+                block_hash: BlockHash(Felt::try_new(&hash.into_str())?),
+                block_number: BlockNumber::try_new(number as i64)?,
+                state_update: flags & activity::STATE > 0,
+                transactions,
+            });
+        }
+
+        Ok(AccountActivityChunk {
+            activity: items,
+            continuation_token,
+        })
+    }
+
+    async fn getClassDeclaration(
+        &self,
+        class_hash: Felt,
+    ) -> std::result::Result<ClassDeclaration, iamgroot::jsonrpc::Error> {
+        let key = U256::from_hex(class_hash.as_ref()).map_err(|e| {
+            iamgroot::jsonrpc::Error::new(
+                -65000,
+                format!("Failed to read class hash: '{e}'"),
+            )
+        })?;
+        let declared = self
+            .db
+            .declarations_index
+            .read()
+            .await
+            .lookup(&key)?
+            .ok_or(crate::api::gen::error::CLASS_HASH_NOT_FOUND)?;
+
+        let number = declared.number();
+        let hash = self
+            .db
+            .blocks_index
+            .read()
+            .await
+            .lookup(&number)?
+            .ok_or(crate::api::gen::error::BLOCK_NOT_FOUND)?;
+
+        let zero = U256::default();
+        let tx = declared.tx();
+        let transaction_hash = if tx != zero {
+            Some(TxnHash(Felt::try_new(&tx.into_str())?))
+        } else {
+            None
+        };
+        let compiled = declared.compiled();
+        let compiled_class_hash = if compiled != zero {
+            Some(Felt::try_new(&compiled.into_str())?)
+        } else {
+            None
+        };
+
+        Ok(ClassDeclaration {
+            block_hash: BlockHash(Felt::try_new(&hash.into_str())?),
+            block_number: BlockNumber::try_new(number.into_u64() as i64)?,
+            class_hash,
+            compiled_class_hash,
+            transaction_hash,
+        })
+    }
+
+    async fn getCompiledCasm(
+        &self,
+        class_hash: Felt,
+    ) -> std::result::Result<CasmCompiledContractClass, iamgroot::jsonrpc::Error>
+    {
+        let key = U256::from_hex(class_hash.as_ref()).map_err(|e| {
+            iamgroot::jsonrpc::Error::new(
+                -65000,
+                format!("Failed to read class hash: '{e}'"),
+            )
+        })?;
+        let declared = self
+            .db
+            .declarations_index
+            .read()
+            .await
+            .lookup(&key)?
+            .ok_or(crate::api::gen::error::CLASS_HASH_NOT_FOUND)?;
+
+        let compiled = declared.compiled();
+        if compiled == U256::default() {
+            // Cairo 0 classes are not compiled to CASM
+            return Err(crate::api::gen::error::CLASS_HASH_NOT_FOUND.into());
+        }
+        let compiled = compiled.into_str();
+
+        // Served from storage only: the sync fetches the CASM of each
+        // declared class
+        let casm = self
+            .db
+            .casms
+            .get(&compiled)
+            .await?
+            .ok_or(crate::api::gen::error::CLASS_HASH_NOT_FOUND)?;
+        Ok(CasmCompiledContractClass(casm))
+    }
+
+    async fn getL1Settlement(
+        &self,
+        block_id: BlockId,
+    ) -> std::result::Result<L1Settlement, iamgroot::jsonrpc::Error> {
+        let number = match self.get_block_number(block_id).await? {
+            u64::MAX => self
+                .db
+                .blocks_index
+                .read()
+                .await
+                .max()?
+                .ok_or(crate::api::gen::error::BLOCK_NOT_FOUND)?,
+            number => U64::from_u64(number),
+        };
+
+        // The state update logged on L1 covers all blocks up to (and
+        // including) its block number, so the first one at or above
+        // the requested block is the one that settled it.
+        let (settled, settlement) = {
+            let db = self.db.settlements_index.read().await;
+            let key = if db.lookup(&number)?.is_some() {
+                Some(number.clone())
+            } else {
+                db.above(&number)?
+            };
+            let key = key.ok_or(crate::api::gen::error::BLOCK_NOT_FOUND)?;
+            let settlement = db
+                .lookup(&key)?
+                .ok_or(crate::api::gen::error::BLOCK_NOT_FOUND)?;
+            (key, settlement)
+        };
+
+        Ok(L1Settlement {
+            block_number: BlockNumber::try_new(number.into_u64() as i64)?,
+            l1_block_hash: NumAsHex::try_new(&settlement.block().into_str())?,
+            l1_block_number: settlement.number().into_u64() as i64,
+            l1_transaction_hash: NumAsHex::try_new(
+                &settlement.tx().into_str(),
+            )?,
+            settled_block_number: BlockNumber::try_new(
+                settled.into_u64() as i64
+            )?,
+        })
+    }
+
+    async fn getMessagesStatus(
+        &self,
+        transaction_hash: NumAsHex,
+    ) -> std::result::Result<GetMessagesStatusResult, iamgroot::jsonrpc::Error>
+    {
+        let tx = U256::from_hex(transaction_hash.as_ref()).map_err(|e| {
+            iamgroot::jsonrpc::Error::new(
+                -65000,
+                format!("Failed to read L1 TX hash: '{e}'"),
+            )
+        })?;
+
+        let mut found: Vec<(u64, U256)> = Vec::new();
+        {
+            let db = self.db.l1_messages_index.read().await;
+            let first = TxAndMessage::from(tx.clone(), U256::default());
+            let mut next = if db.lookup(&first)?.is_some() {
+                Some(first)
+            } else {
+                db.above(&first)?
+            };
+            while let Some(key) = next {
+                if key.tx() != tx {
+                    break;
+                }
+                let index = db.lookup(&key)?.map(|log| log.index().into_u64());
+                found.push((index.unwrap_or_default(), key.message()));
+                next = db.above(&key)?;
+            }
+        }
+        if found.is_empty() {
+            return Err(crate::api::gen::error::TXN_HASH_NOT_FOUND.into());
+        }
+        found.sort_by_key(|(index, _)| *index);
+
+        let mut result = Vec::with_capacity(found.len());
+        for (_, message) in found {
+            result.push(self.get_message_status(message).await?);
+        }
+        Ok(GetMessagesStatusResult(result))
+    }
+
+    async fn getMessage(
+        &self,
+        message_hash: NumAsHex,
+    ) -> std::result::Result<MessageStatus, iamgroot::jsonrpc::Error> {
+        let message = U256::from_hex(message_hash.as_ref()).map_err(|e| {
+            iamgroot::jsonrpc::Error::new(
+                -65000,
+                format!("Failed to read message hash: '{e}'"),
+            )
+        })?;
+        let status = self.get_message_status(message).await?;
+        if status.transaction_hash.is_none() {
+            return Err(crate::api::gen::error::TXN_HASH_NOT_FOUND.into());
+        }
+        Ok(status)
+    }
+
+    async fn getDeadLetters(
+        &self,
+    ) -> std::result::Result<GetDeadLettersResult, iamgroot::jsonrpc::Error>
+    {
+        self.check_admin()?;
+        let mut letters = Vec::new();
+        for id in dead_letter_ids(&self.db).await? {
+            if let Some(letter) =
+                self.db.dead_letters.get(&id.to_string()).await?
+            {
+                letters.push(letter);
+            }
+        }
+        Ok(GetDeadLettersResult(letters))
+    }
+
+    async fn retryDeadLetter(
+        &self,
+        id: i64,
+    ) -> std::result::Result<bool, iamgroot::jsonrpc::Error> {
+        self.check_admin()?;
+        let id = dead_letter_id(id)?;
+        let key = id.to_string();
+        let letter = match self.db.dead_letters.get(&key).await? {
+            Some(letter) => letter,
+            None => return Ok(false),
+        };
+        let event = sync::Event::from_dead_letter(&letter)
+            .ok_or(crate::api::gen::error::NOT_RETRIABLE)?;
+        let tx = self
+            .shared
+            .lock()
+            .await
+            .events
+            .clone()
+            .ok_or(crate::api::gen::error::SYNC_NOT_RUNNING)?;
+        // The letter is kept if the sync does not take the event
+        tx.send(event)
+            .await
+            .map_err(|_| crate::api::gen::error::SYNC_NOT_RUNNING)?;
+        remove_dead_letter(&self.db, id).await?;
+        Ok(true)
+    }
+
+    async fn discardDeadLetter(
+        &self,
+        id: i64,
+    ) -> std::result::Result<bool, iamgroot::jsonrpc::Error> {
+        self.check_admin()?;
+        let letter = remove_dead_letter(&self.db, dead_letter_id(id)?).await?;
+        Ok(letter.is_some())
+    }
+
+    async fn getProof(
+        &self,
+        block_id: BlockId,
+        contract_address: Address,
+        keys: Vec<Felt>,
+    ) -> std::result::Result<Proof, iamgroot::jsonrpc::Error> {
+        // The latest block is the synced head (not the last applied one)
+        let number = match self.get_block_number(block_id).await? {
+            u64::MAX => self
+                .db
+                .blocks_index
+                .read()
+                .await
+                .max()?
+                .ok_or(crate::api::gen::error::BLOCK_NOT_FOUND)?,
+            number => U64::from_u64(number),
+        };
+        let roots = self
+            .db
+            .trie_roots_index
+            .read()
+            .await
+            .lookup(&number)?
+            .ok_or(crate::api::gen::error::STATE_NOT_VERIFIED)?;
+
+        let address = U256::from_hex(contract_address.0.as_ref())?;
+        let nodes = self.db.trie_nodes_index.read().await;
+        let contract_proof = trie::proof(&nodes, &roots.contracts(), &address)?
+            .iter()
+            .map(proof_node)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let contract_data =
+            match trie::get(&nodes, &roots.contracts(), &address)? {
+                Some(leaf) => {
+                    let state = self
+                        .db
+                        .contract_states_index
+                        .read()
+                        .await
+                        .lookup(&leaf)?
+                        .ok_or(
+                            crate::api::gen::error::CONTRACT_STATE_MISSING,
+                        )?;
+                    let mut storage_proofs = Vec::with_capacity(keys.len());
+                    for key in &keys {
+                        let key = U256::from_hex(key.as_ref())?;
+                        let proof =
+                            trie::proof(&nodes, &state.storage_root(), &key)?
+                                .iter()
+                                .map(proof_node)
+                                .collect::<std::result::Result<Vec<_>, _>>()?;
+                        storage_proofs.push(proof);
+                    }
+                    Some(ContractData {
+                        class_hash: to_felt(&state.class_hash())?,
+                        contract_state_hash_version: Felt::try_new("0x0")?,
+                        nonce: to_felt(&state.nonce())?,
+                        root: to_felt(&state.storage_root())?,
+                        storage_proofs,
+                    })
+                }
+                None => None,
+            };
+
+        Ok(Proof {
+            class_commitment: Some(to_felt(&roots.classes())?),
+            contract_data,
+            contract_proof,
+            state_commitment: Some(to_felt(&trie::state_commitment(&roots)?)?),
+        })
+    }
+}
+
+/// Dead letter ids are never negative.
+fn dead_letter_id(
+    id: i64,
+) -> std::result::Result<u64, iamgroot::jsonrpc::Error> {
+    u64::try_from(id).map_err(|_| {
+        iamgroot::jsonrpc::Error::new(
+            -32602,
+            format!("Invalid params: dead letter id {id}"),
+        )
+    })
+}
+
+fn to_felt(
+    value: &U256,
+) -> std::result::Result<Felt, iamgroot::jsonrpc::Error> {
+    match value.into_str().as_str() {
+        "0x" => Felt::try_new("0x0"),
+        hex => Felt::try_new(hex),
+    }
+}
+
+fn proof_node(
+    node: &TrieNode,
+) -> std::result::Result<ProofNode, iamgroot::jsonrpc::Error> {
+    let node = if node.length() == 0 {
+        ProofNode::Binary {
+            binary: BinaryNode {
+                left: to_felt(&node.left())?,
+                right: to_felt(&node.right())?,
+            },
+        }
+    } else {
+        ProofNode::Edge {
+            edge: EdgeNode {
+                child: to_felt(&node.left())?,
+                path: EdgePath {
+                    len: node.length() as i64,
+                    value: to_felt(&node.right())?,
+                },
+            },
+        }
+    };
+    Ok(node)
 }
 
 fn not_implemented<T>() -> std::result::Result<T, iamgroot::jsonrpc::Error> {
@@ -2,6 +2,7 @@ use std::{
     marker::PhantomData,
     path::{Path, PathBuf},
     sync::Arc,
+    time::SystemTime,
 };
 
 use serde::{de::DeserializeOwned, Serialize};
@@ -13,22 +14,63 @@ use tokio::{
 use yakvdb::typed::{Store, DB};
 
 use crate::{
-    api::gen::BlockWithTxs,
+    api::gen::{BlockWithTxs, DeadLetter},
+    cache::{self, CachedRepo},
     seq::dto,
     util::{gzip, U256, U64},
 };
 
 #[derive(Clone)]
 pub struct Storage {
-    pub blocks: DirRepo<BlockWithTxs>,
+    pub base: PathBuf,
+    pub blocks: CachedRepo<BlockWithTxs, DirRepo<BlockWithTxs>>,
     pub blocks_index: Arc<RwLock<Store<U64, U256>>>,
+    pub hashes_index: Arc<RwLock<Store<U256, U64>>>,
+    pub statuses_index: Arc<RwLock<Store<U64, U64>>>,
+    /// Sequencer signatures of the blocks (by block hash).
+    pub signatures_index: Arc<RwLock<Store<U256, Signature>>>,
     pub txs_index: Arc<RwLock<Store<U256, BlockAndIndex>>>,
-    pub states: DirRepo<dto::StateUpdate>,
+    pub states: CachedRepo<dto::StateUpdate, DirRepo<dto::StateUpdate>>,
     pub states_index: Arc<RwLock<Store<AddressWithKeyAndNumber, U256>>>,
     pub nonces_index: Arc<RwLock<Store<AddressAndNumber, U256>>>,
     pub events_index: Arc<RwLock<Store<AddressWithKeyAndNumber, U64>>>,
-    pub classes: DirRepo<dto::Class>,
+    pub classes: CachedRepo<dto::Class, DirRepo<dto::Class>>,
     pub classes_index: Arc<RwLock<Store<AddressAndNumber, U256>>>,
+    pub declarations_index: Arc<RwLock<Store<U256, Declaration>>>,
+    pub casms: DirRepo<dto::CompiledClass>,
+    pub accounts_index: Arc<RwLock<Store<AddressAndNumber, U64>>>,
+    pub settlements_index: Arc<RwLock<Store<U64, Settlement>>>,
+    pub messages_index: Arc<RwLock<Store<MessageAndTx, U64>>>,
+    pub l1_messages_index: Arc<RwLock<Store<TxAndMessage, EthLog>>>,
+    /// Sync events that failed after all retry attempts (by id).
+    pub dead_letters: DirRepo<DeadLetter>,
+    /// Ids of the dead letters (mapped to the time of the last failure).
+    pub dead_letters_index: Arc<RwLock<Store<U64, U64>>>,
+    /// Nodes of the state tries (by node hash).
+    pub trie_nodes_index: Arc<RwLock<Store<U256, TrieNode>>>,
+    /// Roots of the contracts and classes tries after the block (by number).
+    pub trie_roots_index: Arc<RwLock<Store<U64, StateRoots>>>,
+    /// Class hash, storage root and nonce (by contract leaf hash).
+    pub contract_states_index: Arc<RwLock<Store<U256, ContractState>>>,
+    /// Opened by a reader process: stored files are never written.
+    pub read_only: bool,
+}
+
+/// Block statuses stored in the status index (only upgrades are stored:
+/// a block without an entry keeps the status it was downloaded with).
+pub mod status {
+    pub const ACCEPTED_ON_L1: u64 = 1;
+}
+
+/// Flags stored in the account activity index: the kind of activity
+/// that touched the address in the given block.
+pub mod activity {
+    /// The address sent at least one transaction.
+    pub const SENDER: u64 = 1;
+    /// The address emitted at least one event.
+    pub const EVENT: u64 = 2;
+    /// The address is present in the state diff.
+    pub const STATE: u64 = 4;
 }
 
 #[derive(Clone)]
@@ -63,6 +105,332 @@ impl<'a> From<&'a [u8]> for BlockAndIndex {
     }
 }
 
+/// Message hash and the hash of the L2 transaction that sent or consumed it
+/// (mapped to the number of such messages in the transaction): the same
+/// message can be sent more than once, so the hash alone is not a key.
+#[derive(Clone)]
+pub struct MessageAndTx([u8; 64]);
+
+impl MessageAndTx {
+    pub fn from(message: U256, tx: U256) -> Self {
+        let mut bytes = [0u8; 64];
+        bytes[0..32].copy_from_slice(message.as_ref());
+        bytes[32..].copy_from_slice(tx.as_ref());
+        Self(bytes)
+    }
+    pub fn message(&self) -> U256 {
+        U256::from(&self.0[0..32])
+    }
+    pub fn tx(&self) -> U256 {
+        U256::from(&self.0[32..])
+    }
+}
+
+impl AsRef<[u8]> for MessageAndTx {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a [u8]> for MessageAndTx {
+    fn from(value: &'a [u8]) -> Self {
+        let mut bytes = [0u8; 64];
+        bytes.copy_from_slice(value);
+        Self(bytes)
+    }
+}
+
+/// L1 transaction hash and the hash of the L1->L2 message it sent
+/// (mapped to the L1 block and log index of the message).
+#[derive(Clone)]
+pub struct TxAndMessage([u8; 64]);
+
+impl TxAndMessage {
+    pub fn from(tx: U256, message: U256) -> Self {
+        let mut bytes = [0u8; 64];
+        bytes[0..32].copy_from_slice(tx.as_ref());
+        bytes[32..].copy_from_slice(message.as_ref());
+        Self(bytes)
+    }
+    pub fn tx(&self) -> U256 {
+        U256::from(&self.0[0..32])
+    }
+    pub fn message(&self) -> U256 {
+        U256::from(&self.0[32..])
+    }
+}
+
+impl AsRef<[u8]> for TxAndMessage {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a [u8]> for TxAndMessage {
+    fn from(value: &'a [u8]) -> Self {
+        let mut bytes = [0u8; 64];
+        bytes.copy_from_slice(value);
+        Self(bytes)
+    }
+}
+
+/// L1 block number and the index of the log within the block.
+#[derive(Clone)]
+pub struct EthLog([u8; 16]);
+
+impl EthLog {
+    pub fn from(number: U64, index: U64) -> Self {
+        let mut bytes = [0u8; 16];
+        bytes[0..8].copy_from_slice(number.as_ref());
+        bytes[8..16].copy_from_slice(index.as_ref());
+        Self(bytes)
+    }
+    pub fn number(&self) -> U64 {
+        U64::from(&self.0[0..8])
+    }
+    pub fn index(&self) -> U64 {
+        U64::from(&self.0[8..16])
+    }
+}
+
+impl AsRef<[u8]> for EthLog {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a [u8]> for EthLog {
+    fn from(value: &'a [u8]) -> Self {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(value);
+        Self(bytes)
+    }
+}
+
+/// L1 block and transaction where the state update was settled.
+#[derive(Clone)]
+pub struct Settlement([u8; 72]);
+
+impl Settlement {
+    pub fn from(number: U64, block: U256, tx: U256) -> Self {
+        let mut bytes = [0u8; 72];
+        bytes[0..8].copy_from_slice(number.as_ref());
+        bytes[8..40].copy_from_slice(block.as_ref());
+        bytes[40..72].copy_from_slice(tx.as_ref());
+        Self(bytes)
+    }
+    pub fn number(&self) -> U64 {
+        U64::from(&self.0[0..8])
+    }
+    pub fn block(&self) -> U256 {
+        U256::from(&self.0[8..40])
+    }
+    pub fn tx(&self) -> U256 {
+        U256::from(&self.0[40..72])
+    }
+}
+
+impl AsRef<[u8]> for Settlement {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a [u8]> for Settlement {
+    fn from(value: &'a [u8]) -> Self {
+        let mut bytes = [0u8; 72];
+        bytes.copy_from_slice(value);
+        Self(bytes)
+    }
+}
+
+/// ECDSA signature (r, s) of the block hash by the sequencer.
+#[derive(Clone)]
+pub struct Signature([u8; 64]);
+
+impl Signature {
+    pub fn from(r: U256, s: U256) -> Self {
+        let mut bytes = [0u8; 64];
+        bytes[0..32].copy_from_slice(r.as_ref());
+        bytes[32..64].copy_from_slice(s.as_ref());
+        Self(bytes)
+    }
+    pub fn r(&self) -> U256 {
+        U256::from(&self.0[0..32])
+    }
+    pub fn s(&self) -> U256 {
+        U256::from(&self.0[32..64])
+    }
+}
+
+impl AsRef<[u8]> for Signature {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a [u8]> for Signature {
+    fn from(value: &'a [u8]) -> Self {
+        let mut bytes = [0u8; 64];
+        bytes.copy_from_slice(value);
+        Self(bytes)
+    }
+}
+
+/// Node of a state trie: a binary node (left and right child hashes)
+/// or an edge (child hash and path, the first byte is the path length).
+#[derive(Clone)]
+pub struct TrieNode([u8; 65]);
+
+impl TrieNode {
+    pub fn binary(left: U256, right: U256) -> Self {
+        let mut bytes = [0u8; 65];
+        bytes[1..33].copy_from_slice(left.as_ref());
+        bytes[33..65].copy_from_slice(right.as_ref());
+        Self(bytes)
+    }
+    pub fn edge(child: U256, path: U256, length: u8) -> Self {
+        let mut bytes = [0u8; 65];
+        bytes[0] = length;
+        bytes[1..33].copy_from_slice(child.as_ref());
+        bytes[33..65].copy_from_slice(path.as_ref());
+        Self(bytes)
+    }
+    /// Length of the edge path (zero for a binary node).
+    pub fn length(&self) -> u8 {
+        self.0[0]
+    }
+    /// Left child of the binary node, or the child of the edge.
+    pub fn left(&self) -> U256 {
+        U256::from(&self.0[1..33])
+    }
+    /// Right child of the binary node, or the path of the edge.
+    pub fn right(&self) -> U256 {
+        U256::from(&self.0[33..65])
+    }
+}
+
+impl AsRef<[u8]> for TrieNode {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a [u8]> for TrieNode {
+    fn from(value: &'a [u8]) -> Self {
+        let mut bytes = [0u8; 65];
+        bytes.copy_from_slice(value);
+        Self(bytes)
+    }
+}
+
+/// Roots of the contracts and classes tries.
+#[derive(Clone)]
+pub struct StateRoots([u8; 64]);
+
+impl StateRoots {
+    pub fn from(contracts: U256, classes: U256) -> Self {
+        let mut bytes = [0u8; 64];
+        bytes[0..32].copy_from_slice(contracts.as_ref());
+        bytes[32..64].copy_from_slice(classes.as_ref());
+        Self(bytes)
+    }
+    pub fn contracts(&self) -> U256 {
+        U256::from(&self.0[0..32])
+    }
+    pub fn classes(&self) -> U256 {
+        U256::from(&self.0[32..64])
+    }
+}
+
+impl AsRef<[u8]> for StateRoots {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a [u8]> for StateRoots {
+    fn from(value: &'a [u8]) -> Self {
+        let mut bytes = [0u8; 64];
+        bytes.copy_from_slice(value);
+        Self(bytes)
+    }
+}
+
+#[derive(Clone)]
+pub struct ContractState([u8; 96]);
+
+impl ContractState {
+    pub fn from(class_hash: U256, storage_root: U256, nonce: U256) -> Self {
+        let mut bytes = [0u8; 96];
+        bytes[0..32].copy_from_slice(class_hash.as_ref());
+        bytes[32..64].copy_from_slice(storage_root.as_ref());
+        bytes[64..96].copy_from_slice(nonce.as_ref());
+        Self(bytes)
+    }
+    pub fn class_hash(&self) -> U256 {
+        U256::from(&self.0[0..32])
+    }
+    pub fn storage_root(&self) -> U256 {
+        U256::from(&self.0[32..64])
+    }
+    pub fn nonce(&self) -> U256 {
+        U256::from(&self.0[64..96])
+    }
+}
+
+impl AsRef<[u8]> for ContractState {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a [u8]> for ContractState {
+    fn from(value: &'a [u8]) -> Self {
+        let mut bytes = [0u8; 96];
+        bytes.copy_from_slice(value);
+        Self(bytes)
+    }
+}
+
+#[derive(Clone)]
+pub struct Declaration([u8; 72]);
+
+impl Declaration {
+    pub fn from(number: U64, tx: U256, compiled: U256) -> Self {
+        let mut bytes = [0u8; 72];
+        bytes[0..8].copy_from_slice(number.as_ref());
+        bytes[8..40].copy_from_slice(tx.as_ref());
+        bytes[40..72].copy_from_slice(compiled.as_ref());
+        Self(bytes)
+    }
+    pub fn number(&self) -> U64 {
+        U64::from(&self.0[0..8])
+    }
+    /// Hash of the declaring transaction (zero if unknown).
+    pub fn tx(&self) -> U256 {
+        U256::from(&self.0[8..40])
+    }
+    /// Compiled class hash (zero for Cairo 0 classes).
+    pub fn compiled(&self) -> U256 {
+        U256::from(&self.0[40..72])
+    }
+}
+
+impl AsRef<[u8]> for Declaration {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a [u8]> for Declaration {
+    fn from(value: &'a [u8]) -> Self {
+        let mut bytes = [0u8; 72];
+        bytes.copy_from_slice(value);
+        Self(bytes)
+    }
+}
+
 #[derive(Clone)]
 pub struct AddressAndNumber([u8; 40]);
 
@@ -131,15 +499,114 @@ impl<'a> From<&'a [u8]> for AddressWithKeyAndNumber {
     }
 }
 
+/// Index files (directory and file name) of the storage.
+const INDICES: [(&str, &str); 18] = [
+    ("block", "index.yak"),
+    ("block", "hash.yak"),
+    ("block", "status.yak"),
+    ("block", "signature.yak"),
+    ("block", "event.yak"),
+    ("tx", "index.yak"),
+    ("tx", "message.yak"),
+    ("state", "index.yak"),
+    ("state", "nonce.yak"),
+    ("class", "index.yak"),
+    ("class", "declared.yak"),
+    ("account", "index.yak"),
+    ("eth", "index.yak"),
+    ("eth", "message.yak"),
+    ("dead", "index.yak"),
+    ("trie", "node.yak"),
+    ("trie", "index.yak"),
+    ("trie", "contract.yak"),
+];
+
+/// Replace the index with the freshly opened one (not shared yet).
+async fn swap<T>(index: &RwLock<T>, fresh: Arc<RwLock<T>>) {
+    if let Ok(fresh) = Arc::try_unwrap(fresh) {
+        *index.write().await = fresh.into_inner();
+    }
+}
+
 impl Storage {
     pub async fn new<P: AsRef<Path>>(base: P) -> Self {
+        Self::with_cache(base, cache::DEFAULT_CAPACITY).await
+    }
+
+    pub async fn with_cache<P: AsRef<Path>>(base: P, capacity: usize) -> Self {
         fs::create_dir_all(base.as_ref()).await.ok();
+        Self::open(base.as_ref(), capacity, false).await
+    }
 
+    /// Open an existing data directory for reading only (e.g. to serve
+    /// the RPC from a directory synced by another process). The reader
+    /// never creates index files: all of them must exist already (the
+    /// writer creates them on start).
+    pub async fn read_only<P: AsRef<Path>>(
+        base: P,
+        capacity: usize,
+    ) -> anyhow::Result<Self> {
         let base = base.as_ref();
+        if !base.join("block").join("index.yak").exists() {
+            anyhow::bail!("No synced data found at '{}'", base.display());
+        }
+        for (dir, file) in INDICES {
+            let path = base.join(dir).join(file);
+            if !path.exists() {
+                anyhow::bail!("Index not found at '{}'", path.display());
+            }
+        }
+        Ok(Self::open(base, capacity, true).await)
+    }
+
+    /// Latest modification time of the index files: the writer touched the
+    /// indices (and a replica has to reopen them) only if it changed.
+    pub async fn modified(&self) -> anyhow::Result<Option<SystemTime>> {
+        let mut latest = None;
+        for (dir, file) in INDICES {
+            let path = self.base.join(dir).join(file);
+            let modified = fs::metadata(&path).await?.modified()?;
+            latest = latest.max(Some(modified));
+        }
+        Ok(latest)
+    }
+
+    /// Reopen the indices of a read-only storage: an open index keeps
+    /// the pages it has loaded, so the entries written by another process
+    /// become visible only after the index is reopened.
+    pub async fn reopen(&self) {
+        if !self.read_only {
+            return;
+        }
+        let fresh = Self::open(&self.base, 1, true).await;
+        swap(&self.blocks_index, fresh.blocks_index).await;
+        swap(&self.hashes_index, fresh.hashes_index).await;
+        swap(&self.statuses_index, fresh.statuses_index).await;
+        swap(&self.signatures_index, fresh.signatures_index).await;
+        swap(&self.txs_index, fresh.txs_index).await;
+        swap(&self.states_index, fresh.states_index).await;
+        swap(&self.nonces_index, fresh.nonces_index).await;
+        swap(&self.events_index, fresh.events_index).await;
+        swap(&self.classes_index, fresh.classes_index).await;
+        swap(&self.declarations_index, fresh.declarations_index).await;
+        swap(&self.accounts_index, fresh.accounts_index).await;
+        swap(&self.settlements_index, fresh.settlements_index).await;
+        swap(&self.messages_index, fresh.messages_index).await;
+        swap(&self.l1_messages_index, fresh.l1_messages_index).await;
+        swap(&self.dead_letters_index, fresh.dead_letters_index).await;
+        swap(&self.trie_nodes_index, fresh.trie_nodes_index).await;
+        swap(&self.trie_roots_index, fresh.trie_roots_index).await;
+        swap(&self.contract_states_index, fresh.contract_states_index).await;
+    }
 
+    async fn open(base: &Path, capacity: usize, read_only: bool) -> Self {
         let mut path = base.to_owned();
         path.push("block");
-        let blocks = DirRepo::new(&path).await;
+        let blocks = CachedRepo::wrap(
+            DirRepo::open(&path, read_only).await,
+            "block",
+            capacity,
+        );
 
         let mut path = base.to_owned();
         path.push("block");
@@ -147,6 +614,24 @@ impl Storage {
         let blocks_index = Store::new(&path);
         let blocks_index = Arc::new(RwLock::new(blocks_index));
 
+        let mut path = base.to_owned();
+        path.push("block");
+        path.push("hash.yak");
+        let hashes_index = Store::new(&path);
+        let hashes_index = Arc::new(RwLock::new(hashes_index));
+
+        let mut path = base.to_owned();
+        path.push("block");
+        path.push("status.yak");
+        let statuses_index = Store::new(&path);
+        let statuses_index = Arc::new(RwLock::new(statuses_index));
+
+        let mut path = base.to_owned();
+        path.push("block");
+        path.push("signature.yak");
+        let signatures_index = Store::new(&path);
+        let signatures_index = Arc::new(RwLock::new(signatures_index));
+
         let mut path = base.to_owned();
         path.push("block");
         path.push("event.yak");
@@ -155,7 +640,9 @@ impl Storage {
 
         let mut path = base.to_owned();
         path.push("tx");
-        fs::create_dir_all(&path).await.ok();
+        if !read_only {
+            fs::create_dir_all(&path).await.ok();
+        }
 
         let mut path = base.to_owned();
         path.push("tx");
@@ -163,9 +650,19 @@ impl Storage {
         let txs_index = Store::new(&path);
         let txs_index = Arc::new(RwLock::new(txs_index));
 
+        let mut path = base.to_owned();
+        path.push("tx");
+        path.push("message.yak");
+        let messages_index = Store::new(&path);
+        let messages_index = Arc::new(RwLock::new(messages_index));
+
         let mut path = base.to_owned();
         path.push("state");
-        let states = DirRepo::new(&path).await;
+        let states = CachedRepo::wrap(
+            DirRepo::open(&path, read_only).await,
+            "state",
+            capacity,
+        );
 
         let mut path = base.to_owned();
         path.push("state");
@@ -181,7 +678,11 @@ impl Storage {
 
         let mut path = base.to_owned();
         path.push("class");
-        let classes = DirRepo::new(&path).await;
+        let classes = CachedRepo::wrap(
+            DirRepo::open(&path, read_only).await,
+            "class",
+            capacity,
+        );
 
         let mut path = base.to_owned();
         path.push("class");
@@ -189,9 +690,88 @@ impl Storage {
         let classes_index = Store::new(&path);
         let classes_index = Arc::new(RwLock::new(classes_index));
 
+        let mut path = base.to_owned();
+        path.push("class");
+        path.push("declared.yak");
+        let declarations_index = Store::new(&path);
+        let declarations_index = Arc::new(RwLock::new(declarations_index));
+
+        let mut path = base.to_owned();
+        path.push("casm");
+        let casms = DirRepo::open(&path, read_only).await;
+
+        let mut path = base.to_owned();
+        path.push("account");
+        if !read_only {
+            fs::create_dir_all(&path).await.ok();
+        }
+
+        let mut path = base.to_owned();
+        path.push("account");
+        path.push("index.yak");
+        let accounts_index = Store::new(&path);
+        let accounts_index = Arc::new(RwLock::new(accounts_index));
+
+        let mut path = base.to_owned();
+        path.push("eth");
+        if !read_only {
+            fs::create_dir_all(&path).await.ok();
+        }
+
+        let mut path = base.to_owned();
+        path.push("eth");
+        path.push("index.yak");
+        let settlements_index = Store::new(&path);
+        let settlements_index = Arc::new(RwLock::new(settlements_index));
+
+        let mut path = base.to_owned();
+        path.push("eth");
+        path.push("message.yak");
+        let l1_messages_index = Store::new(&path);
+        let l1_messages_index = Arc::new(RwLock::new(l1_messages_index));
+
+        let mut path = base.to_owned();
+        path.push("dead");
+        let dead_letters = DirRepo::open(&path, read_only).await;
+
+        let mut path = base.to_owned();
+        path.push("dead");
+        path.push("index.yak");
+        let dead_letters_index = Store::new(&path);
+        let dead_letters_index = Arc::new(RwLock::new(dead_letters_index));
+
+        let mut path = base.to_owned();
+        path.push("trie");
+        if !read_only {
+            fs::create_dir_all(&path).await.ok();
+        }
+
+        let mut path = base.to_owned();
+        path.push("trie");
+        path.push("node.yak");
+        let trie_nodes_index = Store::new(&path);
+        let trie_nodes_index = Arc::new(RwLock::new(trie_nodes_index));
+
+        let mut path = base.to_owned();
+        path.push("trie");
+        path.push("index.yak");
+        let trie_roots_index = Store::new(&path);
+        let trie_roots_index = Arc::new(RwLock::new(trie_roots_index));
+
+        let mut path = base.to_owned();
+        path.push("trie");
+        path.push("contract.yak");
+        let contract_states_index = Store::new(&path);
+        let contract_states_index =
+            Arc::new(RwLock::new(contract_states_index));
+
         Self {
+            base: base.to_owned(),
             blocks,
             blocks_index,
+            hashes_index,
+            statuses_index,
+            signatures_index,
             txs_index,
             states,
             states_index,
@@ -199,7 +779,129 @@ impl Storage {
             events_index,
             classes,
             classes_index,
+            declarations_index,
+            casms,
+            accounts_index,
+            settlements_index,
+            messages_index,
+            l1_messages_index,
+            dead_letters,
+            dead_letters_index,
+            trie_nodes_index,
+            trie_roots_index,
+            contract_states_index,
+            read_only,
+        }
+    }
+}
+
+impl Storage {
+    /// Wait for in-progress index writes to complete and sync the index
+    /// files to disk: every index stays locked for writing until all the
+    /// files are synced.
+    pub async fn sync_indices(&self) -> anyhow::Result<()> {
+        let _locked = (
+            self.blocks_index.write().await,
+            self.hashes_index.write().await,
+            self.statuses_index.write().await,
+            self.signatures_index.write().await,
+            self.txs_index.write().await,
+            self.states_index.write().await,
+            self.nonces_index.write().await,
+            self.events_index.write().await,
+            self.classes_index.write().await,
+            self.declarations_index.write().await,
+            self.accounts_index.write().await,
+            self.settlements_index.write().await,
+            self.messages_index.write().await,
+            self.l1_messages_index.write().await,
+            self.dead_letters_index.write().await,
+            self.trie_nodes_index.write().await,
+            self.trie_roots_index.write().await,
+            self.contract_states_index.write().await,
+        );
+        if self.read_only {
+            return Ok(());
+        }
+        for (dir, file) in INDICES {
+            let path = self.base.join(dir).join(file);
+            File::open(&path).await?.sync_all().await?;
+        }
+        Ok(())
+    }
+
+    /// Height below which the history is pruned (as recorded by the last
+    /// pruning, regardless of the currently configured horizon).
+    pub async fn pruned(&self) -> anyhow::Result<Option<u64>> {
+        self.marker("pruned").await
+    }
+
+    /// Record the pruned height (it never goes down).
+    pub async fn set_pruned(&self, horizon: u64) -> anyhow::Result<()> {
+        self.set_marker("pruned", horizon).await
+    }
+
+    /// Highest block accepted on L1 (stored blocks up to it are marked).
+    pub async fn accepted(&self) -> anyhow::Result<Option<u64>> {
+        self.marker("accepted").await
+    }
+
+    /// Record the highest block accepted on L1 (it never goes down).
+    pub async fn set_accepted(&self, number: u64) -> anyhow::Result<()> {
+        self.set_marker("accepted", number).await
+    }
+
+    /// Height recorded in the `meta/<name>` file (if any).
+    async fn marker(&self, name: &str) -> anyhow::Result<Option<u64>> {
+        let path = self.base.join("meta").join(name);
+        match fs::read_to_string(&path).await {
+            Ok(text) => Ok(Some(text.trim().parse()?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Record the height in the `meta/<name>` file unless a higher one
+    /// is already recorded.
+    async fn set_marker(&self, name: &str, value: u64) -> anyhow::Result<()> {
+        if self.read_only {
+            anyhow::bail!("Storage is read-only: {}", self.base.display());
+        }
+        if self.marker(name).await?.map_or(true, |known| value > known) {
+            let path = self.base.join("meta");
+            fs::create_dir_all(&path).await?;
+            fs::write(path.join(name), value.to_string()).await?;
+        }
+        Ok(())
+    }
+
+    /// Resolve block number by block hash without touching the block file.
+    pub async fn block_number(
+        &self,
+        hash: &str,
+    ) -> anyhow::Result<Option<u64>> {
+        let key = U256::from_hex(hash)?;
+        let number = self.hashes_index.read().await.lookup(&key)?;
+        Ok(number.map(|number| number.into_u64()))
+    }
+
+    /// Fill the hash index from the number index (for the data saved
+    /// before the hash index existed). Returns number of indexed blocks.
+    pub async fn index_hashes(&self) -> anyhow::Result<u64> {
+        let mut indexed = 0;
+        let mut next = self.blocks_index.read().await.min()?;
+        while let Some(number) = next {
+            let hash = self.blocks_index.read().await.lookup(&number)?;
+            if let Some(hash) = hash {
+                let mut hashes = self.hashes_index.write().await;
+                if hashes.lookup(&hash)?.is_none() {
+                    hashes.insert(&hash, number.clone())?;
+                    indexed += 1;
+                }
+            }
+            next = self.blocks_index.read().await.above(&number)?;
         }
+        Ok(indexed)
     }
 }
 
@@ -227,6 +929,24 @@ where
     Ok(Some(below).zip(val))
 }
 
+/// Merge activity `flags` into the account index entry of the block.
+pub fn mark_account(
+    db: &mut Store<AddressAndNumber, U64>,
+    address: U256,
+    number: u64,
+    flags: u64,
+) -> anyhow::Result<()> {
+    let key = AddressAndNumber::from(address, U64::from_u64(number));
+    let prev = db
+        .lookup(&key)?
+        .map(|val| val.into_u64())
+        .unwrap_or_default();
+    if prev & flags != flags {
+        db.insert(&key, U64::from_u64(prev | flags))?;
+    }
+    Ok(())
+}
+
 #[async_trait::async_trait]
 pub trait Repo<T: Serialize + DeserializeOwned> {
     async fn new(base: &Path) -> Self;
@@ -239,6 +959,7 @@ pub trait Repo<T: Serialize + DeserializeOwned> {
 #[derive(Clone)]
 pub struct DirRepo<T: Serialize + DeserializeOwned> {
     base: PathBuf,
+    read_only: bool,
     _phantom: PhantomData<T>,
 }
 
@@ -246,6 +967,24 @@ impl<T> DirRepo<T>
 where
     T: Serialize + DeserializeOwned + Sync,
 {
+    pub async fn open(base: &Path, read_only: bool) -> Self {
+        if !read_only {
+            fs::create_dir_all(base).await.ok();
+        }
+        Self {
+            base: base.to_owned(),
+            read_only,
+            _phantom: PhantomData,
+        }
+    }
+
+    fn check_writable(&self) -> anyhow::Result<()> {
+        if self.read_only {
+            anyhow::bail!("Storage is read-only: {}", self.base.display());
+        }
+        Ok(())
+    }
+
     fn path(&self, key: &str) -> PathBuf {
         let mut path = self.base.clone();
         path.push(format!("{}.json.gzip", key));
@@ -276,6 +1015,7 @@ where
 
         Self {
             base: base.to_owned(),
+            read_only: false,
             _phantom: PhantomData,
         }
     }
@@ -294,6 +1034,7 @@ where
     }
 
     async fn del(&self, key: &str) -> anyhow::Result<Option<T>> {
+        self.check_writable()?;
         let opt = self.get(key).await?;
         if opt.is_some() {
             fs::remove_file(self.path(key)).await?;
@@ -302,6 +1043,7 @@ where
     }
 
     async fn put(&self, key: &str, val: T) -> anyhow::Result<()> {
+        self.check_writable()?;
         let path = self.path(key);
         let mut file = File::create(path).await?;
         let json = serde_json::to_string(&val)?;
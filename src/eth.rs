@@ -1,6 +1,17 @@
 use std::time::Duration;
 
-use crate::{api::gen::NumAsHex, util::http};
+use crate::{
+    api::gen::NumAsHex,
+    util::{http, U256},
+};
+
+/// `LogStateUpdate(uint256 globalRoot, int256 blockNumber, uint256 blockHash)`
+const LOG_STATE_UPDATE: &str = "LogStateUpdate(uint256,int256,uint256)";
+
+/// `LogMessageToL2(address indexed fromAddress, uint256 indexed toAddress,
+/// uint256 indexed selector, uint256[] payload, uint256 nonce, uint256 fee)`
+const LOG_MESSAGE_TO_L2: &str =
+    "LogMessageToL2(address,uint256,uint256,uint256[],uint256,uint256)";
 
 #[derive(Clone, Debug)]
 pub struct State {
@@ -9,9 +20,46 @@ pub struct State {
     pub state_block_number: u64,
 }
 
+/// Starknet state update settled on L1 (a `LogStateUpdate` event).
+#[derive(Clone, Debug)]
+pub struct Update {
+    pub state: State,
+    pub eth_block_number: u64,
+    pub eth_block_hash: NumAsHex,
+    pub eth_tx_hash: NumAsHex,
+}
+
+/// L1->L2 message sent to Starknet (a `LogMessageToL2` event).
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub hash: U256,
+    pub eth_block_number: u64,
+    pub eth_tx_hash: NumAsHex,
+    pub eth_log_index: u64,
+}
+
 #[async_trait::async_trait]
 pub trait EthApi: Send + Sync + Clone + 'static {
     async fn get_state(&self, address: &str) -> anyhow::Result<State>;
+
+    async fn get_block_number(&self) -> anyhow::Result<u64>;
+    async fn get_block_hash(&self, number: u64) -> anyhow::Result<NumAsHex>;
+
+    /// State updates logged in the (inclusive) range of L1 blocks.
+    async fn get_updates(
+        &self,
+        address: &str,
+        from: u64,
+        to: u64,
+    ) -> anyhow::Result<Vec<Update>>;
+
+    /// L1->L2 messages logged in the (inclusive) range of L1 blocks.
+    async fn get_messages(
+        &self,
+        address: &str,
+        from: u64,
+        to: u64,
+    ) -> anyhow::Result<Vec<Message>>;
 }
 
 #[async_trait::async_trait]
@@ -39,12 +87,65 @@ impl EthApi for EthClient {
                 .and_then(parse_hex_as_num)?,
         })
     }
+
+    async fn get_block_number(&self) -> anyhow::Result<u64> {
+        self.call_ethereum(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "eth_blockNumber",
+            "params": [],
+            "id": 0
+        }))
+        .await
+        .and_then(|value| parse_num_as_hex(&value))
+        .and_then(parse_hex_as_num)
+    }
+
+    async fn get_block_hash(&self, number: u64) -> anyhow::Result<NumAsHex> {
+        self.call_ethereum(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "eth_getBlockByNumber",
+            "params": [
+                format!("0x{number:x}"),
+                false
+            ],
+            "id": 0
+        }))
+        .await
+        .and_then(|value| parse_num_as_hex(&value["hash"]))
+    }
+
+    async fn get_updates(
+        &self,
+        address: &str,
+        from: u64,
+        to: u64,
+    ) -> anyhow::Result<Vec<Update>> {
+        self.get_logs(address, from, to, LOG_STATE_UPDATE)
+            .await?
+            .iter()
+            .map(parse_state_update_log)
+            .collect()
+    }
+
+    async fn get_messages(
+        &self,
+        address: &str,
+        from: u64,
+        to: u64,
+    ) -> anyhow::Result<Vec<Message>> {
+        self.get_logs(address, from, to, LOG_MESSAGE_TO_L2)
+            .await?
+            .iter()
+            .map(parse_message_log)
+            .collect()
+    }
 }
 
 #[derive(Clone)]
 pub struct EthClient {
     http: reqwest::Client,
     url: String,
+    auth: Option<String>,
 }
 
 impl EthClient {
@@ -56,6 +157,15 @@ impl EthClient {
         Self {
             http,
             url: url.to_string(),
+            auth: None,
+        }
+    }
+
+    /// Send the given value as the `Authorization` header with each request.
+    pub fn with_auth(self, auth: &str) -> Self {
+        Self {
+            auth: Some(auth.to_string()),
+            ..self
         }
     }
 
@@ -73,6 +183,33 @@ impl EthClient {
         .and_then(|value| parse_num_as_hex(&value["hash"]))
     }
 
+    async fn get_logs(
+        &self,
+        address: &str,
+        from: u64,
+        to: u64,
+        event: &str,
+    ) -> anyhow::Result<Vec<serde_json::Value>> {
+        let logs = self
+            .call_ethereum(serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_getLogs",
+                "params": [
+                    {
+                        "address": address,
+                        "fromBlock": format!("0x{from:x}"),
+                        "toBlock": format!("0x{to:x}"),
+                        "topics": [encode_event_topic(event)]
+                    }
+                ],
+                "id": 0
+            }))
+            .await?;
+        logs.as_array()
+            .cloned()
+            .ok_or(anyhow::anyhow!("Failed to parse logs"))
+    }
+
     async fn call_starknet_contract(
         &self,
         block_hash: &str,
@@ -101,7 +238,13 @@ impl EthClient {
         &self,
         value: serde_json::Value,
     ) -> anyhow::Result<serde_json::Value> {
-        let res = self.http.post(&self.url).json(&value).send().await?;
+        let req = self.http.post(&self.url).json(&value);
+        let req = if let Some(auth) = self.auth.as_ref() {
+            req.header(reqwest::header::AUTHORIZATION, auth)
+        } else {
+            req
+        };
+        let res = req.send().await?;
 
         let status = res.status();
         let (code, message) = (status.as_u16(), status.as_str());
@@ -115,6 +258,215 @@ impl EthClient {
     }
 }
 
+/// L1 placeholder for processes that do not track L1 (e.g. read-only
+/// RPC): every call fails.
+#[derive(Clone, Default)]
+pub struct NoEth;
+
+#[async_trait::async_trait]
+impl EthApi for NoEth {
+    async fn get_state(&self, _: &str) -> anyhow::Result<State> {
+        anyhow::bail!(NO_ETH)
+    }
+
+    async fn get_block_number(&self) -> anyhow::Result<u64> {
+        anyhow::bail!(NO_ETH)
+    }
+
+    async fn get_block_hash(&self, _: u64) -> anyhow::Result<NumAsHex> {
+        anyhow::bail!(NO_ETH)
+    }
+
+    async fn get_updates(
+        &self,
+        _: &str,
+        _: u64,
+        _: u64,
+    ) -> anyhow::Result<Vec<Update>> {
+        anyhow::bail!(NO_ETH)
+    }
+
+    async fn get_messages(
+        &self,
+        _: &str,
+        _: u64,
+        _: u64,
+    ) -> anyhow::Result<Vec<Message>> {
+        anyhow::bail!(NO_ETH)
+    }
+}
+
+const NO_ETH: &str = "L1 is not available";
+
+/// Multiple L1 providers: the block number fails over to the next
+/// provider, while the state, block hashes and logs require at least
+/// `quorum` providers to agree.
+/// An empty pool means no L1 is available (every call fails).
+#[derive(Clone)]
+pub struct EthPool<ETH> {
+    providers: Vec<ETH>,
+    quorum: usize,
+}
+
+impl<ETH: EthApi> EthPool<ETH> {
+    pub fn new(providers: Vec<ETH>) -> Self {
+        Self {
+            providers,
+            quorum: 1,
+        }
+    }
+
+    pub fn with_quorum(self, quorum: usize) -> Self {
+        Self {
+            quorum: quorum.clamp(1, self.providers.len().max(1)),
+            ..self
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    async fn failover<'a, T, F, R>(&'a self, f: F) -> anyhow::Result<T>
+    where
+        F: Fn(&'a ETH) -> R,
+        R: std::future::Future<Output = anyhow::Result<T>>,
+    {
+        let mut last = anyhow::anyhow!("No L1 providers configured");
+        for (index, provider) in self.providers.iter().enumerate() {
+            match f(provider).await {
+                Ok(ret) => return Ok(ret),
+                Err(e) => {
+                    tracing::warn!(provider = index, error = ?e, "L1 call failed");
+                    metrics::counter!("eth_failover", 1);
+                    last = e;
+                }
+            }
+        }
+        Err(last)
+    }
+
+    /// The answer given by at least `quorum` providers (the most common
+    /// one if there are several). A single provider fails over instead.
+    async fn agree<'a, T, F, R>(
+        &'a self,
+        what: &str,
+        f: F,
+        same: fn(&T, &T) -> bool,
+    ) -> anyhow::Result<T>
+    where
+        T: std::fmt::Debug,
+        F: Fn(&'a ETH) -> R,
+        R: std::future::Future<Output = anyhow::Result<T>>,
+    {
+        if self.providers.len() <= 1 {
+            return self.failover(f).await;
+        }
+
+        let results =
+            futures::future::join_all(self.providers.iter().map(f)).await;
+
+        let mut votes: Vec<(T, usize)> = Vec::new();
+        for (index, result) in results.into_iter().enumerate() {
+            let answer = match result {
+                Ok(answer) => answer,
+                Err(e) => {
+                    tracing::warn!(provider = index, error = ?e, "L1 call failed");
+                    continue;
+                }
+            };
+            match votes.iter_mut().find(|(known, _)| same(known, &answer)) {
+                Some((_, count)) => *count += 1,
+                None => votes.push((answer, 1)),
+            }
+        }
+
+        if votes.len() > 1 {
+            tracing::warn!(what, ?votes, "L1 providers disagree");
+            metrics::counter!("eth_disagreement", 1);
+        }
+        votes
+            .into_iter()
+            .filter(|(_, count)| *count >= self.quorum)
+            .max_by_key(|(_, count)| *count)
+            .map(|(answer, _)| answer)
+            .ok_or(anyhow::anyhow!("L1 {what} quorum not reached"))
+    }
+}
+
+fn same_state(a: &State, b: &State) -> bool {
+    a.state_block_number == b.state_block_number
+        && a.state_block_hash.as_ref() == b.state_block_hash.as_ref()
+        && a.state_root.as_ref() == b.state_root.as_ref()
+}
+
+fn same_update(a: &Update, b: &Update) -> bool {
+    same_state(&a.state, &b.state)
+        && a.eth_block_number == b.eth_block_number
+        && a.eth_block_hash.as_ref() == b.eth_block_hash.as_ref()
+        && a.eth_tx_hash.as_ref() == b.eth_tx_hash.as_ref()
+}
+
+fn same_message(a: &Message, b: &Message) -> bool {
+    a.hash == b.hash
+        && a.eth_block_number == b.eth_block_number
+        && a.eth_tx_hash.as_ref() == b.eth_tx_hash.as_ref()
+        && a.eth_log_index == b.eth_log_index
+}
+
+fn same_all<T>(a: &[T], b: &[T], same: fn(&T, &T) -> bool) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+}
+
+#[async_trait::async_trait]
+impl<ETH: EthApi> EthApi for EthPool<ETH> {
+    async fn get_state(&self, address: &str) -> anyhow::Result<State> {
+        self.agree("state", |eth| eth.get_state(address), same_state)
+            .await
+    }
+
+    async fn get_block_number(&self) -> anyhow::Result<u64> {
+        self.failover(|eth| eth.get_block_number()).await
+    }
+
+    async fn get_block_hash(&self, number: u64) -> anyhow::Result<NumAsHex> {
+        self.agree(
+            "block hash",
+            |eth| eth.get_block_hash(number),
+            |a: &NumAsHex, b: &NumAsHex| a.as_ref() == b.as_ref(),
+        )
+        .await
+    }
+
+    async fn get_updates(
+        &self,
+        address: &str,
+        from: u64,
+        to: u64,
+    ) -> anyhow::Result<Vec<Update>> {
+        self.agree(
+            "updates",
+            |eth| eth.get_updates(address, from, to),
+            |a: &Vec<Update>, b: &Vec<Update>| same_all(a, b, same_update),
+        )
+        .await
+    }
+
+    async fn get_messages(
+        &self,
+        address: &str,
+        from: u64,
+        to: u64,
+    ) -> anyhow::Result<Vec<Message>> {
+        self.agree(
+            "messages",
+            |eth| eth.get_messages(address, from, to),
+            |a: &Vec<Message>, b: &Vec<Message>| same_all(a, b, same_message),
+        )
+        .await
+    }
+}
+
 fn parse_hex_as_num(num: NumAsHex) -> anyhow::Result<u64> {
     let hex = num.as_ref().to_string();
     let hex = hex.strip_prefix("0x").unwrap_or(&hex);
@@ -137,6 +489,124 @@ fn parse_num_as_hex(value: &serde_json::Value) -> anyhow::Result<NumAsHex> {
         })
 }
 
+/// Split hex-encoded data of the log into 32-byte words.
+pub(crate) fn parse_words(data: &str) -> anyhow::Result<Vec<U256>> {
+    let data = data.strip_prefix("0x").unwrap_or(data);
+    if data.len() % 64 != 0 {
+        anyhow::bail!("Invalid log data length: {}", data.len());
+    }
+    (0..data.len())
+        .step_by(64)
+        .map(|i| U256::from_hex(&data[i..i + 64]))
+        .collect()
+}
+
+pub(crate) fn word_as_hex(word: &U256) -> anyhow::Result<NumAsHex> {
+    parse_num_as_hex(&serde_json::Value::String(word.into_str()))
+}
+
+pub(crate) fn word_as_num(word: &U256) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&word.as_ref()[24..32]);
+    u64::from_be_bytes(bytes)
+}
+
+fn parse_state_update_log(log: &serde_json::Value) -> anyhow::Result<Update> {
+    let data = log["data"]
+        .as_str()
+        .ok_or(anyhow::anyhow!("Log data is missing"))?;
+    let words = parse_words(data)?;
+    if words.len() != 3 {
+        anyhow::bail!("Unexpected LogStateUpdate data: {data}");
+    }
+    Ok(Update {
+        state: State {
+            state_root: word_as_hex(&words[0])?,
+            state_block_number: word_as_num(&words[1]),
+            state_block_hash: word_as_hex(&words[2])?,
+        },
+        eth_block_number: parse_num_as_hex(&log["blockNumber"])
+            .and_then(parse_hex_as_num)?,
+        eth_block_hash: parse_num_as_hex(&log["blockHash"])?,
+        eth_tx_hash: parse_num_as_hex(&log["transactionHash"])?,
+    })
+}
+
+fn parse_message_log(log: &serde_json::Value) -> anyhow::Result<Message> {
+    let topics = log["topics"]
+        .as_array()
+        .ok_or(anyhow::anyhow!("Log topics are missing"))?
+        .iter()
+        .map(|topic| {
+            topic
+                .as_str()
+                .ok_or(anyhow::anyhow!("Invalid log topic"))
+                .and_then(U256::from_hex)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if topics.len() != 4 {
+        anyhow::bail!("Unexpected LogMessageToL2 topics: {}", topics.len());
+    }
+    let (from, to, selector) = (&topics[1], &topics[2], &topics[3]);
+
+    let data = log["data"]
+        .as_str()
+        .ok_or(anyhow::anyhow!("Log data is missing"))?;
+    // (offset of the payload, nonce, fee, payload length, payload...)
+    let words = parse_words(data)?;
+    if words.len() < 4 || words.len() != 4 + word_as_num(&words[3]) as usize {
+        anyhow::bail!("Unexpected LogMessageToL2 data: {data}");
+    }
+    let nonce = &words[1];
+    let payload = &words[4..];
+
+    Ok(Message {
+        hash: l1_to_l2_message_hash(from, to, nonce, selector, payload),
+        eth_block_number: parse_num_as_hex(&log["blockNumber"])
+            .and_then(parse_hex_as_num)?,
+        eth_tx_hash: parse_num_as_hex(&log["transactionHash"])?,
+        eth_log_index: parse_num_as_hex(&log["logIndex"])
+            .and_then(parse_hex_as_num)?,
+    })
+}
+
+/// Hash of the L1->L2 message as computed by the core contract:
+/// `keccak256(from, to, nonce, selector, payload.length, payload)`.
+pub fn l1_to_l2_message_hash(
+    from: &U256,
+    to: &U256,
+    nonce: &U256,
+    selector: &U256,
+    payload: &[U256],
+) -> U256 {
+    let len = U256::from_u64(payload.len() as u64);
+    let head = [from, to, nonce, selector, &len];
+    keccak_words(head.into_iter().chain(payload))
+}
+
+/// Hash of the L2->L1 message as computed by the core contract:
+/// `keccak256(from, to, payload.length, payload)`.
+pub fn l2_to_l1_message_hash(from: &U256, to: &U256, payload: &[U256]) -> U256 {
+    let len = U256::from_u64(payload.len() as u64);
+    let head = [from, to, &len];
+    keccak_words(head.into_iter().chain(payload))
+}
+
+fn keccak_words<'a>(words: impl Iterator<Item = &'a U256>) -> U256 {
+    let input = words
+        .flat_map(|word| word.as_ref().iter().copied())
+        .collect::<Vec<_>>();
+    let mut output = [0u8; 32];
+    keccak_hash::keccak_256(&input, &mut output[..]);
+    U256(output)
+}
+
+pub(crate) fn encode_event_topic(signature: &str) -> String {
+    let mut output: [u8; 32] = Default::default();
+    keccak_hash::keccak_256(signature.as_bytes(), &mut output[..]);
+    format!("0x{}", hex::encode(output))
+}
+
 fn encode_ethereum_call_data(signature: &[u8]) -> String {
     let mut output: [u8; 32] = Default::default();
     keccak_hash::keccak_256(signature, &mut output[..]);
@@ -181,6 +651,143 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_encode_event_topic() {
+        assert_eq!(
+            encode_event_topic(LOG_STATE_UPDATE),
+            "0xd342ddf7a308dec111745b00315c14b7efb2bdae570a6856e088ed0c65a3576c"
+        );
+    }
+
+    #[test]
+    fn test_parse_state_update_log() -> anyhow::Result<()> {
+        let log = serde_json::json!({
+            "blockNumber": "0x107d209",
+            "blockHash": "0xabc",
+            "transactionHash": "0xdef",
+            "data": format!(
+                "0x{:0>64}{:0>64}{:0>64}",
+                "1234", "c4aa7", "212440a93e19eb76b3da51b13317152d9412913385d7004079c5b0ff6b224af"
+            )
+        });
+        let update = parse_state_update_log(&log)?;
+        assert_eq!(update.state.state_root.as_ref(), "0x1234");
+        assert_eq!(update.state.state_block_number, 805543);
+        assert_eq!(
+            update.state.state_block_hash.as_ref(),
+            "0x212440a93e19eb76b3da51b13317152d9412913385d7004079c5b0ff6b224af"
+        );
+        assert_eq!(update.eth_block_number, 17289737);
+        assert_eq!(update.eth_tx_hash.as_ref(), "0xdef");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_message_log() -> anyhow::Result<()> {
+        // L1 handler tx 0x65c4eff17ea2bf2a738e88269df164531e8e869ecc664e5ef170bb4b98ddceb
+        let log = serde_json::json!({
+            "blockNumber": "0x107d209",
+            "transactionHash": "0xdef",
+            "logIndex": "0x1f",
+            "topics": [
+                encode_event_topic(LOG_MESSAGE_TO_L2),
+                "0x000000000000000000000000c3511006c04ef1d78af4c8e0e74ec18a6e64ff9e",
+                "0x073314940630fd6dcda0d772d4c972c4e0a9946bef9dabf4ef84eda8ef542b82",
+                "0x02d757788a8d8d6f21d1cd40bce38a8222d70654214e96ff95d8086e684fbee5"
+            ],
+            "data": format!(
+                "0x{:0>64}{:0>64}{:0>64}{:0>64}{:0>64}{:0>64}{:0>64}",
+                "60", "b6e3d", "1", "3",
+                "3abeca1759abd4aadf23b9618858c4dfcca2ee587b04711d533deccb5945d0b",
+                "de0b6b3a7640000", "0"
+            )
+        });
+        let message = parse_message_log(&log)?;
+        assert_eq!(
+            message.hash.into_str(),
+            "0x1d4217e8fb440d1ccd86a15bb5f2c81462a4780f0dcb6c95cc5581509a92a90f"
+        );
+        assert_eq!(message.eth_block_number, 17289737);
+        assert_eq!(message.eth_tx_hash.as_ref(), "0xdef");
+        assert_eq!(message.eth_log_index, 31);
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_message_topic() {
+        assert_eq!(
+            encode_event_topic(LOG_MESSAGE_TO_L2),
+            "0xdb80dd488acf86d17c747445b0eabb5d57c541d3bd7b6b87af987858e5066b2b"
+        );
+    }
+
+    #[derive(Clone)]
+    struct Fixed(Option<u64>);
+
+    #[async_trait::async_trait]
+    impl EthApi for Fixed {
+        async fn get_state(&self, _address: &str) -> anyhow::Result<State> {
+            let number = self.0.ok_or(anyhow::anyhow!("Provider is down"))?;
+            Ok(State {
+                state_root: NumAsHex::try_new("0x1")?,
+                state_block_hash: NumAsHex::try_new("0x2")?,
+                state_block_number: number,
+            })
+        }
+
+        async fn get_block_number(&self) -> anyhow::Result<u64> {
+            self.0.ok_or(anyhow::anyhow!("Provider is down"))
+        }
+
+        async fn get_block_hash(
+            &self,
+            _number: u64,
+        ) -> anyhow::Result<NumAsHex> {
+            anyhow::bail!("Not supported")
+        }
+
+        async fn get_updates(
+            &self,
+            _address: &str,
+            _from: u64,
+            _to: u64,
+        ) -> anyhow::Result<Vec<Update>> {
+            anyhow::bail!("Not supported")
+        }
+
+        async fn get_messages(
+            &self,
+            _address: &str,
+            _from: u64,
+            _to: u64,
+        ) -> anyhow::Result<Vec<Message>> {
+            anyhow::bail!("Not supported")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool_failover() -> anyhow::Result<()> {
+        let pool = EthPool::new(vec![Fixed(None), Fixed(Some(42))]);
+        assert_eq!(pool.get_block_number().await?, 42);
+        assert_eq!(pool.get_state("0x0").await?.state_block_number, 42);
+
+        let pool = EthPool::<Fixed>::new(vec![]);
+        assert!(pool.get_block_number().await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pool_quorum() -> anyhow::Result<()> {
+        let providers = vec![Fixed(Some(1)), Fixed(Some(2)), Fixed(Some(2))];
+
+        let pool = EthPool::new(providers.clone()).with_quorum(2);
+        assert_eq!(pool.get_state("0x0").await?.state_block_number, 2);
+
+        let pool = EthPool::new(providers).with_quorum(3);
+        assert!(pool.get_state("0x0").await.is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_parse_hex_as_num() -> anyhow::Result<()> {
         let num = NumAsHex::try_new("0x107d209")?;
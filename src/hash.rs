@@ -0,0 +1,986 @@
+//! Starknet hashes (Pedersen and Poseidon over felts) used to verify blocks
+//! fetched from the gateway: transaction hashes, transaction and event
+//! commitments and the block hash itself, and classes downloaded from
+//! the gateway: Cairo 0 and Sierra class hashes and compiled class hashes.
+//! Block signatures are checked against the sequencer public key.
+
+use std::collections::BTreeMap;
+
+use once_cell::sync::Lazy;
+use serde_json::{value::RawValue, Value};
+use starknet_crypto::{pedersen_hash, poseidon_hash_many, FieldElement};
+
+use crate::{
+    api::gen::{BlockWithTxs, DeclareTxn, Felt, InvokeTxnKind, Txn},
+    seq::dto,
+    util::tx_hash,
+};
+
+/// Height of the transaction and event commitment trees.
+const COMMITMENT_HEIGHT: usize = 64;
+
+/// Contract addresses are taken modulo `2^251 - 256`.
+static ADDRESS_BOUND: Lazy<FieldElement> = Lazy::new(|| {
+    FieldElement::from_hex_be(
+        "0x7ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff00",
+    )
+    .expect("address bound")
+});
+
+/// `starknet_keccak("constructor")`
+static CONSTRUCTOR_SELECTOR: Lazy<FieldElement> = Lazy::new(|| {
+    FieldElement::from_hex_be(
+        "0x28ffe4ff0f226a9107253e17a904099aa4f63a02a5621de0576e5aa71bc5194",
+    )
+    .expect("constructor selector")
+});
+
+pub fn felt(felt: &Felt) -> anyhow::Result<FieldElement> {
+    FieldElement::from_hex_be(felt.as_ref())
+        .map_err(|e| anyhow::anyhow!("Invalid felt '{}': {e}", felt.as_ref()))
+}
+
+fn felts(items: &[Felt]) -> anyhow::Result<Vec<FieldElement>> {
+    items.iter().map(felt).collect()
+}
+
+/// Cairo short string (up to 31 ASCII chars) as a felt.
+fn short(s: &str) -> FieldElement {
+    FieldElement::from_byte_slice_be(s.as_bytes()).expect("short string")
+}
+
+/// Pedersen hash of the array: `h(h(...h(h(0, a1), a2)..., an), n)`.
+pub fn pedersen_array(items: &[FieldElement]) -> FieldElement {
+    let hash = items
+        .iter()
+        .fold(FieldElement::ZERO, |acc, item| pedersen_hash(&acc, item));
+    pedersen_hash(&hash, &FieldElement::from(items.len() as u64))
+}
+
+/// Poseidon hash of the array.
+pub fn poseidon_array(items: &[FieldElement]) -> FieldElement {
+    poseidon_hash_many(items)
+}
+
+/// Address of the contract deployed with the given class and calldata.
+pub fn contract_address(
+    deployer: FieldElement,
+    salt: FieldElement,
+    class_hash: FieldElement,
+    calldata: &[FieldElement],
+) -> FieldElement {
+    let hash = pedersen_array(&[
+        short("STARKNET_CONTRACT_ADDRESS"),
+        deployer,
+        salt,
+        class_hash,
+        pedersen_array(calldata),
+    ]);
+    if hash >= *ADDRESS_BOUND {
+        hash - *ADDRESS_BOUND
+    } else {
+        hash
+    }
+}
+
+/// Root of the height-64 Patricia tree with the leaves keyed by index.
+pub fn commitment(leaves: &[FieldElement]) -> FieldElement {
+    let leaves = leaves
+        .iter()
+        .enumerate()
+        .map(|(index, leaf)| (index as u64, *leaf))
+        .filter(|(_, leaf)| *leaf != FieldElement::ZERO)
+        .collect::<Vec<_>>();
+    if leaves.is_empty() {
+        return FieldElement::ZERO;
+    }
+    node(&leaves, 0)
+}
+
+fn bit(key: u64, depth: usize) -> bool {
+    (key >> (COMMITMENT_HEIGHT - 1 - depth)) & 1 == 1
+}
+
+/// Hash of the node at `depth`, including the edge starting there (if any).
+fn node(leaves: &[(u64, FieldElement)], depth: usize) -> FieldElement {
+    let (first, last) = (leaves[0].0, leaves[leaves.len() - 1].0);
+    let mut end = depth;
+    while end < COMMITMENT_HEIGHT && bit(first, end) == bit(last, end) {
+        end += 1;
+    }
+    let child = binary(leaves, end);
+    if end == depth {
+        return child;
+    }
+
+    let length = end - depth;
+    let path = first >> (COMMITMENT_HEIGHT - end);
+    let path = if length < 64 {
+        path & ((1 << length) - 1)
+    } else {
+        path
+    };
+    pedersen_hash(&child, &FieldElement::from(path))
+        + FieldElement::from(length as u64)
+}
+
+/// Hash of the binary node (or the leaf) at `depth`.
+fn binary(leaves: &[(u64, FieldElement)], depth: usize) -> FieldElement {
+    if depth == COMMITMENT_HEIGHT {
+        return leaves[0].1;
+    }
+    let split = leaves.partition_point(|(key, _)| !bit(*key, depth));
+    let (left, right) = leaves.split_at(split);
+    pedersen_hash(&node(left, depth + 1), &node(right, depth + 1))
+}
+
+/// Possible hashes of the transaction: the current one first, followed
+/// by the legacy ones (used by the early versions of Starknet).
+pub fn tx_hashes(
+    tx: &Txn,
+    chain_id: FieldElement,
+) -> anyhow::Result<Vec<FieldElement>> {
+    let zero = FieldElement::ZERO;
+    let hashes = match tx {
+        Txn::InvokeTxn(txn) => {
+            let common =
+                &txn.common_txn_properties.broadcasted_txn_common_properties;
+            let max_fee = felt(&common.max_fee)?;
+            match &txn.invoke_txn_kind {
+                InvokeTxnKind::FunctionCall(call) => {
+                    let address = felt(&call.contract_address.0)?;
+                    let selector = felt(&call.entry_point_selector)?;
+                    let calldata = pedersen_array(&felts(&call.calldata)?);
+                    vec![
+                        pedersen_array(&[
+                            short("invoke"),
+                            zero,
+                            address,
+                            selector,
+                            calldata,
+                            max_fee,
+                            chain_id,
+                        ]),
+                        pedersen_array(&[
+                            short("invoke"),
+                            address,
+                            selector,
+                            calldata,
+                            chain_id,
+                        ]),
+                    ]
+                }
+                InvokeTxnKind::InvokeTxnV1(call) => {
+                    vec![pedersen_array(&[
+                        short("invoke"),
+                        FieldElement::from_hex_be(common.version.as_ref())?,
+                        felt(&call.sender_address.0)?,
+                        zero,
+                        pedersen_array(&felts(&call.calldata)?),
+                        max_fee,
+                        chain_id,
+                        felt(&common.nonce)?,
+                    ])]
+                }
+            }
+        }
+        Txn::DeclareTxn(DeclareTxn::DeclareTxnV1(txn)) => {
+            let common =
+                &txn.common_txn_properties.broadcasted_txn_common_properties;
+            let version = FieldElement::from_hex_be(common.version.as_ref())?;
+            let class_hash = felt(&txn.class_hash)?;
+            let (calldata, last) = if version == zero {
+                (pedersen_array(&[]), class_hash)
+            } else {
+                (pedersen_array(&[class_hash]), felt(&common.nonce)?)
+            };
+            vec![pedersen_array(&[
+                short("declare"),
+                version,
+                felt(&txn.sender_address.0)?,
+                zero,
+                calldata,
+                felt(&common.max_fee)?,
+                chain_id,
+                last,
+            ])]
+        }
+        Txn::DeclareTxn(DeclareTxn::DeclareTxnV2(txn)) => {
+            let declare = &txn.declare_txn_v1;
+            let common = &declare
+                .common_txn_properties
+                .broadcasted_txn_common_properties;
+            let compiled_class_hash = txn
+                .compiled_class_hash
+                .as_ref()
+                .map(felt)
+                .transpose()?
+                .unwrap_or_default();
+            vec![pedersen_array(&[
+                short("declare"),
+                FieldElement::from_hex_be(common.version.as_ref())?,
+                felt(&declare.sender_address.0)?,
+                zero,
+                pedersen_array(&[felt(&declare.class_hash)?]),
+                felt(&common.max_fee)?,
+                chain_id,
+                felt(&common.nonce)?,
+                compiled_class_hash,
+            ])]
+        }
+        Txn::DeployAccountTxn(txn) => {
+            let common =
+                &txn.common_txn_properties.broadcasted_txn_common_properties;
+            let props = &txn.deploy_account_txn_properties;
+            let class_hash = felt(&props.class_hash)?;
+            let salt = felt(&props.contract_address_salt)?;
+            let calldata = felts(&props.constructor_calldata)?;
+            let address = contract_address(zero, salt, class_hash, &calldata);
+            let calldata = [vec![class_hash, salt], calldata].concat();
+            vec![pedersen_array(&[
+                short("deploy_account"),
+                FieldElement::from_hex_be(common.version.as_ref())?,
+                address,
+                zero,
+                pedersen_array(&calldata),
+                felt(&common.max_fee)?,
+                chain_id,
+                felt(&common.nonce)?,
+            ])]
+        }
+        Txn::DeployTxn(txn) => {
+            let props = &txn.deploy_txn_properties;
+            let calldata = felts(&props.constructor_calldata)?;
+            let address = contract_address(
+                zero,
+                felt(&props.contract_address_salt)?,
+                felt(&txn.class_hash)?,
+                &calldata,
+            );
+            let calldata = pedersen_array(&calldata);
+            vec![
+                pedersen_array(&[
+                    short("deploy"),
+                    FieldElement::from_hex_be(props.version.as_ref())?,
+                    address,
+                    *CONSTRUCTOR_SELECTOR,
+                    calldata,
+                    zero,
+                    chain_id,
+                ]),
+                pedersen_array(&[
+                    short("deploy"),
+                    address,
+                    *CONSTRUCTOR_SELECTOR,
+                    calldata,
+                    chain_id,
+                ]),
+            ]
+        }
+        Txn::L1HandlerTxn(txn) => {
+            let call = &txn.function_call;
+            let version = FieldElement::from_hex_be(txn.version.as_ref())?;
+            let address = felt(&call.contract_address.0)?;
+            let selector = felt(&call.entry_point_selector)?;
+            let calldata = pedersen_array(&felts(&call.calldata)?);
+            let head = [
+                short("l1_handler"),
+                version,
+                address,
+                selector,
+                calldata,
+                zero,
+                chain_id,
+            ];
+            let nonce = FieldElement::from_hex_be(txn.nonce.as_ref())?;
+            vec![
+                pedersen_array(&[&head[..], &[nonce]].concat()),
+                pedersen_array(&head),
+                pedersen_array(&[
+                    short("invoke"),
+                    address,
+                    selector,
+                    calldata,
+                    chain_id,
+                ]),
+            ]
+        }
+    };
+    Ok(hashes)
+}
+
+/// Signature of the transaction as committed to in the block. Before
+/// Starknet 0.11.1 only signatures of invoke transactions were committed.
+fn tx_signature(tx: &Txn, legacy: bool) -> &[Felt] {
+    let signature = match tx {
+        Txn::InvokeTxn(txn) => Some(&txn.common_txn_properties),
+        Txn::DeclareTxn(DeclareTxn::DeclareTxnV1(txn)) if !legacy => {
+            Some(&txn.common_txn_properties)
+        }
+        Txn::DeclareTxn(DeclareTxn::DeclareTxnV2(txn)) if !legacy => {
+            Some(&txn.declare_txn_v1.common_txn_properties)
+        }
+        Txn::DeployAccountTxn(txn) if !legacy => {
+            Some(&txn.common_txn_properties)
+        }
+        _ => None,
+    };
+    signature
+        .map(|common| {
+            common
+                .broadcasted_txn_common_properties
+                .signature
+                .0
+                .as_slice()
+        })
+        .unwrap_or_default()
+}
+
+fn tx_commitment(
+    block: &BlockWithTxs,
+    legacy: bool,
+) -> anyhow::Result<FieldElement> {
+    let leaves = block
+        .block_body_with_txs
+        .transactions
+        .iter()
+        .map(|tx| {
+            let signature = felts(tx_signature(tx, legacy))?;
+            Ok(pedersen_hash(
+                &felt(tx_hash(tx))?,
+                &pedersen_array(&signature),
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(commitment(&leaves))
+}
+
+fn event_commitment(
+    block: &BlockWithTxs,
+) -> anyhow::Result<(u64, FieldElement)> {
+    let leaves = block
+        .receipts
+        .iter()
+        .flat_map(|receipt| receipt.events.iter())
+        .map(|event| {
+            Ok(pedersen_array(&[
+                felt(&event.from_address.0)?,
+                pedersen_array(&felts(&event.event_content.keys)?),
+                pedersen_array(&felts(&event.event_content.data)?),
+            ]))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok((leaves.len() as u64, commitment(&leaves)))
+}
+
+/// Recompute hashes of all transactions of the block, the transaction and
+/// event commitments and the block hash, and check them against the ones
+/// reported by the gateway.
+pub fn verify_block(
+    block: &BlockWithTxs,
+    chain_id: &str,
+) -> anyhow::Result<()> {
+    let chain_id = FieldElement::from_hex_be(chain_id)?;
+    let txs = &block.block_body_with_txs.transactions;
+    for (index, tx) in txs.iter().enumerate() {
+        let hash = felt(tx_hash(tx))?;
+        if !tx_hashes(tx, chain_id)?.contains(&hash) {
+            anyhow::bail!(
+                "Transaction hash mismatch: index={index} hash={}",
+                tx_hash(tx).as_ref()
+            );
+        }
+    }
+
+    let header = &block.block_header;
+    let number = FieldElement::from(*header.block_number.as_ref() as u64);
+    let state_root = felt(&header.new_root)?;
+    let sequencer = header
+        .sequencer_address
+        .as_ref()
+        .map(felt)
+        .transpose()?
+        .unwrap_or_default();
+    let timestamp = FieldElement::from(header.timestamp as u64);
+    let parent_hash = felt(&header.parent_hash.0)?;
+    let tx_count = FieldElement::from(txs.len() as u64);
+    let (event_count, event_commitment) = event_commitment(block)?;
+    let event_count = FieldElement::from(event_count);
+
+    let expected = felt(&header.block_hash.0)?;
+    let zero = FieldElement::ZERO;
+    for legacy in [false, true] {
+        let tx_commitment = tx_commitment(block, legacy)?;
+        let hashes = [
+            pedersen_array(&[
+                number,
+                state_root,
+                sequencer,
+                timestamp,
+                tx_count,
+                tx_commitment,
+                event_count,
+                event_commitment,
+                zero,
+                zero,
+                parent_hash,
+            ]),
+            // Blocks before Starknet 0.7 commit to the chain id as well
+            // (sequencer, timestamp and events are zero)
+            pedersen_array(&[
+                number,
+                state_root,
+                zero,
+                zero,
+                tx_count,
+                tx_commitment,
+                zero,
+                zero,
+                zero,
+                zero,
+                chain_id,
+                parent_hash,
+            ]),
+        ];
+        if hashes.contains(&expected) {
+            return Ok(());
+        }
+    }
+    anyhow::bail!("Block hash mismatch: hash={}", header.block_hash.0.as_ref())
+}
+
+/// Check the sequencer signature `[r, s]` of the block against the public
+/// key of the network. The signed message is the block hash, or (before
+/// Starknet 0.13.2) the hash of the block hash and the state diff
+/// commitment from the `signature_input`.
+pub fn verify_signature(
+    public_key: &str,
+    block_hash: &Felt,
+    signature: &dto::BlockSignature,
+) -> anyhow::Result<()> {
+    let hash = felt(block_hash)?;
+    if felt(&signature.block_hash)? != hash {
+        anyhow::bail!(
+            "Block signature mismatch: hash={} signed={}",
+            block_hash.as_ref(),
+            signature.block_hash.as_ref()
+        );
+    }
+    let message = match signature.signature_input.as_ref() {
+        Some(input) => {
+            if felt(&input.block_hash)? != hash {
+                anyhow::bail!(
+                    "Block signature mismatch: hash={} signed={}",
+                    block_hash.as_ref(),
+                    input.block_hash.as_ref()
+                );
+            }
+            pedersen_hash(&hash, &felt(&input.state_diff_commitment)?)
+        }
+        None => hash,
+    };
+    let (r, s) = match signature.signature.as_slice() {
+        [r, s] => (felt(r)?, felt(s)?),
+        _ => anyhow::bail!("Block is not signed: hash={}", block_hash.as_ref()),
+    };
+    let public_key = FieldElement::from_hex_be(public_key)?;
+    let valid = starknet_crypto::verify(&public_key, &message, &r, &s)
+        .map_err(|e| anyhow::anyhow!("Invalid block signature: {e}"))?;
+    if !valid {
+        anyhow::bail!("Block signature mismatch: hash={}", block_hash.as_ref());
+    }
+    Ok(())
+}
+
+/// `keccak256` of the data truncated to 250 bits.
+pub fn starknet_keccak(data: &[u8]) -> FieldElement {
+    let mut output = [0u8; 32];
+    keccak_hash::keccak_256(data, &mut output[..]);
+    output[0] &= 0x03;
+    FieldElement::from_bytes_be(&output).expect("250-bit felt")
+}
+
+/// Felt from a JSON string (hex) or number.
+fn json_felt(value: &Value) -> anyhow::Result<FieldElement> {
+    match value {
+        Value::String(hex) => FieldElement::from_hex_be(hex)
+            .map_err(|e| anyhow::anyhow!("Invalid felt '{hex}': {e}")),
+        Value::Number(n) => n
+            .as_u64()
+            .map(FieldElement::from)
+            .ok_or_else(|| anyhow::anyhow!("Invalid felt: {n}")),
+        _ => anyhow::bail!("Invalid felt: {value}"),
+    }
+}
+
+fn json_felts(value: &Value) -> anyhow::Result<Vec<FieldElement>> {
+    value
+        .as_array()
+        .map(|items| items.iter().map(json_felt).collect())
+        .unwrap_or_else(|| Ok(Vec::new()))
+}
+
+fn json_short(value: &Value) -> anyhow::Result<FieldElement> {
+    value
+        .as_str()
+        .map(short)
+        .ok_or_else(|| anyhow::anyhow!("Invalid short string: {value}"))
+}
+
+/// Entry points of the kind in the order the class hash commits to.
+const ENTRY_POINT_KINDS: [&str; 3] = ["EXTERNAL", "L1_HANDLER", "CONSTRUCTOR"];
+
+fn entry_points<'a>(class: &'a Value, kind: &str) -> &'a [Value] {
+    class["entry_points_by_type"][kind]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+/// Hash of the class as served by the gateway (JSON text, as numbers
+/// of Cairo 0 programs do not fit `serde_json::Value`): Sierra classes
+/// come with `sierra_program`, others are Cairo 0 classes.
+pub fn class_hash(json: &str) -> anyhow::Result<FieldElement> {
+    let class: Value = serde_json::from_str(json)?;
+    if class.get("sierra_program").is_some() {
+        sierra_class_hash(&class)
+    } else {
+        cairo0_class_hash(&class, json)
+    }
+}
+
+fn sierra_class_hash(class: &Value) -> anyhow::Result<FieldElement> {
+    let version = class["contract_class_version"].as_str().unwrap_or("0.1.0");
+    let mut items = vec![short(&format!("CONTRACT_CLASS_V{version}"))];
+    for kind in ENTRY_POINT_KINDS {
+        let mut felts = Vec::new();
+        for entry_point in entry_points(class, kind) {
+            felts.push(json_felt(&entry_point["selector"])?);
+            felts.push(json_felt(&entry_point["function_idx"])?);
+        }
+        items.push(poseidon_array(&felts));
+    }
+    let abi = match &class["abi"] {
+        Value::String(abi) => abi.clone(),
+        Value::Null => String::new(),
+        abi => serde_json::to_string(abi)?,
+    };
+    items.push(starknet_keccak(abi.as_bytes()));
+    items.push(poseidon_array(&json_felts(&class["sierra_program"])?));
+    Ok(poseidon_array(&items))
+}
+
+fn cairo0_class_hash(
+    class: &Value,
+    json: &str,
+) -> anyhow::Result<FieldElement> {
+    // API version
+    let mut items = vec![FieldElement::ZERO];
+    for kind in ENTRY_POINT_KINDS {
+        let mut felts = Vec::new();
+        for entry_point in entry_points(class, kind) {
+            felts.push(json_felt(&entry_point["selector"])?);
+            felts.push(json_felt(&entry_point["offset"])?);
+        }
+        items.push(pedersen_array(&felts));
+    }
+    let program = &class["program"];
+    let builtins = program["builtins"]
+        .as_array()
+        .map(|items| items.iter().map(json_short).collect())
+        .unwrap_or_else(|| Ok(Vec::new()))?;
+    items.push(pedersen_array(&builtins));
+    items.push(hinted_class_hash(json)?);
+    items.push(pedersen_array(&json_felts(&program["data"])?));
+    Ok(pedersen_array(&items))
+}
+
+/// JSON value with numbers kept as written: Python's `json` keeps integers
+/// of any size, while `serde_json::Value` turns the ones above `u64` into
+/// floats (and `RC_BOUND = 2**128` is found in most Cairo 0 programs).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Json {
+    /// Number, `true`, `false` or `null` as written.
+    Literal(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+const NULL: &str = "null";
+
+impl Json {
+    pub fn parse(json: &str) -> anyhow::Result<Self> {
+        let raw: &RawValue = serde_json::from_str(json)?;
+        Self::from_raw(raw)
+    }
+
+    fn from_raw(raw: &RawValue) -> anyhow::Result<Self> {
+        let json = raw.get().trim();
+        let value = match json.as_bytes().first() {
+            Some(b'{') => {
+                let fields: BTreeMap<String, &RawValue> =
+                    serde_json::from_str(json)?;
+                let fields = fields
+                    .into_iter()
+                    .map(|(key, raw)| Ok((key, Self::from_raw(raw)?)))
+                    .collect::<anyhow::Result<_>>()?;
+                Self::Object(fields)
+            }
+            Some(b'[') => {
+                let items: Vec<&RawValue> = serde_json::from_str(json)?;
+                let items = items
+                    .into_iter()
+                    .map(Self::from_raw)
+                    .collect::<anyhow::Result<_>>()?;
+                Self::Array(items)
+            }
+            Some(b'"') => Self::String(serde_json::from_str(json)?),
+            _ => Self::Literal(json.to_string()),
+        };
+        Ok(value)
+    }
+
+    fn is_null(&self) -> bool {
+        matches!(self, Self::Literal(literal) if literal == NULL)
+    }
+
+    fn is_empty_array(&self) -> bool {
+        matches!(self, Self::Array(items) if items.is_empty())
+    }
+}
+
+/// Keccak of the ABI and the program without debug info, serialized the
+/// way Python's `json.dumps(..., sort_keys=True)` does it.
+fn hinted_class_hash(json: &str) -> anyhow::Result<FieldElement> {
+    let mut class = match Json::parse(json)? {
+        Json::Object(fields) => fields,
+        _ => anyhow::bail!("Invalid class: not an object"),
+    };
+    let mut program = match class.remove("program") {
+        Some(Json::Object(fields)) => fields,
+        _ => anyhow::bail!("Invalid class: missing program"),
+    };
+    program.insert("debug_info".to_string(), Json::Literal(NULL.to_string()));
+
+    match program.get_mut("attributes") {
+        Some(Json::Array(attributes)) if !attributes.is_empty() => {
+            for attribute in attributes.iter_mut() {
+                if let Json::Object(attribute) = attribute {
+                    let no_scopes = attribute
+                        .get("accessible_scopes")
+                        .map(Json::is_empty_array)
+                        .unwrap_or_default();
+                    if no_scopes {
+                        attribute.remove("accessible_scopes");
+                    }
+                    let no_tracking = attribute
+                        .get("flow_tracking_data")
+                        .map(Json::is_null)
+                        .unwrap_or_default();
+                    if no_tracking {
+                        attribute.remove("flow_tracking_data");
+                    }
+                }
+            }
+        }
+        _ => {
+            program.remove("attributes");
+        }
+    }
+
+    // Classes compiled before Cairo 0.10 hash named tuples as `(a : felt)`
+    if !program.contains_key("compiler_version") {
+        for key in ["identifiers", "reference_manager"] {
+            if let Some(value) = program.get_mut(key) {
+                add_space_before_colon(value);
+            }
+        }
+    }
+
+    let abi = class
+        .remove("abi")
+        .unwrap_or_else(|| Json::Literal(NULL.to_string()));
+    let input = BTreeMap::from([
+        ("abi".to_string(), abi),
+        ("program".to_string(), Json::Object(program)),
+    ]);
+    let mut json = String::new();
+    python_json(&Json::Object(input), &mut json);
+    Ok(starknet_keccak(json.as_bytes()))
+}
+
+fn add_space_before_colon(value: &mut Json) {
+    match value {
+        Json::Array(items) => items.iter_mut().for_each(add_space_before_colon),
+        Json::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                match (key.as_str(), value) {
+                    ("cairo_type" | "value", Json::String(s)) => {
+                        *s = s.replace(": ", " : ").replace("  :", " :");
+                    }
+                    (_, value) => add_space_before_colon(value),
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Serialize the value as Python's `json.dumps(value, sort_keys=True)`:
+/// `", "` and `": "` separators and non-ASCII characters escaped.
+pub fn python_json(value: &Json, out: &mut String) {
+    match value {
+        Json::Literal(literal) => out.push_str(literal),
+        Json::String(s) => python_str(s, out),
+        Json::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                python_json(item, out);
+            }
+            out.push(']');
+        }
+        Json::Object(fields) => {
+            // Keys are sorted by the map
+            out.push('{');
+            for (i, (key, value)) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                python_str(key, out);
+                out.push_str(": ");
+                python_json(value, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn python_str(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0c}' => out.push_str("\\f"),
+            ' '..='~' => out.push(c),
+            c => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    out.push_str(&format!("\\u{unit:04x}"));
+                }
+            }
+        }
+    }
+    out.push('"');
+}
+
+/// Hash of the CASM (Cairo 1 compiled class).
+pub fn compiled_class_hash(casm: &Value) -> anyhow::Result<FieldElement> {
+    let mut items = vec![short("COMPILED_CLASS_V1")];
+    for kind in ENTRY_POINT_KINDS {
+        let mut felts = Vec::new();
+        for entry_point in entry_points(casm, kind) {
+            felts.push(json_felt(&entry_point["selector"])?);
+            felts.push(json_felt(&entry_point["offset"])?);
+            let builtins = entry_point["builtins"]
+                .as_array()
+                .map(|items| items.iter().map(json_short).collect())
+                .unwrap_or_else(|| Ok(Vec::new()))?;
+            felts.push(poseidon_array(&builtins));
+        }
+        items.push(poseidon_array(&felts));
+    }
+    let bytecode = json_felts(&casm["bytecode"])?;
+    let bytecode = match casm.get("bytecode_segment_lengths") {
+        Some(lengths) => {
+            let (hash, length) = segment_hash(lengths, &bytecode, 0)?;
+            if length != bytecode.len() {
+                anyhow::bail!("Invalid CASM: bytecode segments do not match");
+            }
+            hash
+        }
+        None => poseidon_array(&bytecode),
+    };
+    items.push(bytecode);
+    Ok(poseidon_array(&items))
+}
+
+/// Hash and length of the bytecode segment starting at the offset: a leaf
+/// segment is hashed as is, a node is `1 + h(length, hash, ...)`.
+fn segment_hash(
+    lengths: &Value,
+    bytecode: &[FieldElement],
+    offset: usize,
+) -> anyhow::Result<(FieldElement, usize)> {
+    match lengths {
+        Value::Number(length) => {
+            let length = length.as_u64().unwrap_or_default() as usize;
+            let segment =
+                bytecode.get(offset..offset + length).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Invalid CASM: bytecode segment out of range"
+                    )
+                })?;
+            Ok((poseidon_array(segment), length))
+        }
+        Value::Array(children) => {
+            let mut items = Vec::with_capacity(children.len() * 2);
+            let mut total = 0;
+            for child in children {
+                let (hash, length) =
+                    segment_hash(child, bytecode, offset + total)?;
+                items.push(FieldElement::from(length as u64));
+                items.push(hash);
+                total += length;
+            }
+            Ok((poseidon_array(&items) + FieldElement::ONE, total))
+        }
+        _ => anyhow::bail!("Invalid CASM: bytecode segment lengths"),
+    }
+}
+
+/// Check the class downloaded from the gateway (JSON text) against its hash.
+pub fn verify_class(hash: &str, class: &str) -> anyhow::Result<()> {
+    let computed = class_hash(class)?;
+    if computed != FieldElement::from_hex_be(hash)? {
+        anyhow::bail!(
+            "Class hash mismatch: hash={hash} computed={computed:#x}"
+        );
+    }
+    Ok(())
+}
+
+/// Check the CASM downloaded from the gateway against the compiled class
+/// hash from the declaration.
+pub fn verify_compiled_class(
+    compiled: &str,
+    casm: &Value,
+) -> anyhow::Result<()> {
+    let computed = compiled_class_hash(casm)?;
+    if computed != FieldElement::from_hex_be(compiled)? {
+        anyhow::bail!(
+            "Compiled class hash mismatch: hash={compiled} computed={computed:#x}"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commitment() {
+        let a = FieldElement::from(1u64);
+        let b = FieldElement::from(2u64);
+
+        assert_eq!(commitment(&[]), FieldElement::ZERO);
+
+        // Single leaf: an edge of full height from the root to the leaf
+        let edge = pedersen_hash(&a, &FieldElement::ZERO)
+            + FieldElement::from(COMMITMENT_HEIGHT as u64);
+        assert_eq!(commitment(&[a]), edge);
+
+        // Leaves 0 and 1: an edge of length 63 to the binary node
+        let node = pedersen_hash(&a, &b);
+        let edge = pedersen_hash(&node, &FieldElement::ZERO)
+            + FieldElement::from(COMMITMENT_HEIGHT as u64 - 1);
+        assert_eq!(commitment(&[a, b]), edge);
+
+        // Zero leaves are not part of the tree
+        let edge = pedersen_hash(&b, &FieldElement::from(1u64))
+            + FieldElement::from(COMMITMENT_HEIGHT as u64);
+        assert_eq!(commitment(&[FieldElement::ZERO, b]), edge);
+    }
+
+    #[test]
+    fn test_starknet_keccak() {
+        assert_eq!(starknet_keccak(b"constructor"), *CONSTRUCTOR_SELECTOR);
+    }
+
+    #[test]
+    fn test_python_json() -> anyhow::Result<()> {
+        let value = Json::parse(
+            r#"{"b": [1, "café", null, 340282366920938463463374607431768211456],
+                "a": {"d": "\"q\"\n", "c": true}}"#,
+        )?;
+        let mut json = String::new();
+        python_json(&value, &mut json);
+        assert_eq!(
+            json,
+            r#"{"a": {"c": true, "d": "\"q\"\n"}, "b": [1, "café", null, 340282366920938463463374607431768211456]}"#
+        );
+
+        let mut value = Json::parse(
+            r#"{"members": {"x": {"cairo_type": "(a: felt, b : felt)"}},
+                "value": "cast(fp, (a: felt))"}"#,
+        )?;
+        add_space_before_colon(&mut value);
+        let mut json = String::new();
+        python_json(&value, &mut json);
+        assert_eq!(
+            json,
+            r#"{"members": {"x": {"cairo_type": "(a : felt, b : felt)"}}, "value": "cast(fp, (a : felt))"}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_class_hash() -> anyhow::Result<()> {
+        let json = std::fs::read_to_string("etc/class.json")?;
+        let hash = class_hash(&json)?;
+        let edit = |f: &dyn Fn(&mut BTreeMap<String, Json>)| {
+            let mut class = Json::parse(&json)?;
+            if let Json::Object(fields) = &mut class {
+                f(fields);
+            }
+            let mut edited = String::new();
+            python_json(&class, &mut edited);
+            class_hash(&edited)
+        };
+
+        // Neither the formatting nor the debug info are part of the hash
+        assert_eq!(edit(&|_| ())?, hash);
+        let stripped = edit(&|class| {
+            if let Some(Json::Object(program)) = class.get_mut("program") {
+                program.insert("debug_info".to_string(), Json::Array(vec![]));
+            }
+        })?;
+        assert_eq!(stripped, hash);
+
+        let tampered = edit(&|class| {
+            class.insert("abi".to_string(), Json::Array(vec![]));
+        })?;
+        assert_ne!(tampered, hash);
+
+        // Big integers are hashed as written, not as floats
+        let rounded = json.replace(
+            "20853273475220474000000000000",
+            "20853273475220474000000000001",
+        );
+        assert_ne!(rounded, json);
+        assert_ne!(class_hash(&rounded)?, hash);
+
+        verify_class(&format!("{hash:#x}"), &json)?;
+        assert!(verify_class("0x1", &json).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pedersen_array() {
+        let a = FieldElement::from(1u64);
+        let expected = pedersen_hash(
+            &pedersen_hash(&FieldElement::ZERO, &a),
+            &FieldElement::from(1u64),
+        );
+        assert_eq!(pedersen_array(&[a]), expected);
+    }
+}
@@ -1,10 +1,17 @@
 pub mod api;
 pub mod arg;
+pub mod cache;
 pub mod cfg;
+pub mod cmd;
 pub mod ctx;
 pub mod db;
 pub mod eth;
+pub mod hash;
+pub mod node;
+pub mod prune;
 pub mod rpc;
 pub mod seq;
+pub mod sub;
 pub mod sync;
+pub mod trie;
 pub mod util;
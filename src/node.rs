@@ -0,0 +1,470 @@
+use std::{net::SocketAddr, time::Duration};
+
+use metrics_exporter_prometheus::PrometheusHandle;
+use tokio::sync::{mpsc, oneshot, Mutex};
+use yakvdb::typed::DB;
+
+use crate::{
+    api::gen::Felt,
+    cfg::{Config, Direction},
+    ctx::{Context, Shared},
+    db::{Repo, Storage},
+    eth::{EthApi, NoEth},
+    rpc,
+    seq::{NoSeq, SeqApi},
+    sub::{self, Notification},
+    sync::{self, Event, Source},
+    util::{check_chain, detect_gaps, Waiter, U64},
+};
+
+const SECOND: Duration = Duration::from_secs(1);
+
+/// Number of recent blocks validated for parent links on start.
+pub const DEFAULT_CHAIN_CHECK: u64 = 2000;
+
+/// Embeddable node: wires storage, the gateway and L1 clients into the
+/// sync and the RPC server. Without the gateway (or L1) client the node
+/// does not sync blocks (or L1 state).
+pub struct Node<ETH, SEQ> {
+    eth: ETH,
+    seq: SEQ,
+    db: Storage,
+    config: Config,
+    metrics: Option<PrometheusHandle>,
+    with_eth: bool,
+    with_seq: bool,
+    chain_check: u64,
+}
+
+/// Handles of a started node.
+pub struct Handle<ETH, SEQ> {
+    pub ctx: Context<ETH, SEQ>,
+    /// Address the RPC server is listening on.
+    pub addr: SocketAddr,
+    pub server: Waiter,
+    /// Sync task (if the node syncs).
+    pub sync: Option<Waiter>,
+    /// Task following the synced range of the writer (if the node does not
+    /// sync).
+    pub refresh: Option<Waiter>,
+    /// Sender of sync events (if the node syncs).
+    pub tx: Option<mpsc::Sender<Event>>,
+}
+
+impl Node<NoEth, NoSeq> {
+    pub fn new(config: Config, db: Storage) -> Self {
+        Self {
+            eth: NoEth,
+            seq: NoSeq,
+            db,
+            config,
+            metrics: None,
+            with_eth: false,
+            with_seq: false,
+            chain_check: DEFAULT_CHAIN_CHECK,
+        }
+    }
+}
+
+impl<ETH, SEQ> Node<ETH, SEQ>
+where
+    ETH: EthApi,
+    SEQ: SeqApi,
+{
+    pub fn with_eth<E: EthApi>(self, eth: E) -> Node<E, SEQ> {
+        Node {
+            eth,
+            seq: self.seq,
+            db: self.db,
+            config: self.config,
+            metrics: self.metrics,
+            with_eth: true,
+            with_seq: self.with_seq,
+            chain_check: self.chain_check,
+        }
+    }
+
+    pub fn with_seq<S: SeqApi>(self, seq: S) -> Node<ETH, S> {
+        Node {
+            eth: self.eth,
+            seq,
+            db: self.db,
+            config: self.config,
+            metrics: self.metrics,
+            with_eth: self.with_eth,
+            with_seq: true,
+            chain_check: self.chain_check,
+        }
+    }
+
+    pub fn with_metrics(self, handle: PrometheusHandle) -> Self {
+        Self {
+            metrics: Some(handle),
+            ..self
+        }
+    }
+
+    pub fn with_chain_check(self, chain_check: u64) -> Self {
+        Self {
+            chain_check,
+            ..self
+        }
+    }
+
+    fn context(self) -> Context<ETH, SEQ> {
+        let ctx = Context::new(
+            self.eth,
+            self.seq,
+            Shared::default(),
+            self.db,
+            self.config,
+        );
+        if let Some(handle) = self.metrics {
+            ctx.with_metrics(handle)
+        } else {
+            ctx
+        }
+    }
+
+    /// Start the sync (if the gateway client is set) and the RPC server.
+    pub async fn start(self) -> anyhow::Result<Handle<ETH, SEQ>> {
+        let with_eth = self.with_eth;
+        let with_seq = self.with_seq;
+        let chain_check = self.chain_check;
+        let ctx = self.context();
+        if with_seq && ctx.config.verify && ctx.config.chain_id.is_empty() {
+            anyhow::bail!(
+                "Missing chain id for network '{}': blocks cannot be verified",
+                ctx.config.network
+            );
+        }
+        check_genesis(&ctx.db, &ctx.config).await?;
+
+        if !with_seq {
+            return serve(ctx).await;
+        }
+
+        let seq_poll_delay = ctx.config.seq_poll_delay;
+        let source = Source::new(ctx.clone());
+        source.add("uptime", sync::poll_uptime, SECOND).await;
+        source.add("gateway", sync::poll_seq, seq_poll_delay).await;
+        if with_eth {
+            let eth_poll_delay = ctx.config.eth_poll_delay;
+            source.add("ethereum", sync::poll_eth, eth_poll_delay).await;
+            source
+                .add("ethereum-logs", sync::poll_eth_logs, eth_poll_delay)
+                .await;
+        }
+        if ctx.config.state_tries() {
+            source
+                .add("states", sync::poll_states, seq_poll_delay)
+                .await;
+        } else if ctx.config.verify {
+            tracing::warn!(
+                from = ?ctx.config.sync_from,
+                pruning = ctx.config.pruning.is_some(),
+                "State tries are not maintained: state roots are not checked"
+            );
+        }
+        if ctx.config.pruning.is_some() {
+            source
+                .add("prune", sync::poll_prune, 10 * seq_poll_delay)
+                .await;
+        }
+        let tx = source.tx();
+        ctx.shared.lock().await.events = Some(tx.clone());
+        let syncer = sync::sync(source, sync::handler).await;
+
+        let range = refresh_range(&ctx.db, &ctx.shared).await?;
+        if let Some((lo, hi)) = range {
+            let floor = ctx.floor().await.unwrap_or_default();
+            if lo > floor && ctx.config.direction == Direction::Backward {
+                let key = U64::from_u64(lo);
+                let lo_block_hash =
+                    ctx.db.blocks_index.read().await.lookup(&key)?.unwrap();
+                let lo_block = ctx
+                    .db
+                    .blocks
+                    .get(&lo_block_hash.into_str())
+                    .await?
+                    .unwrap();
+                let lo_parent_hash = lo_block.block_header.parent_hash.0;
+                tx.send(Event::PullBlock(lo - 1, lo_parent_hash)).await.ok();
+            }
+            tracing::info!(synced=?(lo, hi), "Sync running");
+        } else {
+            tracing::info!("Sync running");
+        }
+
+        {
+            let db = ctx.db.clone();
+            tokio::spawn(async move {
+                match db.index_hashes().await {
+                    Ok(0) => (),
+                    Ok(indexed) => {
+                        tracing::info!(blocks = indexed, "Block hashes indexed")
+                    }
+                    Err(e) => {
+                        tracing::error!(error=?e, "Failed to index block hashes")
+                    }
+                }
+            });
+        }
+
+        {
+            let ctx = ctx.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let floor = ctx.floor().await.unwrap_or_default();
+                let mut missing = detect_gaps(ctx).await?;
+                missing.retain(|number| *number >= floor);
+                if !missing.is_empty() {
+                    tracing::info!(total = missing.len(), "Sync gap detected");
+                }
+                let zero = Felt::try_new("0x0")?;
+                for number in missing {
+                    let event = Event::PullBlock(number, zero.clone());
+                    tx.send(event).await?;
+                    tokio::time::sleep(SECOND).await;
+                }
+                Ok::<(), anyhow::Error>(())
+            });
+        }
+
+        {
+            let ctx = ctx.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                if let Some((number, hash)) =
+                    check_chain(ctx, chain_check).await?
+                {
+                    tracing::info!(at = number, "Broken chain detected");
+                    let event = Event::PullBlock(number, Felt::try_new(&hash)?);
+                    tx.send(event).await?;
+                } else {
+                    tracing::info!(
+                        length = chain_check,
+                        "Chain head validated successfully"
+                    );
+                }
+                Ok::<(), anyhow::Error>(())
+            });
+        }
+
+        let (addr, server) =
+            rpc::serve(&ctx.config.rpc_bind_addr, ctx.clone()).await;
+        tracing::info!(at=?addr, "RPC server listening");
+
+        Ok(Handle {
+            ctx,
+            addr,
+            server,
+            sync: Some(syncer),
+            refresh: None,
+            tx: Some(tx),
+        })
+    }
+}
+
+impl<ETH, SEQ> Handle<ETH, SEQ> {
+    /// Request shutdown of the sync and the RPC server.
+    pub fn stop(&self) {
+        if let Some(sync) = self.sync.as_ref() {
+            sync.stop();
+        }
+        if let Some(refresh) = self.refresh.as_ref() {
+            refresh.stop();
+        }
+        self.server.stop();
+    }
+
+    /// Wait until the sync and the RPC server are done.
+    pub async fn done(&self) {
+        if let Some(sync) = self.sync.as_ref() {
+            sync.done().await;
+        }
+        if let Some(refresh) = self.refresh.as_ref() {
+            refresh.done().await;
+        }
+        self.server.done().await;
+    }
+
+    /// Stop the node and wait (up to the configured timeout) for in-flight
+    /// sync handlers and RPC requests, then wait for in-progress index
+    /// writes and sync the indices to disk (see `Storage::sync_indices`).
+    pub async fn shutdown(&self) {
+        let timeout = self.ctx.config.shutdown_timeout;
+        if let Some(sync) = self.sync.as_ref() {
+            sync.stop();
+            // The sync drains its handlers within the timeout itself
+            sync.done().await;
+        }
+        if let Some(refresh) = self.refresh.as_ref() {
+            refresh.stop();
+            refresh.done().await;
+        }
+        self.server.stop();
+        if tokio::time::timeout(timeout, self.server.done())
+            .await
+            .is_err()
+        {
+            tracing::warn!("RPC server shutdown timed out");
+        }
+        if let Err(e) = self.ctx.db.sync_indices().await {
+            tracing::warn!(error=?e, "Failed to sync indices");
+        }
+        tracing::info!("Shutdown complete");
+    }
+}
+
+/// Wait for SIGINT (Ctrl-C) or SIGTERM, returns the name of the signal.
+pub async fn signal() -> anyhow::Result<&'static str> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut term = signal(SignalKind::terminate())?;
+        tokio::select! {
+            r = tokio::signal::ctrl_c() => {
+                r?;
+                Ok("SIGINT")
+            }
+            _ = term.recv() => Ok("SIGTERM"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await?;
+        Ok("SIGINT")
+    }
+}
+
+/// Serve the RPC only, following the range of stored blocks
+/// (that can be synced by another process).
+async fn serve<ETH, SEQ>(
+    ctx: Context<ETH, SEQ>,
+) -> anyhow::Result<Handle<ETH, SEQ>>
+where
+    ETH: EthApi,
+    SEQ: SeqApi,
+{
+    let range = refresh_range(&ctx.db, &ctx.shared).await?;
+    let refresh = {
+        let ctx = ctx.clone();
+        let delay = ctx.config.src_poll_delay;
+        let mut head = range.map(|(_, hi)| hi);
+        let mut modified = ctx.db.modified().await.ok().flatten();
+        let (tx, mut rx) = oneshot::channel::<()>();
+        let jh = tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = &mut rx => break,
+                    _ = tokio::time::sleep(delay) => (),
+                }
+                // A shared (writable) storage sees its own writes, a
+                // read-only one is reopened only after the writer's changes
+                if ctx.db.read_only {
+                    match ctx.db.modified().await {
+                        Ok(latest) if latest == modified => continue,
+                        Ok(latest) => modified = latest,
+                        Err(e) => {
+                            tracing::warn!(error=?e, "Failed to check indices");
+                            continue;
+                        }
+                    }
+                    ctx.db.reopen().await;
+                }
+                let hi = match refresh_range(&ctx.db, &ctx.shared).await {
+                    Ok(range) => range.map(|(_, hi)| hi),
+                    Err(e) => {
+                        tracing::warn!(error=?e, "Failed to refresh synced range");
+                        continue;
+                    }
+                };
+                if let Err(e) = publish_heads(&ctx, head, hi).await {
+                    tracing::warn!(error=?e, "Failed to publish new heads");
+                }
+                head = hi.or(head);
+            }
+            tracing::debug!("Synced range refresh stopped");
+        });
+        Waiter::new(jh, tx)
+    };
+
+    let (addr, server) =
+        rpc::serve(&ctx.config.rpc_bind_addr, ctx.clone()).await;
+    tracing::info!(at=?addr, synced=?range, "RPC server listening (no sync)");
+
+    Ok(Handle {
+        ctx,
+        addr,
+        server,
+        sync: None,
+        refresh: Some(refresh),
+        tx: None,
+    })
+}
+
+/// Publish the blocks the writer synced since the last refresh (or only the
+/// highest one on the first refresh) to the subscribers, as the sync does.
+async fn publish_heads<ETH, SEQ>(
+    ctx: &Context<ETH, SEQ>,
+    prev: Option<u64>,
+    hi: Option<u64>,
+) -> anyhow::Result<()> {
+    let hi = match hi {
+        Some(hi) if prev.map(|prev| hi > prev).unwrap_or(true) => hi,
+        _ => return Ok(()),
+    };
+    if ctx.notify.receiver_count() == 0 {
+        return Ok(());
+    }
+    for number in prev.map(|prev| prev + 1).unwrap_or(hi)..=hi {
+        let key = U64::from_u64(number);
+        let hash = match ctx.db.blocks_index.read().await.lookup(&key)? {
+            Some(hash) => hash.into_str(),
+            None => continue,
+        };
+        let block = match ctx.db.blocks.get(&hash).await? {
+            Some(block) => block,
+            None => continue,
+        };
+        let events = sub::events(&block);
+        ctx.notify(Notification::NewHead(block.block_header));
+        if !events.is_empty() {
+            ctx.notify(Notification::Events(events));
+        }
+    }
+    Ok(())
+}
+
+/// Make sure the stored genesis block (if any) matches the profile.
+async fn check_genesis(db: &Storage, config: &Config) -> anyhow::Result<()> {
+    let genesis = db.blocks_index.read().await.lookup(&U64::from_u64(0))?;
+    if let Some(hash) = genesis {
+        let hash = Felt::try_new(&hash.into_str())?;
+        sync::check_genesis(config, &hash)?;
+    }
+    Ok(())
+}
+
+/// Set the synced range to the range of stored blocks (and the pruned
+/// height to the recorded one).
+async fn refresh_range(
+    db: &Storage,
+    shared: &Mutex<Shared>,
+) -> anyhow::Result<Option<(u64, u64)>> {
+    let range = {
+        let idx = db.blocks_index.read().await;
+        let min = idx.min()?.map(|val| val.into_u64());
+        let max = idx.max()?.map(|val| val.into_u64());
+        min.zip(max)
+    };
+    let pruned = db.pruned().await?;
+    let sync = &mut shared.lock().await.sync;
+    if let Some((lo, hi)) = range {
+        sync.lo = Some(lo);
+        sync.hi = Some(hi);
+    }
+    sync.pruned = pruned;
+    Ok(range)
+}
@@ -0,0 +1,219 @@
+use tokio::sync::RwLock;
+use yakvdb::typed::{Store, DB};
+
+use crate::{
+    api::gen::BlockWithTxs,
+    db::{
+        AddressAndNumber, AddressWithKeyAndNumber, DirRepo, MessageAndTx, Repo,
+        Storage,
+    },
+    seq::dto,
+    sync::get_classes,
+    util::{get_messages, tx_hash, tx_sender, U256, U64},
+};
+
+/// Minimal number of blocks to accumulate below the horizon before pruning.
+pub const PRUNE_BATCH: u64 = 100;
+
+/// Drop (or archive) all blocks below the `horizon` and collapse versioned
+/// indices so that only the latest value below the horizon is kept.
+/// Returns the number of pruned blocks.
+pub async fn prune(
+    db: &Storage,
+    horizon: u64,
+    archive: bool,
+) -> anyhow::Result<u64> {
+    let archive = if archive {
+        let mut path = db.base.clone();
+        path.push("archive");
+        path.push("block");
+        let blocks: DirRepo<BlockWithTxs> = DirRepo::new(&path).await;
+
+        let mut path = db.base.clone();
+        path.push("archive");
+        path.push("state");
+        let states: DirRepo<dto::StateUpdate> = DirRepo::new(&path).await;
+
+        Some((blocks, states))
+    } else {
+        None
+    };
+
+    let lo = db.blocks_index.read().await.min()?.map(|lo| lo.into_u64());
+    let lo = match lo {
+        Some(lo) if lo < horizon => lo,
+        _ => return Ok(0),
+    };
+
+    // Only the pruned blocks are walked: their blocks and state diffs name
+    // the index entries to drop or collapse.
+    let mut pruned = 0;
+    let mut counts = Counts::default();
+    for number in lo..horizon {
+        let key = U64::from_u64(number);
+        let hash = db.blocks_index.read().await.lookup(&key)?;
+        let hash = match hash {
+            Some(hash) => {
+                db.hashes_index.write().await.remove(&hash)?;
+                db.signatures_index.write().await.remove(&hash)?;
+                hash.into_str()
+            }
+            None => continue,
+        };
+
+        if let Some(block) = db.blocks.del(&hash).await? {
+            for tx in &block.block_body_with_txs.transactions {
+                let key = U256::from_hex(tx_hash(tx).as_ref())?;
+                db.txs_index.write().await.remove(&key)?;
+                if let Some(sender) = tx_sender(tx) {
+                    let address = U256::from_hex(sender.as_ref())?;
+                    let key =
+                        AddressAndNumber::from(address, U64::from_u64(number));
+                    counts.accounts += remove(&db.accounts_index, &key).await?;
+                }
+            }
+            for (message, tx) in get_messages(&block)? {
+                let key = MessageAndTx::from(message, tx);
+                db.messages_index.write().await.remove(&key)?;
+            }
+            for event in block.receipts.iter().flat_map(|r| &r.events) {
+                let address = U256::from_hex(event.from_address.0.as_ref())?;
+                let key = AddressAndNumber::from(
+                    address.clone(),
+                    U64::from_u64(number),
+                );
+                counts.accounts += remove(&db.accounts_index, &key).await?;
+                for event_key in &event.event_content.keys {
+                    let key = AddressWithKeyAndNumber::from(
+                        address.clone(),
+                        U256::from_hex(event_key.as_ref())?,
+                        U64::from_u64(number),
+                    );
+                    counts.events += remove(&db.events_index, &key).await?;
+                }
+            }
+            if let Some((blocks, _)) = archive.as_ref() {
+                blocks.put(&hash, block).await?;
+            }
+        }
+
+        if let Some(state) = db.states.del(&hash).await? {
+            let diff = &state.state_diff;
+            for (addr, kvs) in &diff.storage_diffs {
+                let address = U256::from_hex(addr.as_ref())?;
+                for kv in kvs {
+                    let key = AddressWithKeyAndNumber::from(
+                        address.clone(),
+                        U256::from_hex(kv.key.as_ref())?,
+                        U64::from_u64(number),
+                    );
+                    counts.states += collapse(&db.states_index, &key).await?;
+                }
+            }
+            for (addr, _) in &diff.nonces {
+                let address = U256::from_hex(addr.as_ref())?;
+                let key =
+                    AddressAndNumber::from(address, U64::from_u64(number));
+                counts.nonces += collapse(&db.nonces_index, &key).await?;
+            }
+            for (addr, _) in get_classes(&state) {
+                let address = U256::from_hex(addr.as_ref())?;
+                let key =
+                    AddressAndNumber::from(address, U64::from_u64(number));
+                counts.classes += collapse(&db.classes_index, &key).await?;
+            }
+            let addresses = diff
+                .nonces
+                .iter()
+                .map(|(addr, _)| addr)
+                .chain(diff.storage_diffs.iter().map(|(addr, _)| addr))
+                .chain(get_classes(&state).map(|(addr, _)| addr));
+            for addr in addresses {
+                let address = U256::from_hex(addr.as_ref())?;
+                let key =
+                    AddressAndNumber::from(address, U64::from_u64(number));
+                counts.accounts += remove(&db.accounts_index, &key).await?;
+            }
+            if let Some((_, states)) = archive.as_ref() {
+                states.put(&hash, state).await?;
+            }
+        }
+
+        db.statuses_index.write().await.remove(&key)?;
+        db.blocks_index.write().await.remove(&key)?;
+        pruned += 1;
+        tracing::debug!(number, hash, "Block pruned");
+    }
+
+    db.set_pruned(horizon).await?;
+    tracing::info!(
+        horizon,
+        blocks = pruned,
+        states = counts.states,
+        nonces = counts.nonces,
+        classes = counts.classes,
+        events = counts.events,
+        accounts = counts.accounts,
+        "Pruning done"
+    );
+
+    metrics::counter!("prune_blocks", pruned);
+    Ok(pruned)
+}
+
+/// Index entries removed by the pruning.
+#[derive(Default)]
+struct Counts {
+    states: u64,
+    nonces: u64,
+    classes: u64,
+    events: u64,
+    accounts: u64,
+}
+
+/// Versioned index keys end with a big-endian block number, thus all
+/// versions of the same item are contiguous and ordered by the block number.
+fn prefix(key: &[u8]) -> &[u8] {
+    &key[..key.len() - 8]
+}
+
+/// Remove all versions of the item older than the given one: pruned blocks
+/// are walked in order, so only the latest version below the horizon stays.
+async fn collapse<K, V>(
+    index: &RwLock<Store<K, V>>,
+    key: &K,
+) -> anyhow::Result<u64>
+where
+    K: Clone + AsRef<[u8]> + for<'a> From<&'a [u8]>,
+    V: AsRef<[u8]> + for<'a> From<&'a [u8]>,
+{
+    let mut db = index.write().await;
+    let mut removed = 0;
+    let mut prev = db.below(key)?;
+    while let Some(older) = prev {
+        if prefix(older.as_ref()) != prefix(key.as_ref()) {
+            break;
+        }
+        prev = db.below(&older)?;
+        db.remove(&older)?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Remove the entry (if present).
+async fn remove<K, V>(
+    index: &RwLock<Store<K, V>>,
+    key: &K,
+) -> anyhow::Result<u64>
+where
+    K: Clone + AsRef<[u8]> + for<'a> From<&'a [u8]>,
+    V: AsRef<[u8]> + for<'a> From<&'a [u8]>,
+{
+    let mut db = index.write().await;
+    if db.lookup(key)?.is_none() {
+        return Ok(0);
+    }
+    db.remove(key)?;
+    Ok(1)
+}
@@ -1,16 +1,27 @@
-use std::net::SocketAddr;
+use std::{collections::HashMap, net::SocketAddr};
 
 use axum::{
-    extract::State,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, State,
+    },
     response::{Html, IntoResponse},
-    routing::{get, post},
+    routing::{get, head, post},
     Json, Router,
 };
 use iamgroot::jsonrpc;
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
 
-use crate::{api::gen, ctx::Context, eth::EthApi, seq::SeqApi, util::Waiter};
+use crate::{
+    api::gen,
+    ctx::Context,
+    eth::EthApi,
+    seq::SeqApi,
+    sub::{self, Topic},
+    util::{Waiter, U256},
+};
 
 #[derive(Deserialize, Serialize)]
 #[serde(untagged)]
@@ -76,6 +87,155 @@ where
     }
 }
 
+/// WebSocket connection: the same requests as over HTTP, plus
+/// `armada_subscribe*` and `armada_unsubscribe` (subscriptions live
+/// as long as the connection).
+async fn handle_ws<ETH, SEQ>(
+    State(state): State<Context<ETH, SEQ>>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse
+where
+    ETH: EthApi,
+    SEQ: SeqApi,
+{
+    ws.on_upgrade(move |socket| serve_ws(state, socket))
+}
+
+async fn serve_ws<ETH, SEQ>(state: Context<ETH, SEQ>, mut socket: WebSocket)
+where
+    ETH: EthApi,
+    SEQ: SeqApi,
+{
+    let mut notifications = state.notify.subscribe();
+    let mut subscriptions = Subscriptions::default();
+    metrics::increment_gauge!("ws_connections", 1.0);
+    'connection: loop {
+        let messages = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    subscriptions.reply(&state, &text).await
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            notification = notifications.recv() => match notification {
+                Ok(notification) => subscriptions.notify(&notification),
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!(missed, "WebSocket subscriber lagged");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+        for message in messages {
+            if socket.send(Message::Text(message)).await.is_err() {
+                break 'connection;
+            }
+        }
+    }
+    metrics::decrement_gauge!("ws_connections", 1.0);
+}
+
+/// Subscriptions of a WebSocket connection (by id).
+#[derive(Default)]
+struct Subscriptions {
+    topics: HashMap<u64, Topic>,
+    next: u64,
+}
+
+impl Subscriptions {
+    async fn reply<ETH, SEQ>(
+        &mut self,
+        state: &Context<ETH, SEQ>,
+        text: &str,
+    ) -> Vec<String>
+    where
+        ETH: EthApi,
+        SEQ: SeqApi,
+    {
+        let res = match serde_json::from_str::<Request>(text) {
+            Ok(Request::Single(req)) => {
+                Response::Single(self.handle(state, &req).await)
+            }
+            Ok(Request::Batch(reqs)) => {
+                let mut ret = Vec::with_capacity(reqs.len());
+                for req in reqs {
+                    ret.push(self.handle(state, &req).await);
+                }
+                Response::Batch(ret)
+            }
+            Err(_) => Response::Single(jsonrpc::Response::error(
+                -32700,
+                "Parse error",
+            )),
+        };
+        serde_json::to_string(&res).into_iter().collect()
+    }
+
+    async fn handle<ETH, SEQ>(
+        &mut self,
+        state: &Context<ETH, SEQ>,
+        req: &jsonrpc::Request,
+    ) -> jsonrpc::Response
+    where
+        ETH: EthApi,
+        SEQ: SeqApi,
+    {
+        log::info!("method: {}", req.method);
+        metrics::counter!("rpc_request", 1, "method" => req.method.clone());
+        let params = req.params.clone().unwrap_or_default();
+        let res = if let Some(topic) = Topic::of(&req.method, &params) {
+            match topic {
+                Ok(topic) => {
+                    self.next += 1;
+                    self.topics.insert(self.next, topic);
+                    jsonrpc::Response::result(self.next.into())
+                }
+                Err(_) => jsonrpc::Response::error(-32602, "Invalid params"),
+            }
+        } else if req.method == "armada_unsubscribe" {
+            let id = params
+                .as_array()
+                .and_then(|params| params.first())
+                .or_else(|| params.get("subscription"))
+                .and_then(serde_json::Value::as_u64);
+            match id {
+                Some(id) => jsonrpc::Response::result(
+                    self.topics.remove(&id).is_some().into(),
+                ),
+                None => jsonrpc::Response::error(-32602, "Invalid params"),
+            }
+        } else {
+            return gen::handle(state, req).await;
+        };
+        if let Some(id) = req.id.as_ref() {
+            res.with_id(id.clone())
+        } else {
+            res
+        }
+    }
+
+    /// Notification messages for all matching subscriptions.
+    fn notify(&self, notification: &sub::Notification) -> Vec<String> {
+        self.topics
+            .iter()
+            .flat_map(|(id, topic)| {
+                topic.select(notification).into_iter().map(|result| {
+                    serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "method": sub::NOTIFICATION,
+                        "params": {
+                            "subscription": id,
+                            "result": result,
+                        }
+                    })
+                    .to_string()
+                })
+            })
+            .collect()
+    }
+}
+
 async fn handle_metrics<ETH, SEQ>(
     State(state): State<Context<ETH, SEQ>>,
 ) -> Result<impl IntoResponse, RpcError>
@@ -116,6 +276,26 @@ where
     )))
 }
 
+/// Cheap existence check: `HEAD /block/0x...` is served from the hash index
+/// and returns the block number in the `x-block-number` header.
+async fn handle_block_head<ETH, SEQ>(
+    State(state): State<Context<ETH, SEQ>>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse, RpcError>
+where
+    ETH: EthApi,
+    SEQ: SeqApi,
+{
+    if U256::from_hex(&hash).is_err() {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+    if let Some(number) = state.db.block_number(&hash).await? {
+        let header = [("x-block-number", number.to_string())];
+        return Ok((StatusCode::OK, header).into_response());
+    }
+    Ok(StatusCode::NOT_FOUND.into_response())
+}
+
 pub async fn serve<ETH, SEQ>(
     addr: &SocketAddr,
     ctx: Context<ETH, SEQ>,
@@ -125,9 +305,10 @@ where
     SEQ: SeqApi,
 {
     let app = Router::new()
-        .route("/rpc/v0.3", post(handle_request))
+        .route("/rpc/v0.3", post(handle_request).get(handle_ws))
         .route("/metrics", get(handle_metrics))
         .route("/sync/status", get(handle_status))
+        .route("/block/:hash", head(handle_block_head))
         .with_state(ctx);
 
     let (tx, rx) = tokio::sync::oneshot::channel::<()>();
@@ -55,8 +55,30 @@ pub mod dto {
         pub class_hash: Felt,
     }
 
+    /// Sequencer signature `[r, s]` of the block.
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct BlockSignature {
+        pub block_hash: Felt,
+        pub signature: Vec<Felt>,
+        /// Present for blocks from Starknet 0.12 to 0.13.1 only: these
+        /// are signed over `pedersen(block_hash, state_diff_commitment)`,
+        /// later blocks are signed over the block hash itself.
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub signature_input: Option<SignatureInput>,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct SignatureInput {
+        pub block_hash: Felt,
+        pub state_diff_commitment: Felt,
+    }
+
     // TODO: add Class DTO definition
     pub type Class = serde_json::Value;
+
+    // TODO: add CASM DTO definition
+    pub type CompiledClass = serde_json::Value;
 }
 
 #[async_trait::async_trait]
@@ -81,10 +103,27 @@ pub trait SeqApi: Send + Sync + Clone + 'static {
         block_hash: &str,
     ) -> anyhow::Result<dto::StateUpdate>;
 
+    async fn get_signature(
+        &self,
+        block_hash: &str,
+    ) -> anyhow::Result<dto::BlockSignature>;
+
     async fn get_class_by_hash(
         &self,
         block_hash: &str,
     ) -> anyhow::Result<dto::Class>;
+
+    /// The class as JSON text: hashing Cairo 0 classes needs the numbers
+    /// exactly as served (see `hash::Json`).
+    async fn get_class_source(
+        &self,
+        class_hash: &str,
+    ) -> anyhow::Result<String>;
+
+    async fn get_compiled_class_by_class_hash(
+        &self,
+        class_hash: &str,
+    ) -> anyhow::Result<dto::CompiledClass>;
 }
 
 #[async_trait::async_trait]
@@ -155,6 +194,18 @@ impl SeqApi for SeqClient {
         .await
     }
 
+    async fn get_signature(
+        &self,
+        block_hash: &str,
+    ) -> anyhow::Result<dto::BlockSignature> {
+        self.get(
+            "/feeder_gateway/get_signature",
+            &format!("blockHash={}", block_hash),
+            identity,
+        )
+        .await
+    }
+
     async fn get_class_by_hash(
         &self,
         block_hash: &str,
@@ -166,6 +217,29 @@ impl SeqApi for SeqClient {
         )
         .await
     }
+
+    async fn get_class_source(
+        &self,
+        class_hash: &str,
+    ) -> anyhow::Result<String> {
+        self.get_text(
+            "/feeder_gateway/get_class_by_hash",
+            &format!("classHash={}", class_hash),
+        )
+        .await
+    }
+
+    async fn get_compiled_class_by_class_hash(
+        &self,
+        class_hash: &str,
+    ) -> anyhow::Result<dto::CompiledClass> {
+        self.get(
+            "/feeder_gateway/get_compiled_class_by_class_hash",
+            &format!("classHash={}", class_hash),
+            identity,
+        )
+        .await
+    }
 }
 
 #[derive(Clone)]
@@ -192,6 +266,13 @@ impl SeqClient {
         args: &str,
         map: fn(serde_json::Value) -> serde_json::Value,
     ) -> anyhow::Result<T> {
+        let text = self.get_text(path, args).await?;
+        let value: serde_json::Value = serde_json::from_str(&text)?;
+        let block = serde_json::from_value(map(value))?;
+        Ok(block)
+    }
+
+    async fn get_text(&self, path: &str, args: &str) -> anyhow::Result<String> {
         let url = format!("{}{path}?{args}", self.url);
         let res = self.http.get(&url).send().await?;
         let status = res.status();
@@ -200,12 +281,75 @@ impl SeqClient {
             tracing::error!(path, code, message, "Gateway call failed");
             anyhow::bail!(code);
         }
-        let value: serde_json::Value = res.json().await?;
-        let block = serde_json::from_value(map(value))?;
-        Ok(block)
+        Ok(res.text().await?)
     }
 }
 
+/// Gateway placeholder for processes that only read the stored chain
+/// (e.g. read-only RPC): every call fails.
+#[derive(Clone, Default)]
+pub struct NoSeq;
+
+#[async_trait::async_trait]
+impl SeqApi for NoSeq {
+    async fn get_block_by_number(
+        &self,
+        _: u64,
+    ) -> anyhow::Result<BlockWithTxs> {
+        anyhow::bail!(NO_SEQ)
+    }
+
+    async fn get_block_by_hash(&self, _: &str) -> anyhow::Result<BlockWithTxs> {
+        anyhow::bail!(NO_SEQ)
+    }
+
+    async fn get_latest_block(&self) -> anyhow::Result<BlockWithTxs> {
+        anyhow::bail!(NO_SEQ)
+    }
+
+    async fn get_pending_block(&self) -> anyhow::Result<PendingBlockWithTxs> {
+        anyhow::bail!(NO_SEQ)
+    }
+
+    async fn get_state_by_number(
+        &self,
+        _: u64,
+    ) -> anyhow::Result<dto::StateUpdate> {
+        anyhow::bail!(NO_SEQ)
+    }
+
+    async fn get_state_by_hash(
+        &self,
+        _: &str,
+    ) -> anyhow::Result<dto::StateUpdate> {
+        anyhow::bail!(NO_SEQ)
+    }
+
+    async fn get_signature(
+        &self,
+        _: &str,
+    ) -> anyhow::Result<dto::BlockSignature> {
+        anyhow::bail!(NO_SEQ)
+    }
+
+    async fn get_class_by_hash(&self, _: &str) -> anyhow::Result<dto::Class> {
+        anyhow::bail!(NO_SEQ)
+    }
+
+    async fn get_class_source(&self, _: &str) -> anyhow::Result<String> {
+        anyhow::bail!(NO_SEQ)
+    }
+
+    async fn get_compiled_class_by_class_hash(
+        &self,
+        _: &str,
+    ) -> anyhow::Result<dto::CompiledClass> {
+        anyhow::bail!(NO_SEQ)
+    }
+}
+
+const NO_SEQ: &str = "Gateway is not available";
+
 #[cfg(test)]
 mod tests {
 
@@ -272,5 +416,130 @@ mod tests {
             assert_eq!(state.block_hash.as_ref(), HASH);
             Ok(())
         }
+
+        #[tokio::test]
+        async fn test_signature() -> anyhow::Result<()> {
+            let seq = SeqClient::new(URL);
+            let signature = seq.get_signature(HASH).await?;
+            assert_eq!(signature.block_hash.as_ref(), HASH);
+            assert_eq!(signature.signature.len(), 2);
+            Ok(())
+        }
+    }
+
+    mod mainnet {
+        use super::super::*;
+        use crate::{
+            cfg::Profile,
+            hash::{
+                verify_block, verify_class, verify_compiled_class,
+                verify_signature,
+            },
+        };
+
+        const URL: &str = "https://alpha-mainnet.starknet.io";
+
+        #[tokio::test]
+        async fn test_signature() -> anyhow::Result<()> {
+            let profile = Profile::builtin("mainnet", None).expect("mainnet");
+            let public_key = profile.public_key.expect("public key");
+            let seq = SeqClient::new(URL);
+            // With the state diff commitment (0.13.1) and without it (0.13.2+)
+            let mut legacy = 0;
+            for number in [500000, 700000] {
+                let signature: dto::BlockSignature = seq
+                    .get(
+                        "/feeder_gateway/get_signature",
+                        &format!("blockNumber={number}"),
+                        identity,
+                    )
+                    .await?;
+                if signature.signature_input.is_some() {
+                    legacy += 1;
+                }
+                let hash = signature.block_hash.clone();
+                verify_signature(&public_key, &hash, &signature)?;
+            }
+            assert_eq!(legacy, 1);
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_class_hash() -> anyhow::Result<()> {
+            let seq = SeqClient::new(URL);
+            for hash in [
+                // Cairo 0: StarkGate token proxy
+                "0xd0e183745e9dae3e4e78a8ffedcce0903fc4900beace4e0abf192d4c202da3",
+                // Sierra: Argent account
+                "0x1a736d6ed154502257f02b1ccdf4d9d1089f80811cd6acad48e6b6a9d1f2003",
+            ] {
+                let source = seq.get_class_source(hash).await?;
+                verify_class(hash, &source)?;
+            }
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_compiled_class_hash() -> anyhow::Result<()> {
+            let seq = SeqClient::new(URL);
+            // The first Sierra class declared from the block on
+            for number in 650000..650500 {
+                let state = seq.get_state_by_number(number).await?;
+                let declared = match state.state_diff.declared_classes.first() {
+                    Some(declared) => declared.clone(),
+                    None => continue,
+                };
+                let hash = declared.class_hash.as_ref();
+                let source = seq.get_class_source(hash).await?;
+                verify_class(hash, &source)?;
+                let casm = seq.get_compiled_class_by_class_hash(hash).await?;
+                let compiled = declared.compiled_class_hash.as_ref();
+                verify_compiled_class(compiled, &casm)?;
+                return Ok(());
+            }
+            anyhow::bail!("No Sierra class declared")
+        }
+
+        #[tokio::test]
+        async fn test_genesis_state_roots() -> anyhow::Result<()> {
+            use crate::db::{Repo, Storage};
+            use crate::sync::{apply_states, APPLY_BATCH};
+            use crate::util::{U256, U64};
+            use yakvdb::typed::DB;
+
+            let seq = SeqClient::new(URL);
+            let dir = tempdir::TempDir::new("genesis")?;
+            let db = Storage::new(dir.path()).await;
+            for number in 0..3 {
+                let state = seq.get_state_by_number(number).await?;
+                let hash = U256::from_hex(state.block_hash.as_ref())?;
+                db.states.put(&hash.into_str(), state).await?;
+                db.blocks_index
+                    .write()
+                    .await
+                    .insert(&U64::from_u64(number), hash)?;
+            }
+            // Each computed root is checked against `new_root`
+            assert_eq!(apply_states(&db, APPLY_BATCH).await?, 3);
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_verify_pre_0_7_blocks() -> anyhow::Result<()> {
+            let profile = Profile::builtin("mainnet", None).expect("mainnet");
+            let seq = SeqClient::new(URL);
+            for number in [0, 1, 1000] {
+                let block = seq.get_block_by_number(number).await?;
+                verify_block(&block, &profile.chain_id)?;
+                if number == 0 {
+                    let hash = block.block_header.block_hash.0;
+                    assert_eq!(
+                        Some(hash.as_ref()),
+                        profile.genesis_hash.as_ref()
+                    );
+                }
+            }
+            Ok(())
+        }
     }
 }
@@ -0,0 +1,181 @@
+//! Subscriptions: notifications published by the sync (new heads and their
+//! events, blocks accepted on L1 and reorgs) and delivered over
+//! WebSocket connections (`GET /rpc/v0.3`) to the matching subscriptions.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::api::gen::{
+    BlockHeader, BlockWithTxs, EmittedEvent, Event, EventFilter, Felt,
+};
+use crate::util::U256;
+
+/// Notifications buffered for each connection: a client lagging behind
+/// by more than that misses the oldest ones.
+pub const CAPACITY: usize = 1024;
+
+/// Method of the notification messages sent to subscribers.
+pub const NOTIFICATION: &str = "armada_subscription";
+
+#[derive(Clone, Debug)]
+pub enum Notification {
+    /// The highest synced block advanced.
+    NewHead(BlockHeader),
+    /// Events of the new highest synced block.
+    Events(Vec<EmittedEvent>),
+    /// Blocks up to the given one are accepted on L1.
+    AcceptedOnL1(Block),
+    /// Blocks from the given number are replaced (the new block
+    /// at this number is the given hash).
+    Reorg(Block),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Block {
+    pub block_number: u64,
+    pub block_hash: Felt,
+}
+
+impl From<(u64, Felt)> for Block {
+    fn from((block_number, block_hash): (u64, Felt)) -> Self {
+        Self {
+            block_number,
+            block_hash,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Topic {
+    NewHeads,
+    Events(EventFilter),
+    AcceptedOnL1,
+    Reorgs,
+}
+
+impl Topic {
+    /// Subscription methods and the topics they subscribe to.
+    pub fn of(method: &str, params: &Value) -> Option<anyhow::Result<Self>> {
+        let topic = match method {
+            "armada_subscribeNewHeads" => Self::NewHeads,
+            "armada_subscribeEvents" => {
+                return Some(event_filter(params).map(Self::Events))
+            }
+            "armada_subscribeAcceptedOnL1" => Self::AcceptedOnL1,
+            "armada_subscribeReorgs" => Self::Reorgs,
+            _ => return None,
+        };
+        Some(Ok(topic))
+    }
+
+    /// Payloads of the notification for the subscription (if any).
+    pub fn select(&self, notification: &Notification) -> Vec<Value> {
+        match (self, notification) {
+            (Self::NewHeads, Notification::NewHead(header)) => {
+                serde_json::to_value(header).into_iter().collect()
+            }
+            (Self::Events(filter), Notification::Events(events)) => events
+                .iter()
+                .filter(|event| matches(filter, &event.event))
+                .filter_map(|event| serde_json::to_value(event).ok())
+                .collect(),
+            (Self::AcceptedOnL1, Notification::AcceptedOnL1(block))
+            | (Self::Reorgs, Notification::Reorg(block)) => {
+                serde_json::to_value(block).into_iter().collect()
+            }
+            _ => vec![],
+        }
+    }
+}
+
+/// Filter is accepted by position (`[filter]`) or by name (`{"filter"}`).
+fn event_filter(params: &Value) -> anyhow::Result<EventFilter> {
+    #[derive(Deserialize)]
+    struct ArgByPos((EventFilter,));
+
+    #[derive(Deserialize)]
+    struct ArgByName {
+        filter: EventFilter,
+    }
+
+    serde_json::from_value::<ArgByName>(params.clone())
+        .map(|args| args.filter)
+        .or_else(|_| {
+            serde_json::from_value::<ArgByPos>(params.clone())
+                .map(|ArgByPos((filter,))| filter)
+        })
+        .map_err(|e| anyhow::anyhow!("Invalid filter: {e}"))
+}
+
+/// Same matching as in `starknet_getEvents`: the address (if set) and
+/// any of the keys (if any).
+fn matches(filter: &EventFilter, event: &Event) -> bool {
+    let same = |a: &Felt, b: &Felt| {
+        U256::from_hex(a.as_ref()).ok() == U256::from_hex(b.as_ref()).ok()
+    };
+    if let Some(address) = filter.address.as_ref() {
+        if !same(&address.0, &event.from_address.0) {
+            return false;
+        }
+    }
+    let keys = filter.keys.iter().flatten().flatten().collect::<Vec<_>>();
+    keys.is_empty()
+        || event
+            .event_content
+            .keys
+            .iter()
+            .any(|key| keys.iter().any(|expected| same(expected, key)))
+}
+
+/// Events emitted by the transactions of the block.
+pub fn events(block: &BlockWithTxs) -> Vec<EmittedEvent> {
+    let header = &block.block_header;
+    block
+        .receipts
+        .iter()
+        .flat_map(|receipt| {
+            receipt.events.iter().map(|event| EmittedEvent {
+                block_hash: header.block_hash.clone(),
+                block_number: header.block_number.clone(),
+                event: event.clone(),
+                transaction_hash: receipt.transaction_hash.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_filter() -> anyhow::Result<()> {
+        let event: Event = serde_json::from_value(serde_json::json!({
+            "from_address": "0x123",
+            "keys": ["0x1", "0x2"],
+            "data": []
+        }))?;
+        let filter = |params: Value| -> anyhow::Result<EventFilter> {
+            match Topic::of("armada_subscribeEvents", &params) {
+                Some(Ok(Topic::Events(filter))) => Ok(filter),
+                _ => anyhow::bail!("not an event filter"),
+            }
+        };
+
+        let all = filter(serde_json::json!([{}]))?;
+        assert!(matches(&all, &event));
+        let by_address = filter(serde_json::json!({
+            "filter": {"address": "0x123"}
+        }))?;
+        assert!(matches(&by_address, &event));
+        let by_key = filter(serde_json::json!([{"keys": [["0x3", "0x2"]]}]))?;
+        assert!(matches(&by_key, &event));
+        let other = filter(serde_json::json!([{
+            "address": "0x123",
+            "keys": [["0x3"]]
+        }]))?;
+        assert!(!matches(&other, &event));
+        assert!(filter(serde_json::json!([{"keys": "0x1"}])).is_err());
+        Ok(())
+    }
+}
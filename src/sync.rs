@@ -1,19 +1,28 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::time::Instant;
 use std::{sync::Arc, time::Duration};
 
 use futures::Future;
-use tokio::sync::{mpsc, oneshot::channel, Mutex, Notify};
+use tokio::sync::{mpsc, oneshot::channel, watch, Mutex, Notify, Semaphore};
+use tokio::task::JoinSet;
 
 use crate::api::gen::BlockStatus;
-use crate::db::{AddressAndNumber, AddressWithKeyAndNumber, Repo};
+use crate::db::{
+    activity, mark_account, status, AddressAndNumber, AddressWithKeyAndNumber,
+    Declaration, EthLog, MessageAndTx, Repo, Settlement, Signature, StateRoots,
+    TxAndMessage,
+};
 use crate::{
-    api::gen::{BlockWithTxs, Felt},
+    api::gen::{BlockNumber, BlockWithTxs, DeadLetter, DeclareTxn, Felt, Txn},
+    cfg::{Config, Direction},
     ctx::Context,
     db::{BlockAndIndex, Storage},
     eth::{self, EthApi},
+    hash, prune,
     seq::{dto, SeqApi},
-    util::{is_open, tx_hash, Waiter, U256, U64},
+    sub::{self, Notification},
+    trie,
+    util::{get_messages, tx_hash, tx_sender, Waiter, U256, U64},
 };
 use yakvdb::typed::DB;
 
@@ -91,6 +100,36 @@ impl<T: Send + 'static, C: Send + 'static> Source<T, C> {
     }
 }
 
+/// Block pulls in flight (by number): the zero hash pulls the block at the
+/// number whatever its hash is, so it matches a pull of any hash.
+#[derive(Default)]
+struct Pulls(HashMap<u64, Vec<U256>>);
+
+impl Pulls {
+    /// Register the pull unless a matching one is in flight already.
+    fn start(&mut self, number: u64, hash: &U256) -> bool {
+        let any = U256::default();
+        let hashes = self.0.entry(number).or_default();
+        if hashes
+            .iter()
+            .any(|known| known == hash || known == &any || hash == &any)
+        {
+            return false;
+        }
+        hashes.push(hash.clone());
+        true
+    }
+
+    fn done(&mut self, number: u64, hash: &U256) {
+        if let Some(hashes) = self.0.get_mut(&number) {
+            hashes.retain(|known| known != hash);
+            if hashes.is_empty() {
+                self.0.remove(&number);
+            }
+        }
+    }
+}
+
 pub async fn sync<ETH, SEQ, F, R>(
     source: Source<Event, Context<ETH, SEQ>>,
     handler: F,
@@ -105,36 +144,141 @@ where
         + 'static,
     R: Future<Output = anyhow::Result<Vec<Event>>> + Send + 'static,
 {
-    let delay = source.ctx().lock().await.config.src_poll_delay;
+    let (retry, timeout, max_handlers) = {
+        let config = &source.ctx().lock().await.config;
+        (
+            config.retry.clone(),
+            config.shutdown_timeout,
+            config.max_handlers,
+        )
+    };
+    let (stopping, stopped) = watch::channel(false);
+    let permits = Arc::new(Semaphore::new(max_handlers));
 
     let (tx, mut rx) = channel::<()>();
     let jh = tokio::spawn(async move {
         let mut source = source.run();
+        let mut running = JoinSet::new();
+        let pending = Arc::new(std::sync::Mutex::new(HashMap::new()));
+        let pulls = Arc::new(std::sync::Mutex::new(Pulls::default()));
+        let mut seq = 0u64;
+
+        loop {
+            // No more events are taken while all handlers are busy
+            let permit = tokio::select! {
+                _ = &mut rx => break,
+                permit = permits.clone().acquire_owned() => {
+                    permit.expect("semaphore is never closed")
+                }
+            };
+            let event = loop {
+                tokio::select! {
+                    _ = &mut rx => break None,
+                    Some(_) = running.join_next(), if !running.is_empty() => continue,
+                    event = source.get() => break event,
+                }
+            };
+            let event = match event {
+                Some(event) => event,
+                None => break,
+            };
+
+            // Hashes are compared as numbers ("0x0" is any hash)
+            let pull = match &event {
+                Event::PullBlock(number, hash) => U256::from_hex(hash.as_ref())
+                    .ok()
+                    .map(|hash| (*number, hash)),
+                _ => None,
+            };
+            if let Some((number, hash)) = pull.as_ref() {
+                if !pulls.lock().unwrap().start(*number, hash) {
+                    tracing::debug!(?event, "Block pull already in flight");
+                    metrics::counter!("sync_coalesced", 1);
+                    continue;
+                }
+            }
+            metrics::gauge!(
+                "sync_handlers",
+                (max_handlers - permits.available_permits()) as f64
+            );
+
+            seq += 1;
+            let id = seq;
+            pending.lock().unwrap().insert(id, event.clone());
 
-        while let Some(event) = source.get().await {
             let ctx = source.ctx();
             let tx = source.tx();
-            tokio::spawn(async move {
-                match handler(ctx, event.clone()).await {
-                    Ok(events) => {
-                        for event in events {
-                            tx.send(event).await.ok();
+            let pending = pending.clone();
+            let pulls = pulls.clone();
+            let permits = permits.clone();
+            let retry = retry.clone();
+            let mut stopped = stopped.clone();
+            running.spawn(async move {
+                let mut permit = Some(permit);
+                let mut attempt = 0;
+                loop {
+                    let held = match permit.take() {
+                        Some(permit) => permit,
+                        None => permits
+                            .clone()
+                            .acquire_owned()
+                            .await
+                            .expect("semaphore is never closed"),
+                    };
+                    let result = handler(ctx.clone(), event.clone()).await;
+                    // Release the permit before sending follow-up events:
+                    // the sync loop might be waiting for it to take them.
+                    drop(held);
+                    let e = match result {
+                        Ok(events) => {
+                            for event in events {
+                                tx.send(event).await.ok();
+                            }
+                            break;
+                        }
+                        Err(e) => e,
+                    };
+                    attempt += 1;
+                    tracing::error!(?event, attempt, reason=?e, "Handler failed");
+                    metrics::counter!("sync_error", 1, "reason" => format!("{e}"));
+                    if attempt < retry.attempts {
+                        tokio::select! {
+                            _ = tokio::time::sleep(retry.backoff(attempt)) => continue,
+                            _ = stopped.changed() => {
+                                // Not a dead letter: the pollers produce the
+                                // event again after a restart
+                                tracing::warn!(?event, attempt, "Retry interrupted");
+                                break;
+                            }
                         }
                     }
-                    Err(e) => {
-                        tracing::error!(?event, reason=?e, "Handler failed");
-                        metrics::counter!("sync_error", 1, "reason" => format!("{e}"));
-                        tokio::spawn(async move {
-                            tokio::time::sleep(10 * delay).await;
-                            tx.send(event).await.ok();
-                        });
+                    let db = ctx.lock().await.db.clone();
+                    let reason = format!("{e:?}");
+                    match save_dead_letter(&db, &event, attempt, reason).await {
+                        Ok(id) => tracing::warn!(?event, id, "Dead letter saved"),
+                        Err(e) => tracing::error!(?event, reason=?e, "Dead letter lost"),
                     }
+                    break;
+                }
+                if let Some((number, hash)) = pull {
+                    pulls.lock().unwrap().done(number, &hash);
                 }
+                pending.lock().unwrap().remove(&id);
             });
-            if !is_open(&mut rx) {
-                break;
+        }
+
+        // Stop the pollers and let in-flight handlers finish
+        source.stop();
+        stopping.send(true).ok();
+        tracing::info!(running = running.len(), "Sync stopping");
+        let drain = async { while running.join_next().await.is_some() {} };
+        if tokio::time::timeout(timeout, drain).await.is_err() {
+            for event in pending.lock().unwrap().values() {
+                tracing::warn!(?event, "Handler interrupted");
             }
+            running.abort_all();
         }
+        tracing::info!("Sync stopped");
     });
 
     Waiter::new(jh, tx)
@@ -146,9 +290,131 @@ pub enum Event {
     Head(u64, Felt),
     PullBlock(u64, Felt),
     PurgeBlock(u64, Felt),
-    Uptime { seconds: u64 },
+    Prune(u64),
+    /// State updates and L1->L2 messages logged on L1
+    /// up to the (inclusive) L1 block.
+    Settle(u64, Vec<eth::Update>, Vec<eth::Message>),
+    /// Stored settlement at the L1 block no longer matches the L1 chain.
+    EthReorg(u64),
+    /// Stored state updates from the block on are applied to the tries.
+    ApplyStates(u64),
+    Uptime {
+        seconds: u64,
+    },
 }
 
+impl Event {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::Ethereum(_) => "Ethereum",
+            Event::Head(..) => "Head",
+            Event::PullBlock(..) => "PullBlock",
+            Event::PurgeBlock(..) => "PurgeBlock",
+            Event::Prune(_) => "Prune",
+            Event::Settle(..) => "Settle",
+            Event::EthReorg(_) => "EthReorg",
+            Event::ApplyStates(_) => "ApplyStates",
+            Event::Uptime { .. } => "Uptime",
+        }
+    }
+
+    fn block(&self) -> (Option<u64>, Option<Felt>) {
+        match self {
+            Event::Head(number, hash)
+            | Event::PullBlock(number, hash)
+            | Event::PurgeBlock(number, hash) => {
+                (Some(*number), Some(hash.clone()))
+            }
+            Event::Prune(number)
+            | Event::EthReorg(number)
+            | Event::ApplyStates(number) => (Some(*number), None),
+            _ => (None, None),
+        }
+    }
+
+    /// Restore the event from the dead letter. Events produced by the
+    /// pollers on each poll (L1 state, settlements, uptime) are not restored.
+    pub fn from_dead_letter(letter: &DeadLetter) -> Option<Self> {
+        let number = *letter.block_number.as_ref()?.as_ref() as u64;
+        let hash = letter.block_hash.clone();
+        match (letter.kind.as_str(), hash) {
+            ("Head", Some(hash)) => Some(Event::Head(number, hash)),
+            ("PullBlock", Some(hash)) => Some(Event::PullBlock(number, hash)),
+            ("PurgeBlock", Some(hash)) => Some(Event::PurgeBlock(number, hash)),
+            ("Prune", _) => Some(Event::Prune(number)),
+            ("EthReorg", _) => Some(Event::EthReorg(number)),
+            ("ApplyStates", _) => Some(Event::ApplyStates(number)),
+            _ => None,
+        }
+    }
+}
+
+/// Store the failed event as a dead letter, returns the id of the letter.
+pub async fn save_dead_letter(
+    db: &Storage,
+    event: &Event,
+    attempts: u32,
+    reason: String,
+) -> anyhow::Result<u64> {
+    let (number, hash) = event.block();
+    let failed_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+
+    let mut idx = db.dead_letters_index.write().await;
+    let id = idx.max()?.map(|id| id.into_u64() + 1).unwrap_or_default();
+    let letter = DeadLetter {
+        attempts: attempts as i64,
+        block_hash: hash,
+        block_number: number
+            .map(|number| BlockNumber::try_new(number as i64))
+            .transpose()?,
+        event: format!("{event:?}"),
+        failed_at: failed_at as i64,
+        id: id as i64,
+        kind: event.kind().to_string(),
+        reason,
+    };
+    db.dead_letters.put(&id.to_string(), letter).await?;
+    idx.insert(&U64::from_u64(id), U64::from_u64(failed_at))?;
+    drop(idx);
+
+    metrics::gauge!("dead_letters", dead_letter_ids(db).await?.len() as f64);
+    Ok(id)
+}
+
+/// Ids of all stored dead letters in ascending order.
+pub async fn dead_letter_ids(db: &Storage) -> anyhow::Result<Vec<u64>> {
+    let idx = db.dead_letters_index.read().await;
+    let mut ids = Vec::new();
+    let mut next = idx.min()?;
+    while let Some(id) = next {
+        ids.push(id.into_u64());
+        next = idx.above(&id)?;
+    }
+    Ok(ids)
+}
+
+/// Remove the dead letter, returns the letter if it was found.
+pub async fn remove_dead_letter(
+    db: &Storage,
+    id: u64,
+) -> anyhow::Result<Option<DeadLetter>> {
+    let letter = db.dead_letters.del(&id.to_string()).await?;
+    db.dead_letters_index
+        .write()
+        .await
+        .remove(&U64::from_u64(id))?;
+    metrics::gauge!("dead_letters", dead_letter_ids(db).await?.len() as f64);
+    Ok(letter)
+}
+
+/// Maximum number of L1 blocks to scan for logs in one request.
+pub const ETH_LOGS_RANGE: u64 = 1000;
+
+/// State updates applied to the tries per `ApplyStates` event.
+pub const APPLY_BATCH: u64 = 100;
+
 pub async fn fetch_block<SEQ, ETH>(
     ctx: Arc<Mutex<Context<ETH, SEQ>>>,
     number: u64,
@@ -200,6 +466,23 @@ where
     Ok(block)
 }
 
+/// Make sure the genesis block matches the one expected by the profile.
+pub fn check_genesis(config: &Config, hash: &Felt) -> anyhow::Result<()> {
+    let expected = match config.genesis_hash.as_ref() {
+        Some(expected) => U256::from_hex(expected)?,
+        None => return Ok(()),
+    };
+    if U256::from_hex(hash.as_ref())? != expected {
+        anyhow::bail!(
+            "Genesis block mismatch for network '{}': expected {}, got {}",
+            config.network,
+            expected.into_str(),
+            hash.as_ref()
+        );
+    }
+    Ok(())
+}
+
 pub async fn pull_block<SEQ, ETH>(
     ctx: Arc<Mutex<Context<ETH, SEQ>>>,
     number: u64,
@@ -219,13 +502,86 @@ where
 
     let block_number = *block.block_header.block_number.as_ref() as u64;
     let block_hash = block.block_header.block_hash.0.clone();
+    if block_number == 0 {
+        check_genesis(&ctx.lock().await.config, &block_hash)?;
+    }
+
+    let config = ctx.lock().await.config.clone();
+    if config.verify {
+        let t = Instant::now();
+        if let Err(e) = hash::verify_block(&block, &config.chain_id) {
+            tracing::warn!(
+                number = block_number,
+                hash = block_hash.as_ref(),
+                reason = %e,
+                "Block rejected"
+            );
+            metrics::counter!("block_rejected", 1);
+            return Err(e);
+        }
+        metrics::gauge!("block_verify", t.elapsed().as_secs_f64());
+    }
+
+    let signature = match config.public_key.as_ref() {
+        Some(public_key) if config.verify => {
+            let signature = {
+                let seq = &ctx.lock().await.seq;
+                seq.get_signature(block_hash.as_ref()).await
+            };
+            let checked = signature.and_then(|signature| {
+                hash::verify_signature(public_key, &block_hash, &signature)?;
+                Ok(signature)
+            });
+            match checked {
+                Ok(signature) => Some(signature),
+                Err(e) => {
+                    tracing::warn!(
+                        number = block_number,
+                        hash = block_hash.as_ref(),
+                        reason = %e,
+                        "Block signature rejected"
+                    );
+                    metrics::counter!("block_rejected", 1);
+                    return Err(e);
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let header = block.block_header.clone();
+    let emitted = if ctx.lock().await.notify.receiver_count() > 0 {
+        sub::events(&block)
+    } else {
+        vec![]
+    };
 
     let t = Instant::now();
     if let Some(event) = {
         let db = &mut ctx.lock().await.db;
         save_block(db, block_hash.clone(), block).await?
     } {
-        events.push(event);
+        // Parent walking stops at the pruning horizon or at the sync range
+        let floor = ctx.lock().await.floor().await;
+        match (&event, floor) {
+            (Event::PullBlock(parent, _), Some(floor)) if *parent < floor => {
+                tracing::debug!(
+                    number = parent,
+                    floor,
+                    "Parent is out of range"
+                );
+            }
+            _ => events.push(event),
+        }
+    }
+    if let Some(signature) = signature {
+        let key = U256::from_hex(block_hash.as_ref())?;
+        let val = Signature::from(
+            U256::from_hex(signature.signature[0].as_ref())?,
+            U256::from_hex(signature.signature[1].as_ref())?,
+        );
+        let db = &ctx.lock().await.db;
+        db.signatures_index.write().await.insert(&key, val)?;
     }
     metrics::gauge!("block_save", t.elapsed().as_secs_f64());
 
@@ -244,18 +600,59 @@ where
     let handle = {
         let ctx = ctx.clone();
         let classes = get_classes(&state)
-            .map(|(_, hash)| hash.as_ref().to_string())
+            .map(|(_, hash)| hash)
+            .chain(get_declared(&state).map(|(hash, _)| hash))
+            .map(|hash| hash.as_ref().to_string())
             .collect::<HashSet<_>>();
+        let compiled = get_declared(&state)
+            .filter_map(|(hash, compiled)| Some(hash).zip(compiled))
+            .map(|(hash, compiled)| {
+                // CASM is stored by the normalized compiled class hash
+                let compiled = U256::from_hex(compiled.as_ref())?.into_str();
+                Ok((hash.as_ref().to_string(), compiled))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let verify = config.verify;
         tokio::spawn(async move {
             for hash in classes {
                 if ctx.lock().await.db.classes.has(&hash).await? {
                     continue;
                 }
-                let class =
-                    ctx.lock().await.seq.get_class_by_hash(&hash).await?;
+                let source =
+                    ctx.lock().await.seq.get_class_source(&hash).await?;
+                if verify {
+                    if let Err(e) = hash::verify_class(&hash, &source) {
+                        tracing::warn!(hash, reason = %e, "Class rejected");
+                        metrics::counter!("class_rejected", 1);
+                        return Err(e);
+                    }
+                }
+                let class: dto::Class = serde_json::from_str(&source)?;
                 ctx.lock().await.db.classes.put(&hash, class).await?;
                 tracing::debug!(hash, "Class saved");
             }
+            for (hash, compiled) in compiled {
+                if ctx.lock().await.db.casms.has(&compiled).await? {
+                    continue;
+                }
+                let casm = ctx
+                    .lock()
+                    .await
+                    .seq
+                    .get_compiled_class_by_class_hash(&hash)
+                    .await?;
+                if verify {
+                    if let Err(e) =
+                        hash::verify_compiled_class(&compiled, &casm)
+                    {
+                        tracing::warn!(hash, compiled, reason = %e, "CASM rejected");
+                        metrics::counter!("class_rejected", 1);
+                        return Err(e);
+                    }
+                }
+                ctx.lock().await.db.casms.put(&compiled, casm).await?;
+                tracing::debug!(hash, compiled, "CASM saved");
+            }
             Ok::<(), anyhow::Error>(())
         })
     };
@@ -264,7 +661,7 @@ where
     let t = Instant::now();
     {
         let db = &mut ctx.lock().await.db;
-        save_state(db, hash.clone(), block_number, state).await?
+        save_state(db, block_hash.clone(), block_number, state).await?
     };
 
     handle.await??;
@@ -280,16 +677,28 @@ where
         let key = U64::from_u64(block_number);
         let val = U256::from_hex(block_hash.as_ref())?;
         let db = &mut ctx.lock().await.db;
-        db.blocks_index.write().await.insert(&key, val)?;
+        db.blocks_index.write().await.insert(&key, val.clone())?;
+        db.hashes_index.write().await.insert(&val, key)?;
+        mark_accepted(db, block_number).await?;
     }
 
-    let (lo, hi) = {
+    let (lo, hi, advanced) = {
         let ctx = ctx.lock().await;
         let sync = &mut ctx.shared.lock().await.sync;
+        let advanced = sync.hi.map(|hi| number > hi).unwrap_or(true);
         sync.lo = sync.lo.map(|lo| number.min(lo)).or(Some(number));
         sync.hi = sync.hi.map(|hi| number.max(hi)).or(Some(number));
-        (sync.lo, sync.hi)
+        (sync.lo, sync.hi, advanced)
     };
+    // Blocks below the highest one (backward sync, gaps, re-pulls) are not
+    // published: subscribers follow the head.
+    if advanced {
+        let ctx = ctx.lock().await;
+        ctx.notify(Notification::NewHead(header));
+        if !emitted.is_empty() {
+            ctx.notify(Notification::Events(emitted));
+        }
+    }
 
     if let Some((lo, hi)) = lo.zip(hi) {
         metrics::gauge!("sync_lo", lo as f64);
@@ -318,12 +727,23 @@ pub async fn save_block(
         let key = U256::from_hex(tx_hash(tx).as_ref())?;
         db.txs_index.write().await.insert(&key, val)?;
         tracing::debug!(hash = key.into_str(), "TX saved");
+
+        if let Some(sender) = tx_sender(tx) {
+            let address = U256::from_hex(sender.as_ref())?;
+            let mut accounts = db.accounts_index.write().await;
+            mark_account(&mut accounts, address, number, activity::SENDER)?;
+        }
     }
 
     // TODO: spawn
     for receipt in &block.receipts {
         for event in &receipt.events {
             let addr = &event.from_address.0;
+            let address = U256::from_hex(addr.as_ref())?;
+            let mut accounts = db.accounts_index.write().await;
+            mark_account(&mut accounts, address, number, activity::EVENT)?;
+            drop(accounts);
+
             let keys = &event.event_content.keys;
             for key in keys {
                 let address = U256::from_hex(addr.as_ref()).unwrap();
@@ -346,6 +766,24 @@ pub async fn save_block(
         }
     }
 
+    let mut messages: Vec<(U256, U256, u64)> = Vec::new();
+    for (message, tx) in get_messages(&block)? {
+        match messages
+            .iter_mut()
+            .find(|(m, t, _)| m == &message && t == &tx)
+        {
+            Some((_, _, count)) => *count += 1,
+            None => messages.push((message, tx, 1)),
+        }
+    }
+    for (message, tx, count) in messages {
+        let hash = message.into_str();
+        let key = MessageAndTx::from(message, tx);
+        let val = U64::from_u64(count);
+        db.messages_index.write().await.insert(&key, val)?;
+        tracing::debug!(hash, count, "Message saved");
+    }
+
     if number == 0 {
         // Stop if a genesis block is reached
         return Ok(None);
@@ -437,10 +875,91 @@ pub async fn save_state(
         );
     }
 
-    // TODO: how to handle [old_]declared_contracts?
+    // TODO: spawn
+    let addresses = state
+        .state_diff
+        .nonces
+        .iter()
+        .map(|(addr, _)| addr)
+        .chain(state.state_diff.storage_diffs.iter().map(|(addr, _)| addr))
+        .chain(get_classes(&state).map(|(addr, _)| addr))
+        .map(|addr| addr.as_ref())
+        .collect::<HashSet<&String>>();
+    for addr in addresses {
+        let address = U256::from_hex(addr)?;
+        let mut accounts = db.accounts_index.write().await;
+        mark_account(&mut accounts, address, number, activity::STATE)?;
+    }
+
+    // TODO: spawn
+    let declared_by = {
+        let block = db.blocks.get(hash.as_ref()).await?;
+        block
+            .map(|block| get_declare_txs(&block))
+            .transpose()?
+            .unwrap_or_default()
+    };
+    for (class_hash, compiled_class_hash) in get_declared(&state) {
+        let key = U256::from_hex(class_hash.as_ref())?;
+        let known = db.declarations_index.read().await.lookup(&key)?;
+        if matches!(known, Some(known) if known.number().into_u64() <= number) {
+            // Keep the earliest declaration: blocks are not saved in order
+            continue;
+        }
+        let tx = declared_by
+            .iter()
+            .find(|(hash, _)| hash == &key)
+            .map(|(_, tx)| tx.clone())
+            .unwrap_or_default();
+        let compiled = compiled_class_hash
+            .map(|hash| U256::from_hex(hash.as_ref()))
+            .transpose()?
+            .unwrap_or_default();
+        let val = Declaration::from(U64::from_u64(number), tx, compiled);
+        db.declarations_index.write().await.insert(&key, val)?;
+        tracing::debug!(hash = class_hash.as_ref(), "Class declared");
+    }
+
     Ok(())
 }
 
+/// Declared classes: Cairo 0 ones (no compiled class hash) and Sierra ones.
+pub fn get_declared(
+    state: &dto::StateUpdate,
+) -> impl Iterator<Item = (&Felt, Option<&Felt>)> + '_ {
+    state
+        .state_diff
+        .old_declared_contracts
+        .iter()
+        .map(|hash| (hash, None))
+        .chain(state.state_diff.declared_classes.iter().map(|declared| {
+            (&declared.class_hash, Some(&declared.compiled_class_hash))
+        }))
+}
+
+/// Pairs of (class hash, declare tx hash) for all declare txs of the block.
+fn get_declare_txs(block: &BlockWithTxs) -> anyhow::Result<Vec<(U256, U256)>> {
+    block
+        .block_body_with_txs
+        .transactions
+        .iter()
+        .filter_map(|tx| match tx {
+            Txn::DeclareTxn(DeclareTxn::DeclareTxnV1(txn)) => {
+                Some((&txn.class_hash, tx))
+            }
+            Txn::DeclareTxn(DeclareTxn::DeclareTxnV2(txn)) => {
+                Some((&txn.declare_txn_v1.class_hash, tx))
+            }
+            _ => None,
+        })
+        .map(|(class_hash, tx)| {
+            let class_hash = U256::from_hex(class_hash.as_ref())?;
+            let tx_hash = U256::from_hex(tx_hash(tx).as_ref())?;
+            Ok((class_hash, tx_hash))
+        })
+        .collect()
+}
+
 pub fn get_classes(
     state: &dto::StateUpdate,
 ) -> impl Iterator<Item = (&Felt, &Felt)> + '_ {
@@ -460,7 +979,7 @@ pub fn get_classes(
 
 // TODO: avoid function-scoped lock on Storage
 pub async fn purge_block(
-    _db: &mut Storage,
+    db: &mut Storage,
     number: u64,
     hash: Felt,
     events: &mut Vec<Event>,
@@ -469,10 +988,255 @@ pub async fn purge_block(
     // Currently re-pulling the block will restore the chain integrity,
     // but indexed data from "purged" block will remain available.
 
+    let key = U64::from_u64(number);
+    db.statuses_index.write().await.remove(&key)?;
+    {
+        // The tries are rolled back to the state before the block
+        let mut roots = db.trie_roots_index.write().await;
+        while let Some(max) = roots.max()? {
+            if max.into_u64() < number {
+                break;
+            }
+            roots.remove(&max)?;
+        }
+    }
+    let purged = db.blocks_index.read().await.lookup(&key)?;
+    if let Some(purged) = purged {
+        db.hashes_index.write().await.remove(&purged)?;
+        let purged = purged.into_str();
+        db.blocks.evict(&purged).await;
+        db.states.evict(&purged).await;
+    }
+
     events.push(Event::PullBlock(number, hash));
     Ok(())
 }
 
+/// The stored block (number and hash) next to the last one applied to
+/// the state tries, if its state update is stored.
+pub async fn next_state(db: &Storage) -> anyhow::Result<Option<(u64, U256)>> {
+    let next = db
+        .trie_roots_index
+        .read()
+        .await
+        .max()?
+        .map(|last| last.into_u64() + 1)
+        .unwrap_or_default();
+    let hash = db.blocks_index.read().await.lookup(&U64::from_u64(next))?;
+    match hash {
+        Some(hash) if db.states.has(&hash.into_str()).await? => {
+            Ok(Some((next, hash)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Apply stored state updates to the state tries in block order, starting
+/// from the block next to the last applied one (up to `limit` of them).
+/// Each computed state root is checked against the `new_root` of the state
+/// update. Returns the number of applied state updates.
+pub async fn apply_states(db: &Storage, limit: u64) -> anyhow::Result<u64> {
+    // Holding the roots index serializes concurrent appliers
+    let mut index = db.trie_roots_index.write().await;
+    let (mut next, mut roots) = match index.max()? {
+        Some(last) => {
+            let roots = index.lookup(&last)?.expect("state roots");
+            (last.into_u64() + 1, roots)
+        }
+        None => (0, StateRoots::from(U256::default(), U256::default())),
+    };
+
+    let mut applied = 0;
+    while applied < limit {
+        let key = U64::from_u64(next);
+        let hash = match db.blocks_index.read().await.lookup(&key)? {
+            Some(hash) => hash,
+            None => break,
+        };
+        let state = match db.states.get(&hash.into_str()).await? {
+            Some(state) => state,
+            None => break,
+        };
+
+        let updated = trie::apply_state(db, &roots, &state).await?;
+        let root = trie::state_commitment(&updated)?;
+        if root != U256::from_hex(state.new_root.as_ref())? {
+            metrics::counter!("state_root_mismatch", 1);
+            tracing::error!(
+                number = next,
+                expected = state.new_root.as_ref(),
+                computed = root.into_str(),
+                "State root mismatch"
+            );
+            anyhow::bail!("State root mismatch: number={next}");
+        }
+        index.insert(&key, updated.clone())?;
+        tracing::debug!(number = next, root = root.into_str(), "State applied");
+
+        roots = updated;
+        next += 1;
+        applied += 1;
+    }
+    if applied > 0 {
+        metrics::gauge!("state_applied", (next - 1) as f64);
+    }
+    Ok(applied)
+}
+
+/// Check the state root settled on L1 against the computed one
+/// (if the state tries have reached the L1 state block).
+pub async fn check_l1_root(
+    db: &Storage,
+    state: &eth::State,
+) -> anyhow::Result<()> {
+    let number = state.state_block_number;
+    let roots = db
+        .trie_roots_index
+        .read()
+        .await
+        .lookup(&U64::from_u64(number))?;
+    let roots = match roots {
+        Some(roots) => roots,
+        None => {
+            tracing::debug!(number, "L1 state block is not applied yet");
+            return Ok(());
+        }
+    };
+    let root = trie::state_commitment(&roots)?;
+    if root != U256::from_hex(state.state_root.as_ref())? {
+        metrics::counter!("state_root_mismatch", 1);
+        tracing::error!(
+            number,
+            l1 = state.state_root.as_ref(),
+            computed = root.into_str(),
+            "L1 state root mismatch"
+        );
+        anyhow::bail!("L1 state root mismatch: number={number}");
+    }
+    tracing::info!(number, root = root.into_str(), "L1 state root verified");
+    Ok(())
+}
+
+/// Mark all stored blocks up to the L1 state block as accepted on L1,
+/// unless the L1 state block hash does not match the stored one.
+/// Returns the number of upgraded blocks.
+pub async fn accept_on_l1(
+    db: &Storage,
+    state: &eth::State,
+) -> anyhow::Result<u64> {
+    let number = state.state_block_number;
+    let stored = db
+        .blocks_index
+        .read()
+        .await
+        .lookup(&U64::from_u64(number))?;
+    let stored = match stored {
+        Some(stored) => stored,
+        None => {
+            tracing::debug!(number, "L1 state block is not synced yet");
+            return Ok(0);
+        }
+    };
+
+    let hash = U256::from_hex(state.state_block_hash.as_ref())?;
+    if stored != hash {
+        tracing::error!(
+            number,
+            stored = stored.into_str(),
+            received = hash.into_str(),
+            "L1 state block hash mismatch"
+        );
+        metrics::counter!("l1_mismatch", 1);
+        metrics::gauge!("l1_mismatch_block", number as f64);
+        return Ok(0);
+    }
+
+    // Only the blocks above the previously accepted one are walked: blocks
+    // stored below it later are marked when saved (see `mark_accepted`).
+    let known = db.accepted().await?;
+    if known.map(|known| known >= number).unwrap_or_default() {
+        return Ok(0);
+    }
+    let mut accepted = 0;
+    let mut next = match known {
+        Some(known) => {
+            db.blocks_index.read().await.above(&U64::from_u64(known))?
+        }
+        None => db.blocks_index.read().await.min()?,
+    };
+    while let Some(key) = next {
+        if key.into_u64() > number {
+            break;
+        }
+        if db.statuses_index.read().await.lookup(&key)?.is_none() {
+            let val = U64::from_u64(status::ACCEPTED_ON_L1);
+            db.statuses_index.write().await.insert(&key, val)?;
+            accepted += 1;
+        }
+        next = db.blocks_index.read().await.above(&key)?;
+    }
+    db.set_accepted(number).await?;
+    metrics::gauge!("head_accepted_on_l1", number as f64);
+    Ok(accepted)
+}
+
+/// Mark the saved block as accepted on L1 if a higher block is already
+/// accepted. Returns true if the block was marked.
+pub async fn mark_accepted(db: &Storage, number: u64) -> anyhow::Result<bool> {
+    let accepted = db.accepted().await?;
+    if accepted.map(|accepted| number > accepted).unwrap_or(true) {
+        return Ok(false);
+    }
+    let key = U64::from_u64(number);
+    if db.statuses_index.read().await.lookup(&key)?.is_some() {
+        return Ok(false);
+    }
+    let val = U64::from_u64(status::ACCEPTED_ON_L1);
+    db.statuses_index.write().await.insert(&key, val)?;
+    Ok(true)
+}
+
+/// Remove settlements logged at or above the L1 block.
+pub async fn drop_settlements(
+    db: &Storage,
+    number: u64,
+) -> anyhow::Result<u64> {
+    let mut idx = db.settlements_index.write().await;
+    let mut dropped = 0;
+    let mut next = idx.max()?;
+    while let Some(key) = next {
+        next = idx.below(&key)?;
+        let settled = idx.lookup(&key)?.map(|val| val.number().into_u64());
+        if settled.unwrap_or_default() < number {
+            break;
+        }
+        idx.remove(&key)?;
+        dropped += 1;
+    }
+    Ok(dropped)
+}
+
+/// Drop L1->L2 messages sent in the L1 blocks from the given one on.
+pub async fn drop_messages(db: &Storage, number: u64) -> anyhow::Result<u64> {
+    let mut dropped = Vec::new();
+    {
+        let idx = db.l1_messages_index.read().await;
+        let mut next = idx.min()?;
+        while let Some(key) = next {
+            let sent = idx.lookup(&key)?.map(|val| val.number().into_u64());
+            if sent.unwrap_or_default() >= number {
+                dropped.push(key.clone());
+            }
+            next = idx.above(&key)?;
+        }
+    }
+    let mut idx = db.l1_messages_index.write().await;
+    for key in &dropped {
+        idx.remove(key)?;
+    }
+    Ok(dropped.len() as u64)
+}
+
 pub async fn handler<ETH, SEQ>(
     ctx: Arc<Mutex<Context<ETH, SEQ>>>,
     event: Event,
@@ -495,11 +1259,27 @@ where
                 pull_block(ctx.clone(), number, hash.clone(), &mut events)
                     .await?;
             tracing::info!(number, hash = hash.as_ref(), "Block done");
+
+            // Forward sync goes on with the next block up to the known head
+            let (direction, to, sync) = {
+                let ctx = ctx.lock().await;
+                let sync = ctx.shared.lock().await.sync.clone();
+                (ctx.config.direction, ctx.config.sync_to, sync)
+            };
+            let last = sync.head.map(|head| to.map_or(head, |to| to.min(head)));
+            if direction == Direction::Forward
+                && sync.hi == Some(number)
+                && last.map(|last| number < last).unwrap_or_default()
+            {
+                events
+                    .push(Event::PullBlock(number + 1, Felt::try_new("0x0")?));
+            }
         }
         Event::PurgeBlock(number, hash) => {
-            let db = &mut ctx.lock().await.db;
-            purge_block(db, number, hash.clone(), &mut events).await?;
+            let ctx = &mut ctx.lock().await;
+            purge_block(&mut ctx.db, number, hash.clone(), &mut events).await?;
             tracing::warn!(number, hash = hash.as_ref(), "Block purged");
+            ctx.notify(Notification::Reorg((number, hash).into()));
         }
         Event::Head(number, hash) => {
             metrics::gauge!("head_level_two", number as f64);
@@ -510,6 +1290,123 @@ where
             let hash = state.state_block_hash.as_ref();
             metrics::gauge!("head_level_one", number as f64);
             tracing::info!(number, hash, "L1 head");
+
+            let db = ctx.lock().await.db.clone();
+            let accepted = accept_on_l1(&db, &state).await?;
+            if accepted > 0 {
+                tracing::info!(number, blocks = accepted, "Accepted on L1");
+                let hash = Felt::try_new(&U256::from_hex(hash)?.into_str())?;
+                let block = (number, hash).into();
+                ctx.lock().await.notify(Notification::AcceptedOnL1(block));
+            }
+            if ctx.lock().await.config.state_tries() {
+                check_l1_root(&db, &state).await?;
+            }
+        }
+        Event::Settle(to, updates, messages) => {
+            let db = ctx.lock().await.db.clone();
+            for update in &updates {
+                let number = update.state.state_block_number;
+                let key = U64::from_u64(number);
+                let val = Settlement::from(
+                    U64::from_u64(update.eth_block_number),
+                    U256::from_hex(update.eth_block_hash.as_ref())?,
+                    U256::from_hex(update.eth_tx_hash.as_ref())?,
+                );
+                db.settlements_index.write().await.insert(&key, val)?;
+                tracing::debug!(
+                    number,
+                    eth_block = update.eth_block_number,
+                    eth_tx = update.eth_tx_hash.as_ref(),
+                    "State update settled"
+                );
+            }
+            for message in &messages {
+                // Keyed by the L1 tx hash that stays the same even if the tx
+                // gets re-included into another L1 block after a reorg.
+                let key = TxAndMessage::from(
+                    U256::from_hex(message.eth_tx_hash.as_ref())?,
+                    message.hash.clone(),
+                );
+                // The log index keeps the order of messages within the L1 tx,
+                // the L1 block allows to drop the messages on L1 reorg.
+                let val = EthLog::from(
+                    U64::from_u64(message.eth_block_number),
+                    U64::from_u64(message.eth_log_index),
+                );
+                db.l1_messages_index.write().await.insert(&key, val)?;
+                tracing::debug!(
+                    hash = message.hash.into_str(),
+                    eth_block = message.eth_block_number,
+                    eth_tx = message.eth_tx_hash.as_ref(),
+                    "Message sent to L2"
+                );
+            }
+            ctx.lock().await.shared.lock().await.sync.eth = Some(to + 1);
+            // The L1 head is polled separately (`poll_eth`): the backfill
+            // can be far behind it.
+            metrics::gauge!("head_level_one_scanned", to as f64);
+        }
+        Event::EthReorg(number) => {
+            let db = ctx.lock().await.db.clone();
+            let dropped = drop_settlements(&db, number).await?;
+            let messages = drop_messages(&db, number).await?;
+            ctx.lock().await.shared.lock().await.sync.eth = Some(number);
+            metrics::counter!("eth_reorg", 1);
+            tracing::warn!(number, dropped, messages, "L1 reorg detected");
+        }
+        Event::ApplyStates(number) => {
+            let (db, shared) = {
+                let ctx = ctx.lock().await;
+                (ctx.db.clone(), ctx.shared())
+            };
+            let t = Instant::now();
+            match apply_states(&db, APPLY_BATCH).await {
+                Ok(0) => (),
+                Ok(applied) => {
+                    metrics::gauge!("state_apply", t.elapsed().as_secs_f64());
+                    tracing::info!(from = number, applied, "States applied");
+                }
+                Err(e) => {
+                    // The poller skips the block until it is replaced
+                    let next = next_state(&db).await?;
+                    shared.lock().await.sync.mismatch = next;
+                    return Err(e);
+                }
+            }
+        }
+        Event::Prune(horizon) => {
+            let (db, archive, shared) = {
+                let ctx = ctx.lock().await;
+                let archive = ctx
+                    .config
+                    .pruning
+                    .as_ref()
+                    .map(|pruning| pruning.archive)
+                    .unwrap_or_default();
+                (ctx.db.clone(), archive, ctx.shared())
+            };
+            if std::mem::replace(&mut shared.lock().await.sync.pruning, true) {
+                return Ok(events);
+            }
+            let result = prune::prune(&db, horizon, archive).await;
+            let lo = db
+                .blocks_index
+                .read()
+                .await
+                .min()
+                .ok()
+                .flatten()
+                .map(|lo| lo.into_u64());
+            let pruned = db.pruned().await.ok().flatten();
+            {
+                let sync = &mut shared.lock().await.sync;
+                sync.pruning = false;
+                sync.lo = lo.or(sync.lo);
+                sync.pruned = pruned.or(sync.pruned);
+            }
+            let pruned = result?;
+            tracing::info!(horizon, pruned, "Blocks pruned");
         }
     }
 
@@ -528,6 +1425,44 @@ where
     Ok(Some(Event::Uptime { seconds }))
 }
 
+pub async fn poll_prune<ETH, SEQ>(
+    ctx: Arc<Mutex<Context<ETH, SEQ>>>,
+) -> anyhow::Result<Option<Event>>
+where
+    ETH: EthApi,
+    SEQ: SeqApi,
+{
+    let ctx = ctx.lock().await;
+    let horizon = match ctx.horizon().await {
+        Some(horizon) => horizon,
+        None => return Ok(None),
+    };
+    let lo = ctx.shared.lock().await.sync.lo.unwrap_or_default();
+    if horizon >= lo + prune::PRUNE_BATCH {
+        Ok(Some(Event::Prune(horizon)))
+    } else {
+        Ok(None)
+    }
+}
+
+pub async fn poll_states<ETH, SEQ>(
+    ctx: Arc<Mutex<Context<ETH, SEQ>>>,
+) -> anyhow::Result<Option<Event>>
+where
+    ETH: EthApi,
+    SEQ: SeqApi,
+{
+    let (db, shared) = {
+        let ctx = ctx.lock().await;
+        (ctx.db.clone(), ctx.shared())
+    };
+    let next = next_state(&db).await?;
+    if next.is_none() || next == shared.lock().await.sync.mismatch {
+        return Ok(None);
+    }
+    Ok(next.map(|(number, _)| Event::ApplyStates(number)))
+}
+
 pub async fn poll_eth<ETH, SEQ>(
     ctx: Arc<Mutex<Context<ETH, SEQ>>>,
 ) -> anyhow::Result<Option<Event>>
@@ -540,6 +1475,91 @@ where
     Ok(Some(Event::Ethereum(state)))
 }
 
+pub async fn poll_eth_logs<ETH, SEQ>(
+    ctx: Arc<Mutex<Context<ETH, SEQ>>>,
+) -> anyhow::Result<Option<Event>>
+where
+    ETH: EthApi,
+    SEQ: SeqApi,
+{
+    let (eth, db, addr, depth, deployed, cursor) = {
+        let ctx = ctx.lock().await;
+        let cursor = ctx.shared.lock().await.sync.eth;
+        (
+            ctx.eth.clone(),
+            ctx.db.clone(),
+            ctx.config.ethereum_contract_address.clone(),
+            ctx.config.eth_confirmations,
+            ctx.config.eth_deploy_block,
+            cursor,
+        )
+    };
+
+    let head = eth.get_block_number().await?;
+    let confirmed = head.saturating_sub(depth);
+
+    // The last settlement and all settlements logged within the
+    // confirmation depth below it are checked: the lowest mismatch is
+    // where the L1 reorg starts.
+    let settlements = {
+        let idx = db.settlements_index.read().await;
+        let mut settlements: Vec<Settlement> = Vec::new();
+        let mut next = idx.max()?;
+        while let Some(key) = next {
+            let settlement = match idx.lookup(&key)? {
+                Some(settlement) => settlement,
+                None => break,
+            };
+            if let Some(last) = settlements.first() {
+                let floor = last.number().into_u64().saturating_sub(depth);
+                if settlement.number().into_u64() < floor {
+                    break;
+                }
+            }
+            settlements.push(settlement);
+            next = idx.below(&key)?;
+        }
+        settlements
+    };
+    let mut reorg = None;
+    for settlement in &settlements {
+        let number = settlement.number().into_u64();
+        let hash = eth.get_block_hash(number).await?;
+        if U256::from_hex(hash.as_ref())? != settlement.block() {
+            reorg = Some(number);
+        }
+    }
+    if let Some(number) = reorg {
+        return Ok(Some(Event::EthReorg(number)));
+    }
+
+    // Without a settlement the logs are scanned from the deployment
+    // of the core contract (if known)
+    let from = match cursor {
+        Some(from) => from,
+        None => settlements
+            .first()
+            .map(|last| last.number().into_u64())
+            .or(deployed)
+            .unwrap_or(confirmed.saturating_sub(ETH_LOGS_RANGE)),
+    };
+    if from > confirmed {
+        return Ok(None);
+    }
+    let to = confirmed.min(from + ETH_LOGS_RANGE - 1);
+
+    let updates = eth.get_updates(&addr, from, to).await?;
+    let messages = eth.get_messages(&addr, from, to).await?;
+    tracing::debug!(
+        from,
+        to,
+        updates = updates.len(),
+        messages = messages.len(),
+        "L1 logs scanned"
+    );
+    Ok(Some(Event::Settle(to, updates, messages)))
+}
+
 pub async fn poll_seq<ETH, SEQ>(
     ctx: Arc<Mutex<Context<ETH, SEQ>>>,
 ) -> anyhow::Result<Option<Event>>
@@ -558,8 +1578,47 @@ where
         "Latest block"
     );
 
-    let block_exists =
-        ctx.lock().await.db.blocks.has(block_hash.as_ref()).await?;
+    let (direction, from, to, hi) = {
+        let ctx = ctx.lock().await;
+        let sync = &mut ctx.shared.lock().await.sync;
+        sync.head = Some(block_number);
+        (
+            ctx.config.direction,
+            ctx.config.sync_from,
+            ctx.config.sync_to,
+            sync.hi,
+        )
+    };
+
+    if direction == Direction::Forward {
+        // The block next to the highest stored one is pulled (by number)
+        let next = hi.map(|hi| hi + 1).unwrap_or(from.unwrap_or_default());
+        let last = to.map_or(block_number, |to| to.min(block_number));
+        if next <= last {
+            return Ok(Some(Event::PullBlock(next, Felt::try_new("0x0")?)));
+        }
+        return Ok(Some(Event::Head(block_number, block_hash)));
+    }
+
+    if let Some(to) = to.filter(|to| block_number > *to) {
+        // The head of the sync range is pulled instead of the latest block
+        let db = ctx.lock().await.db.clone();
+        let saved = db.blocks_index.read().await.lookup(&U64::from_u64(to))?;
+        if saved.is_none() {
+            return Ok(Some(Event::PullBlock(to, Felt::try_new("0x0")?)));
+        }
+        return Ok(Some(Event::Head(block_number, block_hash)));
+    }
+
+    // The hash index is written last: a block blob left by a failed pull
+    // does not count as stored
+    let block_exists = ctx
+        .lock()
+        .await
+        .db
+        .block_number(block_hash.as_ref())
+        .await?
+        .is_some();
     if !block_exists {
         Ok(Some(Event::PullBlock(block_number, block_hash)))
     } else {
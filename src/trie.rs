@@ -0,0 +1,523 @@
+//! Starknet state tries: height-251 Merkle-Patricia tries of contract
+//! storage, of contracts (the global state) and of Sierra classes.
+//!
+//! Nodes are stored by their hash and never overwritten, so the trie at
+//! any stored root stays readable (a reorg only rolls back the roots).
+
+use std::collections::{BTreeMap, HashMap};
+
+use starknet_crypto::{
+    pedersen_hash, poseidon_hash, poseidon_hash_many, FieldElement,
+};
+use yakvdb::typed::{Store, DB};
+
+use crate::{
+    db::{ContractState, StateRoots, Storage, TrieNode},
+    seq::dto,
+    util::U256,
+};
+
+/// Height of the state tries.
+pub const HEIGHT: usize = 251;
+
+/// The trie keys are 251-bit felts: the top 5 bits are always zero.
+const OFFSET: usize = 256 - HEIGHT;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Hasher {
+    /// Storage and contracts tries.
+    Pedersen,
+    /// Classes trie.
+    Poseidon,
+}
+
+impl Hasher {
+    fn hash(&self, a: &FieldElement, b: &FieldElement) -> FieldElement {
+        match self {
+            Self::Pedersen => pedersen_hash(a, b),
+            Self::Poseidon => poseidon_hash(a, b),
+        }
+    }
+}
+
+pub fn to_felt(value: &U256) -> anyhow::Result<FieldElement> {
+    FieldElement::from_bytes_be(&value.0)
+        .map_err(|_| anyhow::anyhow!("Invalid felt: {}", value.into_str()))
+}
+
+pub fn from_felt(felt: FieldElement) -> U256 {
+    U256(felt.to_bytes_be())
+}
+
+fn short(s: &str) -> FieldElement {
+    FieldElement::from_byte_slice_be(s.as_bytes()).expect("short string")
+}
+
+fn bit(bytes: &[u8; 32], index: usize) -> bool {
+    (bytes[index / 8] >> (7 - index % 8)) & 1 == 1
+}
+
+fn set_bit(bytes: &mut [u8; 32], index: usize) {
+    bytes[index / 8] |= 1 << (7 - index % 8);
+}
+
+/// Edge path: `length` bits stored right-aligned.
+#[derive(Clone, Debug)]
+struct Path {
+    bits: U256,
+    length: usize,
+}
+
+impl Path {
+    /// Bits `depth..depth+length` of the key.
+    fn of(key: &U256, depth: usize, length: usize) -> Self {
+        let mut bits = [0u8; 32];
+        for i in 0..length {
+            if bit(&key.0, OFFSET + depth + i) {
+                set_bit(&mut bits, 256 - length + i);
+            }
+        }
+        Self {
+            bits: U256(bits),
+            length,
+        }
+    }
+
+    fn get(&self, i: usize) -> bool {
+        bit(&self.bits.0, 256 - self.length + i)
+    }
+
+    /// Bits `from..` of the path.
+    fn suffix(&self, from: usize) -> Self {
+        let length = self.length - from;
+        let mut bits = [0u8; 32];
+        for i in 0..length {
+            if self.get(from + i) {
+                set_bit(&mut bits, 256 - length + i);
+            }
+        }
+        Self {
+            bits: U256(bits),
+            length,
+        }
+    }
+
+    /// The path followed by the other one.
+    fn join(&self, other: &Path) -> Self {
+        let length = self.length + other.length;
+        let mut bits = [0u8; 32];
+        for i in 0..length {
+            let set = if i < self.length {
+                self.get(i)
+            } else {
+                other.get(i - self.length)
+            };
+            if set {
+                set_bit(&mut bits, 256 - length + i);
+            }
+        }
+        Self {
+            bits: U256(bits),
+            length,
+        }
+    }
+}
+
+fn key_bit(key: &U256, depth: usize) -> bool {
+    bit(&key.0, OFFSET + depth)
+}
+
+/// Value stored in the trie under the key (`None` if missing or zero).
+pub fn get(
+    nodes: &Store<U256, TrieNode>,
+    root: &U256,
+    key: &U256,
+) -> anyhow::Result<Option<U256>> {
+    let zero = U256::default();
+    let mut hash = root.clone();
+    let mut depth = 0;
+    while depth < HEIGHT {
+        if hash == zero {
+            return Ok(None);
+        }
+        let node = nodes.lookup(&hash)?.ok_or_else(|| {
+            anyhow::anyhow!("Missing trie node: {}", hash.into_str())
+        })?;
+        let length = node.length() as usize;
+        if length == 0 {
+            hash = if key_bit(key, depth) {
+                node.right()
+            } else {
+                node.left()
+            };
+            depth += 1;
+        } else {
+            let path = Path {
+                bits: node.right(),
+                length,
+            };
+            if (0..length).any(|i| path.get(i) != key_bit(key, depth + i)) {
+                return Ok(None);
+            }
+            hash = node.left();
+            depth += length;
+        }
+    }
+    Ok(Some(hash).filter(|value| value != &zero))
+}
+
+/// Nodes on the path from the root to the key: the proof of the value
+/// (or of its absence, if the path diverges from the key).
+pub fn proof(
+    nodes: &Store<U256, TrieNode>,
+    root: &U256,
+    key: &U256,
+) -> anyhow::Result<Vec<TrieNode>> {
+    let mut proof = Vec::new();
+    let mut hash = root.clone();
+    let mut depth = 0;
+    while depth < HEIGHT && hash != U256::default() {
+        let node = nodes.lookup(&hash)?.ok_or_else(|| {
+            anyhow::anyhow!("Missing trie node: {}", hash.into_str())
+        })?;
+        let length = node.length() as usize;
+        let mut diverged = false;
+        if length == 0 {
+            hash = if key_bit(key, depth) {
+                node.right()
+            } else {
+                node.left()
+            };
+            depth += 1;
+        } else {
+            let path = Path {
+                bits: node.right(),
+                length,
+            };
+            diverged =
+                (0..length).any(|i| path.get(i) != key_bit(key, depth + i));
+            hash = node.left();
+            depth += length;
+        }
+        proof.push(node);
+        if diverged {
+            break;
+        }
+    }
+    Ok(proof)
+}
+
+/// Apply the changes (sorted by key, zero value removes the key) to the
+/// trie with the given root, returns the new root (zero if empty).
+pub fn update(
+    nodes: &mut Store<U256, TrieNode>,
+    hasher: Hasher,
+    root: &U256,
+    changes: &[(U256, U256)],
+) -> anyhow::Result<U256> {
+    let root = Some(root.clone()).filter(|root| root != &U256::default());
+    let mut trie = Trie { nodes, hasher };
+    let root = trie.update(root, 0, changes)?;
+    Ok(root.unwrap_or_default())
+}
+
+struct Trie<'a> {
+    nodes: &'a mut Store<U256, TrieNode>,
+    hasher: Hasher,
+}
+
+impl Trie<'_> {
+    fn load(&self, hash: &U256) -> anyhow::Result<TrieNode> {
+        self.nodes.lookup(hash)?.ok_or_else(|| {
+            anyhow::anyhow!("Missing trie node: {}", hash.into_str())
+        })
+    }
+
+    fn binary(&mut self, left: U256, right: U256) -> anyhow::Result<U256> {
+        let hash = self.hasher.hash(&to_felt(&left)?, &to_felt(&right)?);
+        let hash = from_felt(hash);
+        self.nodes.insert(&hash, TrieNode::binary(left, right))?;
+        Ok(hash)
+    }
+
+    fn edge(&mut self, child: U256, path: Path) -> anyhow::Result<U256> {
+        let hash = self.hasher.hash(&to_felt(&child)?, &to_felt(&path.bits)?)
+            + FieldElement::from(path.length as u64);
+        let hash = from_felt(hash);
+        let node = TrieNode::edge(child, path.bits, path.length as u8);
+        self.nodes.insert(&hash, node)?;
+        Ok(hash)
+    }
+
+    /// Edge with the path leading to the subtree at `depth`
+    /// (merged with the edge at the top of the subtree, if any).
+    fn prepend(
+        &mut self,
+        path: Path,
+        subtree: Option<U256>,
+        depth: usize,
+    ) -> anyhow::Result<Option<U256>> {
+        let subtree = match subtree {
+            Some(subtree) => subtree,
+            None => return Ok(None),
+        };
+        if depth < HEIGHT {
+            let node = self.load(&subtree)?;
+            let length = node.length() as usize;
+            if length > 0 {
+                let rest = Path {
+                    bits: node.right(),
+                    length,
+                };
+                return self.edge(node.left(), path.join(&rest)).map(Some);
+            }
+        }
+        self.edge(subtree, path).map(Some)
+    }
+
+    fn update(
+        &mut self,
+        node: Option<U256>,
+        depth: usize,
+        changes: &[(U256, U256)],
+    ) -> anyhow::Result<Option<U256>> {
+        if changes.is_empty() {
+            return Ok(node);
+        }
+        if depth == HEIGHT {
+            let (_, value) = &changes[changes.len() - 1];
+            return Ok(Some(value.clone()).filter(|v| v != &U256::default()));
+        }
+
+        let loaded = node.as_ref().map(|hash| self.load(hash)).transpose()?;
+        let edge =
+            loaded
+                .as_ref()
+                .filter(|node| node.length() > 0)
+                .map(|node| Path {
+                    bits: node.right(),
+                    length: node.length() as usize,
+                });
+
+        // Longest prefix shared by all changed keys (and the edge)
+        let (first, last) = (&changes[0].0, &changes[changes.len() - 1].0);
+        let limit = match (&loaded, &edge) {
+            (None, _) => HEIGHT - depth,
+            (Some(_), Some(edge)) => edge.length,
+            (Some(_), None) => 0,
+        };
+        let mut common = 0;
+        while common < limit
+            && key_bit(first, depth + common) == key_bit(last, depth + common)
+            && edge
+                .as_ref()
+                .map(|edge| edge.get(common) == key_bit(first, depth + common))
+                .unwrap_or(true)
+        {
+            common += 1;
+        }
+
+        if common > 0 {
+            let below = match (&loaded, &edge) {
+                (Some(node), Some(edge)) if common == edge.length => {
+                    Some(node.left())
+                }
+                (Some(node), Some(edge)) => {
+                    let rest = edge.suffix(common);
+                    Some(self.edge(node.left(), rest)?)
+                }
+                _ => None,
+            };
+            let below = self.update(below, depth + common, changes)?;
+            let path = Path::of(first, depth, common);
+            return self.prepend(path, below, depth + common);
+        }
+
+        let (left, right) = match (loaded, edge) {
+            (None, _) => (None, None),
+            (Some(node), None) => (Some(node.left()), Some(node.right())),
+            (Some(node), Some(edge)) => {
+                // The changes diverge right at the first bit of the edge
+                let rest = if edge.length == 1 {
+                    node.left()
+                } else {
+                    self.edge(node.left(), edge.suffix(1))?
+                };
+                if edge.get(0) {
+                    (None, Some(rest))
+                } else {
+                    (Some(rest), None)
+                }
+            }
+        };
+
+        let split = changes.partition_point(|(key, _)| !key_bit(key, depth));
+        let (lo, hi) = changes.split_at(split);
+        let left = self.update(left, depth + 1, lo)?;
+        let right = self.update(right, depth + 1, hi)?;
+        match (left, right) {
+            (Some(left), Some(right)) => self.binary(left, right).map(Some),
+            (Some(left), None) => {
+                let path = Path::of(&U256::default(), depth, 1);
+                self.prepend(path, Some(left), depth + 1)
+            }
+            (None, Some(right)) => {
+                let mut bits = [0u8; 32];
+                set_bit(&mut bits, 255);
+                let path = Path {
+                    bits: U256(bits),
+                    length: 1,
+                };
+                self.prepend(path, Some(right), depth + 1)
+            }
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Leaf of the contracts trie.
+pub fn contract_leaf(state: &ContractState) -> anyhow::Result<U256> {
+    let hash = pedersen_hash(
+        &pedersen_hash(
+            &pedersen_hash(
+                &to_felt(&state.class_hash())?,
+                &to_felt(&state.storage_root())?,
+            ),
+            &to_felt(&state.nonce())?,
+        ),
+        &FieldElement::ZERO,
+    );
+    Ok(from_felt(hash))
+}
+
+/// Leaf of the classes trie.
+pub fn class_leaf(compiled_class_hash: &U256) -> anyhow::Result<U256> {
+    let hash = poseidon_hash(
+        &short("CONTRACT_CLASS_LEAF_V0"),
+        &to_felt(compiled_class_hash)?,
+    );
+    Ok(from_felt(hash))
+}
+
+/// Global state commitment: the contracts trie root alone until the first
+/// Sierra class is declared.
+pub fn state_commitment(roots: &StateRoots) -> anyhow::Result<U256> {
+    let (contracts, classes) = (roots.contracts(), roots.classes());
+    if classes == U256::default() {
+        return Ok(contracts);
+    }
+    let hash = poseidon_hash_many(&[
+        short("STARKNET_STATE_V0"),
+        to_felt(&contracts)?,
+        to_felt(&classes)?,
+    ]);
+    Ok(from_felt(hash))
+}
+
+fn sorted(changes: BTreeMap<[u8; 32], U256>) -> Vec<(U256, U256)> {
+    changes
+        .into_iter()
+        .map(|(key, value)| (U256(key), value))
+        .collect()
+}
+
+/// Apply the state diff to the tries with the given roots, returns the
+/// roots after the update.
+pub async fn apply_state(
+    db: &Storage,
+    roots: &StateRoots,
+    state: &dto::StateUpdate,
+) -> anyhow::Result<StateRoots> {
+    let diff = &state.state_diff;
+
+    #[derive(Default)]
+    struct Change {
+        class_hash: Option<U256>,
+        nonce: Option<U256>,
+        storage: BTreeMap<[u8; 32], U256>,
+    }
+
+    let mut contracts: HashMap<[u8; 32], Change> = HashMap::new();
+    for deployed in &diff.deployed_contracts {
+        let address = U256::from_hex(deployed.address.as_ref())?;
+        let change = contracts.entry(address.0).or_default();
+        change.class_hash = Some(U256::from_hex(deployed.class_hash.as_ref())?);
+    }
+    for replaced in &diff.replaced_classes {
+        let address = U256::from_hex(replaced.address.as_ref())?;
+        let change = contracts.entry(address.0).or_default();
+        change.class_hash = Some(U256::from_hex(replaced.class_hash.as_ref())?);
+    }
+    for (address, nonce) in &diff.nonces {
+        let address = U256::from_hex(address.as_ref())?;
+        let change = contracts.entry(address.0).or_default();
+        change.nonce = Some(U256::from_hex(nonce.as_ref())?);
+    }
+    for (address, kvs) in &diff.storage_diffs {
+        let address = U256::from_hex(address.as_ref())?;
+        let change = contracts.entry(address.0).or_default();
+        for kv in kvs {
+            let key = U256::from_hex(kv.key.as_ref())?;
+            let value = U256::from_hex(kv.value.as_ref())?;
+            change.storage.insert(key.0, value);
+        }
+    }
+
+    let mut nodes = db.trie_nodes_index.write().await;
+    let mut states = db.contract_states_index.write().await;
+
+    let mut leaves = BTreeMap::new();
+    for (address, change) in contracts {
+        let current = get(&nodes, &roots.contracts(), &U256(address))?
+            .map(|leaf| {
+                states.lookup(&leaf)?.ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Missing contract state: {}",
+                        leaf.into_str()
+                    )
+                })
+            })
+            .transpose()?;
+        let (class_hash, storage_root, nonce) = match current {
+            Some(state) => {
+                (state.class_hash(), state.storage_root(), state.nonce())
+            }
+            None => Default::default(),
+        };
+        let storage_root = update(
+            &mut nodes,
+            Hasher::Pedersen,
+            &storage_root,
+            &sorted(change.storage),
+        )?;
+        let state = ContractState::from(
+            change.class_hash.unwrap_or(class_hash),
+            storage_root,
+            change.nonce.unwrap_or(nonce),
+        );
+        let leaf = contract_leaf(&state)?;
+        states.insert(&leaf, state)?;
+        leaves.insert(address, leaf);
+    }
+    let contracts_root = update(
+        &mut nodes,
+        Hasher::Pedersen,
+        &roots.contracts(),
+        &sorted(leaves),
+    )?;
+
+    let mut classes = BTreeMap::new();
+    for declared in &diff.declared_classes {
+        let class_hash = U256::from_hex(declared.class_hash.as_ref())?;
+        let compiled = U256::from_hex(declared.compiled_class_hash.as_ref())?;
+        classes.insert(class_hash.0, class_leaf(&compiled)?);
+    }
+    let classes_root = update(
+        &mut nodes,
+        Hasher::Poseidon,
+        &roots.classes(),
+        &sorted(classes),
+    )?;
+
+    Ok(StateRoots::from(contracts_root, classes_root))
+}
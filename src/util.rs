@@ -9,13 +9,14 @@ use crate::{
         DeclareTxn, DeclareTxnReceipt, DeclareTxnReceiptType,
         DeclaredClassesItem, DeployAccountTxnReceipt,
         DeployAccountTxnReceiptType, DeployTxnReceipt, DeployTxnReceiptType,
-        DeployedContractItem, Felt, GetClassResult, InvokeTxnReceipt,
-        InvokeTxnReceiptType, L1HandlerTxnReceipt, L1HandlerTxnReceiptType,
-        NoncesItem, PendingStateUpdate, ReplacedClassesItem, SierraEntryPoint,
-        StateDiff, StateUpdate, StorageEntriesItem, Txn, TxnHash, TxnReceipt,
-        TxnStatus,
+        DeployedContractItem, Felt, GetClassResult, InvokeTxnKind,
+        InvokeTxnReceipt, InvokeTxnReceiptType, L1HandlerTxnReceipt,
+        L1HandlerTxnReceiptType, NoncesItem, PendingStateUpdate,
+        ReplacedClassesItem, SierraEntryPoint, StateDiff, StateUpdate,
+        StorageEntriesItem, Txn, TxnHash, TxnReceipt, TxnStatus,
     },
     ctx::Context,
+    eth,
     seq::dto::{self, DeclaredClass, DeployedContract, ReplacedClass},
 };
 
@@ -118,6 +119,12 @@ impl U256 {
         Ok(Self(slice))
     }
 
+    pub fn from_u64(x: u64) -> Self {
+        let mut slice = [0u8; 32];
+        slice[24..].copy_from_slice(&x.to_be_bytes());
+        Self(slice)
+    }
+
     pub fn into_str(&self) -> String {
         let unpadded = hex::encode(self.0)
             .chars()
@@ -280,6 +287,82 @@ pub fn tx_hash(tx: &Txn) -> &Felt {
     }
 }
 
+/// Address of the account (or contract) that initiated the transaction.
+/// Deploy transactions have no sender: the deployed address is only
+/// known from the state diff.
+pub fn tx_sender(tx: &Txn) -> Option<&Felt> {
+    match tx {
+        Txn::DeclareTxn(DeclareTxn::DeclareTxnV1(txn)) => {
+            Some(&txn.sender_address.0)
+        }
+        Txn::DeclareTxn(DeclareTxn::DeclareTxnV2(txn)) => {
+            Some(&txn.declare_txn_v1.sender_address.0)
+        }
+        Txn::InvokeTxn(txn) => match &txn.invoke_txn_kind {
+            InvokeTxnKind::FunctionCall(call) => Some(&call.contract_address.0),
+            InvokeTxnKind::InvokeTxnV1(txn) => Some(&txn.sender_address.0),
+        },
+        Txn::L1HandlerTxn(txn) => Some(&txn.function_call.contract_address.0),
+        Txn::DeployAccountTxn(_) | Txn::DeployTxn(_) => None,
+    }
+}
+
+/// Hashes of L1<->L2 messages in the block, along with the hash of the
+/// transaction that consumed (L1->L2) or sent (L2->L1) each message.
+pub fn get_messages(block: &BlockWithTxs) -> anyhow::Result<Vec<(U256, U256)>> {
+    let mut messages = Vec::new();
+
+    for tx in &block.block_body_with_txs.transactions {
+        if let Txn::L1HandlerTxn(txn) = tx {
+            // Calldata of the L1 handler is the L1 sender followed by the payload
+            let calldata = txn
+                .function_call
+                .calldata
+                .iter()
+                .map(|felt| U256::from_hex(felt.as_ref()))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let (from, payload) = match calldata.split_first() {
+                Some(split) => split,
+                None => continue,
+            };
+            let hash = eth::l1_to_l2_message_hash(
+                from,
+                &U256::from_hex(txn.function_call.contract_address.0.as_ref())?,
+                &U256::from_hex(txn.nonce.as_ref())?,
+                &U256::from_hex(
+                    txn.function_call.entry_point_selector.as_ref(),
+                )?,
+                payload,
+            );
+            messages
+                .push((hash, U256::from_hex(txn.transaction_hash.0.as_ref())?));
+        }
+    }
+
+    for receipt in &block.receipts {
+        for message in &receipt.l2_to_l1_messages {
+            let from = match message.from_address.as_ref() {
+                Some(from) => U256::from_hex(from.as_ref())?,
+                None => continue,
+            };
+            let payload = message
+                .payload
+                .iter()
+                .map(|felt| U256::from_hex(felt.as_ref()))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let hash = eth::l2_to_l1_message_hash(
+                &from,
+                &U256::from_hex(message.to_address.as_ref())?,
+                &payload,
+            );
+            let tx = U256::from_hex(receipt.transaction_hash.0.as_ref())?;
+            messages.push((hash, tx));
+        }
+    }
+
+    Ok(messages)
+}
+
 pub fn map_state_update(state: dto::StateUpdate) -> StateUpdate {
     StateUpdate {
         block_hash: BlockHash(state.block_hash),
@@ -0,0 +1,119 @@
+use armada::{
+    api::gen::BlockWithTxs,
+    cmd,
+    db::{Repo, Storage},
+    seq::dto,
+    util::{tx_hash, U256, U64},
+};
+use yakvdb::typed::DB;
+
+mod common;
+
+#[tokio::test]
+async fn test_export_import() -> anyhow::Result<()> {
+    let source = common::Test::new().await;
+    let target = common::Test::new().await;
+
+    let json = tokio::fs::read_to_string("etc/805543-block.json").await?;
+    let block: BlockWithTxs = serde_json::from_str(&json)?;
+    let json =
+        tokio::fs::read_to_string("etc/805543-state-update.json").await?;
+    let state: dto::StateUpdate = serde_json::from_str(&json)?;
+    let number = *block.block_header.block_number.as_ref() as u64;
+    let hash = block.block_header.block_hash.0.clone();
+    let tx = U256::from_hex(
+        tx_hash(&block.block_body_with_txs.transactions[0]).as_ref(),
+    )?;
+
+    source.ctx.db.blocks.put(hash.as_ref(), block).await?;
+    source.ctx.db.states.put(hash.as_ref(), state).await?;
+    source
+        .ctx
+        .db
+        .blocks_index
+        .write()
+        .await
+        .insert(&U64::from_u64(number), U256::from_hex(hash.as_ref())?)?;
+
+    let dir = tempdir::TempDir::new("export")?;
+    let file = dir.path().join("blocks.jsonl");
+    let file = file.to_str().unwrap();
+
+    assert_eq!(
+        cmd::export(&source.ctx.db, file, Some(number + 1), None).await?,
+        0
+    );
+    assert_eq!(cmd::export(&source.ctx.db, file, None, None).await?, 1);
+    assert_eq!(cmd::import(&target.ctx.db, file).await?, 1);
+
+    let db = &target.ctx.db;
+    assert!(db.blocks.has(hash.as_ref()).await?);
+    assert!(db.states.has(hash.as_ref()).await?);
+    assert!(db.txs_index.read().await.lookup(&tx)?.is_some());
+    assert_eq!(db.block_number(hash.as_ref()).await?, Some(number));
+
+    let status = cmd::status(db, "test").await?;
+    assert_eq!(status.lo, Some(number));
+    assert_eq!(status.hi, Some(number));
+
+    let (gaps, broken) = cmd::verify(db).await?;
+    assert!(gaps.is_empty());
+    assert!(broken.is_empty());
+
+    // Drop the TX index entry and restore it by reindexing
+    db.txs_index.write().await.remove(&tx)?;
+    assert_eq!(cmd::reindex(db).await?, 1);
+    assert!(db.txs_index.read().await.lookup(&tx)?.is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_only_storage() -> anyhow::Result<()> {
+    let writer = common::Test::new().await;
+
+    let dir = tempdir::TempDir::new("empty")?;
+    assert!(Storage::read_only(dir.path(), 16).await.is_err());
+
+    // Missing indices are not created by a reader
+    let block = dir.path().join("block");
+    tokio::fs::create_dir_all(&block).await?;
+    tokio::fs::File::create(block.join("index.yak")).await?;
+    assert!(Storage::read_only(dir.path(), 16).await.is_err());
+    assert!(!block.join("hash.yak").exists());
+
+    let json = tokio::fs::read_to_string("etc/805543-block.json").await?;
+    let block: BlockWithTxs = serde_json::from_str(&json)?;
+    let hash = block.block_header.block_hash.0.clone();
+
+    // Blocks written after the reader was opened are visible to it
+    let reader = Storage::read_only(&writer.ctx.db.base, 16).await?;
+    writer
+        .ctx
+        .db
+        .blocks
+        .put(hash.as_ref(), block.clone())
+        .await?;
+
+    assert!(reader.read_only);
+    assert!(reader.blocks.get(hash.as_ref()).await?.is_some());
+    assert!(reader.blocks.put(hash.as_ref(), block).await.is_err());
+    assert!(reader.blocks.del(hash.as_ref()).await.is_err());
+
+    // Index entries written after the reader was opened are visible to
+    // it once the indices are reopened
+    let key = U64::from_u64(805543);
+    let val = U256::from_hex(hash.as_ref())?;
+    assert!(reader.blocks_index.read().await.lookup(&key)?.is_none());
+    writer
+        .ctx
+        .db
+        .blocks_index
+        .write()
+        .await
+        .insert(&key, val.clone())?;
+    reader.reopen().await;
+    let found = reader.blocks_index.read().await.lookup(&key)?;
+    assert!(found == Some(val));
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use armada::eth::EthApi;
+use armada::{api::gen::NumAsHex, eth::EthApi};
 use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
 
 #[derive(Clone)]
@@ -11,6 +11,9 @@ pub struct TestEth {
 #[derive(Default)]
 struct Inner {
     state: Option<armada::eth::State>,
+    head: u64,
+    updates: Vec<armada::eth::Update>,
+    messages: Vec<armada::eth::Message>,
 }
 
 impl TestEth {
@@ -23,6 +26,20 @@ impl TestEth {
     pub async fn state(&self) -> MappedMutexGuard<Option<armada::eth::State>> {
         MutexGuard::map(self.inner.lock().await, |inner| &mut inner.state)
     }
+
+    pub async fn head(&self) -> MappedMutexGuard<u64> {
+        MutexGuard::map(self.inner.lock().await, |inner| &mut inner.head)
+    }
+
+    pub async fn updates(&self) -> MappedMutexGuard<Vec<armada::eth::Update>> {
+        MutexGuard::map(self.inner.lock().await, |inner| &mut inner.updates)
+    }
+
+    pub async fn messages(
+        &self,
+    ) -> MappedMutexGuard<Vec<armada::eth::Message>> {
+        MutexGuard::map(self.inner.lock().await, |inner| &mut inner.messages)
+    }
 }
 
 #[async_trait::async_trait]
@@ -38,4 +55,49 @@ impl EthApi for TestEth {
             anyhow::bail!("Failed to fetch ethereum contract state");
         }
     }
+
+    async fn get_block_number(&self) -> anyhow::Result<u64> {
+        Ok(*self.head().await)
+    }
+
+    async fn get_block_hash(&self, number: u64) -> anyhow::Result<NumAsHex> {
+        self.updates()
+            .await
+            .iter()
+            .find(|update| update.eth_block_number == number)
+            .map(|update| update.eth_block_hash.clone())
+            .ok_or(anyhow::anyhow!("Block not found"))
+    }
+
+    async fn get_updates(
+        &self,
+        _address: &str,
+        from: u64,
+        to: u64,
+    ) -> anyhow::Result<Vec<armada::eth::Update>> {
+        Ok(self
+            .updates()
+            .await
+            .iter()
+            .filter(|update| update.eth_block_number >= from)
+            .filter(|update| update.eth_block_number <= to)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_messages(
+        &self,
+        _address: &str,
+        from: u64,
+        to: u64,
+    ) -> anyhow::Result<Vec<armada::eth::Message>> {
+        Ok(self
+            .messages()
+            .await
+            .iter()
+            .filter(|message| message.eth_block_number >= from)
+            .filter(|message| message.eth_block_number <= to)
+            .cloned()
+            .collect())
+    }
 }
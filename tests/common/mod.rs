@@ -42,7 +42,10 @@ impl Test {
             Duration::from_secs(1),
             Duration::from_secs(1),
             "0x0".to_string(),
-        );
+        )
+        // Fixtures are testnet blocks: checked in dedicated tests only
+        .with_verify(false)
+        .with_admin(true);
 
         let ctx = Context::new(eth, seq, shared, db, config);
 
@@ -85,6 +88,18 @@ impl Test {
         let res: R = serde_json::from_value(res.result.take().unwrap())?;
         Ok(res)
     }
+
+    #[allow(dead_code)]
+    pub fn ws_url(&self) -> String {
+        self.url.replacen("http://", "ws://", 1)
+    }
+
+    #[allow(dead_code)]
+    pub async fn head(&self, path: &str) -> anyhow::Result<reqwest::Response> {
+        let base = self.url.trim_end_matches("/rpc/v0.3");
+        let res = self.http.head(format!("{base}{path}")).send().await?;
+        Ok(res)
+    }
 }
 
 // More on async in Drop impl: https://stackoverflow.com/a/75584109
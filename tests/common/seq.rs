@@ -1,6 +1,9 @@
 use std::sync::Arc;
 
-use armada::{api::gen::BlockWithTxs, seq::SeqApi};
+use armada::{
+    api::gen::BlockWithTxs,
+    seq::{dto::StateUpdate, SeqApi},
+};
 use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
 
 #[derive(Clone)]
@@ -11,6 +14,8 @@ pub struct TestSeq {
 #[derive(Default)]
 struct Inner {
     latest: Option<BlockWithTxs>,
+    block: Option<BlockWithTxs>,
+    state: Option<StateUpdate>,
 }
 
 impl TestSeq {
@@ -23,22 +28,49 @@ impl TestSeq {
     pub async fn latest(&self) -> MappedMutexGuard<Option<BlockWithTxs>> {
         MutexGuard::map(self.inner.lock().await, |inner| &mut inner.latest)
     }
+
+    /// Block served by number and by hash.
+    pub async fn block(&self) -> MappedMutexGuard<Option<BlockWithTxs>> {
+        MutexGuard::map(self.inner.lock().await, |inner| &mut inner.block)
+    }
+
+    /// State update served by block hash.
+    pub async fn state(&self) -> MappedMutexGuard<Option<StateUpdate>> {
+        MutexGuard::map(self.inner.lock().await, |inner| &mut inner.state)
+    }
 }
 
 #[async_trait::async_trait]
 impl SeqApi for TestSeq {
     async fn get_block_by_number(
         &self,
-        _block_number: u64,
+        block_number: u64,
     ) -> anyhow::Result<armada::api::gen::BlockWithTxs> {
-        Err(anyhow::anyhow!("Block not found"))
+        let block = self.block().await;
+        match block.as_ref() {
+            Some(block)
+                if *block.block_header.block_number.as_ref() as u64
+                    == block_number =>
+            {
+                Ok(block.clone())
+            }
+            _ => Err(anyhow::anyhow!("Block not found")),
+        }
     }
 
     async fn get_block_by_hash(
         &self,
-        _block_hash: &str,
+        block_hash: &str,
     ) -> anyhow::Result<armada::api::gen::BlockWithTxs> {
-        Err(anyhow::anyhow!("Block not found"))
+        let block = self.block().await;
+        match block.as_ref() {
+            Some(block)
+                if block.block_header.block_hash.0.as_ref() == block_hash =>
+            {
+                Ok(block.clone())
+            }
+            _ => Err(anyhow::anyhow!("Block not found")),
+        }
     }
 
     async fn get_latest_block(
@@ -66,9 +98,22 @@ impl SeqApi for TestSeq {
 
     async fn get_state_by_hash(
         &self,
-        _block_hash: &str,
+        block_hash: &str,
     ) -> anyhow::Result<armada::seq::dto::StateUpdate> {
-        Err(anyhow::anyhow!("State Update not found"))
+        let state = self.state().await;
+        match state.as_ref() {
+            Some(state) if state.block_hash.as_ref() == block_hash => {
+                Ok(state.clone())
+            }
+            _ => Err(anyhow::anyhow!("State Update not found")),
+        }
+    }
+
+    async fn get_signature(
+        &self,
+        _block_hash: &str,
+    ) -> anyhow::Result<armada::seq::dto::BlockSignature> {
+        Err(anyhow::anyhow!("Signature not found"))
     }
 
     async fn get_class_by_hash(
@@ -77,4 +122,18 @@ impl SeqApi for TestSeq {
     ) -> anyhow::Result<armada::seq::dto::Class> {
         Err(anyhow::anyhow!("Class not found"))
     }
+
+    async fn get_class_source(
+        &self,
+        _class_hash: &str,
+    ) -> anyhow::Result<String> {
+        Err(anyhow::anyhow!("Class not found"))
+    }
+
+    async fn get_compiled_class_by_class_hash(
+        &self,
+        _class_hash: &str,
+    ) -> anyhow::Result<armada::seq::dto::CompiledClass> {
+        Err(anyhow::anyhow!("Compiled class not found"))
+    }
 }
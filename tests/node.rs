@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use armada::{
+    api::gen::BlockWithTxs,
+    cfg::Config,
+    db::{Repo, Storage},
+    node::Node,
+    sub::Notification,
+    util::{U256, U64},
+};
+use yakvdb::typed::DB;
+
+mod common;
+
+use common::{eth::TestEth, seq::TestSeq};
+
+#[tokio::test]
+async fn test_node() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("node")?;
+    let db = Storage::new(dir.path()).await;
+    let config = Config::new(
+        "test".to_string(),
+        ([127, 0, 0, 1], 0).into(),
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+        "0x0".to_string(),
+    )
+    .with_chain_id("0x534e5f4d41494e".to_string());
+
+    // Blocks cannot be verified without the chain id
+    let unverifiable =
+        Node::new(config.clone().with_chain_id(String::new()), db.clone())
+            .with_eth(TestEth::new())
+            .with_seq(TestSeq::new())
+            .start()
+            .await;
+    assert!(unverifiable.is_err());
+
+    let node = Node::new(config, db)
+        .with_eth(TestEth::new())
+        .with_seq(TestSeq::new())
+        .start()
+        .await?;
+    assert!(node.sync.is_some());
+
+    let res: serde_json::Value = reqwest::Client::new()
+        .post(format!("http://{}/rpc/v0.3", node.addr))
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "starknet_chainId",
+            "params": [],
+            "id": 1
+        }))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(res["result"], "0x534e5f4d41494e");
+
+    node.shutdown().await;
+    assert!(reqwest::get(format!("http://{}/sync/status", node.addr))
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_node_without_sync() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("node")?;
+    let db = Storage::new(dir.path()).await;
+    let config = Config::new(
+        "test".to_string(),
+        ([127, 0, 0, 1], 0).into(),
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+        "0x0".to_string(),
+    );
+
+    let node = Node::new(config, db).start().await?;
+    assert!(node.sync.is_none());
+    assert!(node.refresh.is_some());
+
+    // The synced range refresh stops together with the RPC server
+    tokio::time::timeout(Duration::from_secs(5), node.shutdown()).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_node_without_sync_publishes_heads() -> anyhow::Result<()> {
+    let dir = tempdir::TempDir::new("node")?;
+    let db = Storage::new(dir.path()).await;
+    let config = Config::new(
+        "test".to_string(),
+        ([127, 0, 0, 1], 0).into(),
+        Duration::from_millis(100),
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+        "0x0".to_string(),
+    );
+
+    let node = Node::new(config, db.clone()).start().await?;
+    let mut rx = node.ctx.notify.subscribe();
+
+    // A block stored by the writer is published on the next refresh
+    let json = std::fs::read_to_string("./etc/805543-block.json")?;
+    let block: BlockWithTxs = serde_json::from_str(&json)?;
+    let hash = block.block_header.block_hash.0.as_ref().clone();
+    db.blocks.put(&hash, block).await?;
+    db.blocks_index
+        .write()
+        .await
+        .insert(&U64::from_u64(805543), U256::from_hex(&hash)?)?;
+
+    let notification =
+        tokio::time::timeout(Duration::from_secs(5), rx.recv()).await??;
+    match notification {
+        Notification::NewHead(header) => {
+            assert_eq!(header.block_hash.0.as_ref(), &hash);
+        }
+        other => panic!("Unexpected notification: {other:?}"),
+    }
+
+    node.shutdown().await;
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use armada::{
+    api::gen::BlockWithTxs,
+    db::{AddressWithKeyAndNumber, Repo},
+    seq::dto,
+    util::{tx_hash, U256, U64},
+};
+use yakvdb::typed::DB;
+
+mod common;
+
+#[tokio::test]
+async fn test_prune_below_horizon() -> anyhow::Result<()> {
+    let test = common::Test::new().await;
+    let db = &test.ctx.db;
+
+    let json = std::fs::read_to_string("./etc/805543-block.json")?;
+    let block: BlockWithTxs = serde_json::from_str(&json)?;
+    let hash = block.block_header.block_hash.0.as_ref().clone();
+    let tx = U256::from_hex(
+        tx_hash(&block.block_body_with_txs.transactions[0]).as_ref(),
+    )?;
+
+    db.blocks.put(&hash, block).await?;
+    db.blocks_index
+        .write()
+        .await
+        .insert(&U64::from_u64(1), U256::from_hex(&hash)?)?;
+    db.txs_index.write().await.insert(
+        &tx,
+        armada::db::BlockAndIndex::from(
+            U256::from_hex(&hash)?,
+            U64::from_u64(0),
+        ),
+    )?;
+
+    let address = U256::from_hex("0x1")?;
+    let key = U256::from_hex("0x2")?;
+    let item = |number: u64| {
+        AddressWithKeyAndNumber::from(
+            address.clone(),
+            key.clone(),
+            U64::from_u64(number),
+        )
+    };
+    // Pruning walks the state diffs of the pruned blocks only
+    let hash = &hash;
+    let store = |number: u64| async move {
+        let hash = if number == 1 {
+            U256::from_hex(&hash)?
+        } else {
+            U256::from_hex(&format!("0x{number}"))?
+        };
+        let key = U64::from_u64(number);
+        db.blocks_index.write().await.insert(&key, hash.clone())?;
+        let state: dto::StateUpdate =
+            serde_json::from_value(serde_json::json!({
+                "block_hash": hash.into_str(),
+                "new_root": "0x0",
+                "old_root": "0x0",
+                "state_diff": {
+                    "storage_diffs": {"0x1": [{"key": "0x2", "value": "0x0"}]},
+                    "nonces": {},
+                    "deployed_contracts": [],
+                    "old_declared_contracts": [],
+                    "declared_classes": [],
+                    "replaced_classes": []
+                }
+            }))?;
+        db.states.put(&hash.into_str(), state).await?;
+        Ok::<(), anyhow::Error>(())
+    };
+    for number in [1, 2, 3, 7] {
+        let val = U256::from_hex(&format!("0x1{number}"))?;
+        db.states_index.write().await.insert(&item(number), val)?;
+        if number < 5 {
+            store(number).await?;
+        }
+    }
+
+    let pruned = armada::prune::prune(db, 5, false).await?;
+    assert_eq!(pruned, 3);
+
+    assert!(db
+        .blocks_index
+        .read()
+        .await
+        .lookup(&U64::from_u64(1))?
+        .is_none());
+    assert!(!db.blocks.has(&hash).await?);
+    assert!(db.txs_index.read().await.lookup(&tx)?.is_none());
+
+    let states = db.states_index.read().await;
+    assert!(states.lookup(&item(1))?.is_none());
+    assert!(states.lookup(&item(2))?.is_none());
+    assert_eq!(states.lookup(&item(3))?, Some(U256::from_hex("0x13")?));
+    assert_eq!(states.lookup(&item(7))?, Some(U256::from_hex("0x17")?));
+    drop(states);
+
+    assert_eq!(db.pruned().await?, Some(5));
+    armada::prune::prune(db, 3, false).await?;
+    assert_eq!(db.pruned().await?, Some(5));
+
+    // The next pruning collapses versions left below the previous horizon
+    let val = U256::from_hex("0x19")?;
+    db.states_index.write().await.insert(&item(9), val)?;
+    store(9).await?;
+    assert_eq!(armada::prune::prune(db, 10, false).await?, 1);
+    let states = db.states_index.read().await;
+    assert!(states.lookup(&item(3))?.is_none());
+    assert!(states.lookup(&item(7))?.is_none());
+    assert_eq!(states.lookup(&item(9))?, Some(U256::from_hex("0x19")?));
+    drop(states);
+    assert_eq!(db.pruned().await?, Some(10));
+
+    Ok(())
+}
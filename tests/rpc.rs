@@ -76,3 +76,327 @@ mod get_block_with_txs {
         Ok(())
     }
 }
+
+mod get_account_activity {
+    use armada::api::gen::{AccountActivityChunk, BlockWithTxs};
+    use armada::util::{U256, U64};
+    use yakvdb::typed::DB;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sender_activity() -> anyhow::Result<()> {
+        let json = fs::read_to_string("./etc/805543-block.json")?;
+        let block: BlockWithTxs = serde_json::from_str(&json)?;
+        let hash = block.block_header.block_hash.0.clone();
+
+        let test = common::Test::new().await;
+        let mut db = test.ctx.db.clone();
+        armada::sync::save_block(&mut db, hash.clone(), block).await?;
+        db.blocks_index
+            .write()
+            .await
+            .insert(&U64::from_u64(805543), U256::from_hex(hash.as_ref())?)?;
+
+        let address =
+            "0x62eb503da732df69794bd13f933d4994694ae729c8f4602f6940292d326fcb0";
+        let res: AccountActivityChunk = test
+            .rpc(json!({
+                "jsonrpc": "2.0",
+                "method": "armada_getAccountActivity",
+                "params": {"filter": {"address": address, "chunk_size": 10}},
+                "id": 1
+            }))
+            .await?;
+
+        assert!(res.continuation_token.is_none());
+        assert_eq!(res.activity.len(), 1);
+
+        let activity = &res.activity[0];
+        assert_eq!(activity.block_hash.0.as_ref(), hash.as_ref());
+        assert!(!activity.state_update);
+
+        let txs = activity
+            .transactions
+            .iter()
+            .map(|tx| tx.0.as_ref().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            txs,
+            vec![
+                "0x78ddcf3ee7b8c9a487cd1efd03c77242fea40d9121c285b73ebccfb86e6b805",
+                "0xa476059593e41ec40c5b3d126553196512f7d07010c60b8dcbcd8975702f92",
+                "0x5f973c95872b4e89607d1434bf77007401ad0bf2cac650cff0aff4ed97912a2",
+            ]
+        );
+
+        Ok(())
+    }
+}
+
+mod block_by_hash {
+    use armada::util::{U256, U64};
+    use yakvdb::typed::DB;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_head_block() -> anyhow::Result<()> {
+        let test = common::Test::new().await;
+
+        let hash =
+            "0x2ac4a5d3d2d8a4e1c0d73c7a5c1b0b8f2a6e6e49ab4bd1b4fef4b4dd7b0c0d1";
+        test.ctx
+            .db
+            .blocks_index
+            .write()
+            .await
+            .insert(&U64::from_u64(42), U256::from_hex(hash)?)?;
+        assert_eq!(test.ctx.db.index_hashes().await?, 1);
+        assert_eq!(test.ctx.db.index_hashes().await?, 0);
+        assert_eq!(test.ctx.db.block_number(hash).await?, Some(42));
+
+        let res = test.head(&format!("/block/{hash}")).await?;
+        assert_eq!(res.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            res.headers()
+                .get("x-block-number")
+                .map(|value| value.to_str())
+                .transpose()?,
+            Some("42")
+        );
+
+        let res = test.head("/block/0x1").await?;
+        assert_eq!(res.status(), reqwest::StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+}
+
+mod get_class_declaration {
+    use armada::api::gen::{ClassDeclaration, Felt};
+    use armada::seq::dto::{self, DeclaredClass};
+    use armada::util::{U256, U64};
+    use yakvdb::typed::DB;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_declared_class() -> anyhow::Result<()> {
+        let json = fs::read_to_string("./etc/805543-state-update.json")?;
+        let mut state: dto::StateUpdate = serde_json::from_str(&json)?;
+        state.state_diff.declared_classes.push(DeclaredClass {
+            class_hash: Felt::try_new("0x123")?,
+            compiled_class_hash: Felt::try_new("0x456")?,
+        });
+        let hash = state.block_hash.clone();
+
+        let test = common::Test::new().await;
+        let mut db = test.ctx.db.clone();
+        // Top-down sync saves the later declaration first
+        let later = state.clone();
+        armada::sync::save_state(&mut db, hash.clone(), 805600, later).await?;
+        armada::sync::save_state(&mut db, hash.clone(), 805543, state).await?;
+        db.blocks_index
+            .write()
+            .await
+            .insert(&U64::from_u64(805543), U256::from_hex(hash.as_ref())?)?;
+
+        let res: ClassDeclaration = test
+            .rpc(json!({
+                "jsonrpc": "2.0",
+                "method": "armada_getClassDeclaration",
+                "params": {"class_hash": "0x123"},
+                "id": 1
+            }))
+            .await?;
+
+        assert_eq!(res.block_hash.0.as_ref(), hash.as_ref());
+        assert_eq!(res.block_number.as_ref(), &805543);
+        assert_eq!(
+            res.compiled_class_hash
+                .as_ref()
+                .map(|hash| hash.as_ref().as_str()),
+            Some("0x456")
+        );
+        assert!(res.transaction_hash.is_none());
+
+        let res: anyhow::Result<ClassDeclaration> = test
+            .rpc(json!({
+                "jsonrpc": "2.0",
+                "method": "armada_getClassDeclaration",
+                "params": {"class_hash": "0x789"},
+                "id": 2
+            }))
+            .await;
+        assert!(res.is_err());
+
+        Ok(())
+    }
+}
+
+mod get_compiled_casm {
+    use armada::api::gen::{CasmCompiledContractClass, Felt};
+    use armada::db::Repo;
+    use armada::seq::dto::{self, DeclaredClass};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stored_casm() -> anyhow::Result<()> {
+        let json = fs::read_to_string("./etc/805543-state-update.json")?;
+        let mut state: dto::StateUpdate = serde_json::from_str(&json)?;
+        state.state_diff.declared_classes.push(DeclaredClass {
+            class_hash: Felt::try_new("0x123")?,
+            compiled_class_hash: Felt::try_new("0x456")?,
+        });
+        state
+            .state_diff
+            .old_declared_contracts
+            .push(Felt::try_new("0x789")?);
+        let hash = state.block_hash.clone();
+
+        let test = common::Test::new().await;
+        let mut db = test.ctx.db.clone();
+        armada::sync::save_state(&mut db, hash, 805543, state).await?;
+        let casm = json!({"prime": "0x800000000000011000000000000000000000000000000000000000000000001"});
+        db.casms.put("0x456", casm.clone()).await?;
+
+        let res: CasmCompiledContractClass = test
+            .rpc(json!({
+                "jsonrpc": "2.0",
+                "method": "starknet_getCompiledCasm",
+                "params": {"class_hash": "0x123"},
+                "id": 1
+            }))
+            .await?;
+        assert_eq!(res.0, casm);
+
+        // Cairo 0 class has no CASM
+        let res: anyhow::Result<CasmCompiledContractClass> = test
+            .rpc(json!({
+                "jsonrpc": "2.0",
+                "method": "starknet_getCompiledCasm",
+                "params": {"class_hash": "0x789"},
+                "id": 2
+            }))
+            .await;
+        assert!(res.is_err());
+
+        // Missing CASM is not fetched from the gateway
+        db.casms.del("0x456").await?;
+        let res: anyhow::Result<CasmCompiledContractClass> = test
+            .rpc(json!({
+                "jsonrpc": "2.0",
+                "method": "starknet_getCompiledCasm",
+                "params": {"class_hash": "0x123"},
+                "id": 3
+            }))
+            .await;
+        assert!(res.is_err());
+
+        Ok(())
+    }
+}
+
+mod websocket {
+    use std::time::Duration;
+
+    use armada::{
+        api::gen::BlockWithTxs,
+        db::Repo,
+        sub::{self, Notification},
+    };
+    use futures::{SinkExt, Stream, StreamExt};
+    use tokio_tungstenite::{
+        connect_async,
+        tungstenite::{Error, Message},
+    };
+
+    use super::*;
+
+    async fn next<S>(ws: &mut S) -> anyhow::Result<serde_json::Value>
+    where
+        S: Stream<Item = Result<Message, Error>> + Unpin,
+    {
+        let message = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Connection closed"))??;
+        Ok(serde_json::from_str(message.to_text()?)?)
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions() -> anyhow::Result<()> {
+        let json = fs::read_to_string("./etc/805543-block.json")?;
+        let block: BlockWithTxs = serde_json::from_str(&json)?;
+        let hash = block.block_header.block_hash.0.as_ref().clone();
+
+        let test = common::Test::new().await;
+        test.ctx.db.blocks.put(&hash, block.clone()).await?;
+        let (mut ws, _) = connect_async(test.ws_url()).await?;
+
+        // Plain requests are served as over HTTP
+        let req = json!({
+            "jsonrpc": "2.0",
+            "method": "starknet_getBlockWithTxHashes",
+            "params": {"block_hash": hash},
+            "id": 1
+        });
+        ws.send(Message::Text(req.to_string())).await?;
+        let res = next(&mut ws).await?;
+        assert_eq!(res["id"], 1);
+        assert_eq!(res["result"]["block_hash"], hash.as_str());
+
+        let req = json!({
+            "jsonrpc": "2.0",
+            "method": "armada_subscribeNewHeads",
+            "params": [],
+            "id": 2
+        });
+        ws.send(Message::Text(req.to_string())).await?;
+        let heads = next(&mut ws).await?["result"].clone();
+
+        test.ctx
+            .notify(Notification::NewHead(block.block_header.clone()));
+        let res = next(&mut ws).await?;
+        assert_eq!(res["method"], sub::NOTIFICATION);
+        assert_eq!(res["params"]["subscription"], heads);
+        assert_eq!(res["params"]["result"]["block_hash"], hash.as_str());
+
+        let events = sub::events(&block);
+        let address = events[0].event.from_address.0.as_ref().clone();
+        let expected = events
+            .iter()
+            .filter(|event| event.event.from_address.0.as_ref() == &address)
+            .count();
+        let req = json!({
+            "jsonrpc": "2.0",
+            "method": "armada_subscribeEvents",
+            "params": [{"address": address}],
+            "id": 3
+        });
+        ws.send(Message::Text(req.to_string())).await?;
+        let id = next(&mut ws).await?["result"].clone();
+        assert_ne!(id, heads);
+
+        test.ctx.notify(Notification::Events(events));
+        for _ in 0..expected {
+            let res = next(&mut ws).await?;
+            assert_eq!(res["params"]["subscription"], id);
+            assert_eq!(res["params"]["result"]["from_address"], address);
+            assert_eq!(res["params"]["result"]["block_hash"], hash.as_str());
+        }
+
+        for (id, expected) in [(heads.clone(), true), (heads, false)] {
+            let req = json!({
+                "jsonrpc": "2.0",
+                "method": "armada_unsubscribe",
+                "params": [id],
+                "id": 4
+            });
+            ws.send(Message::Text(req.to_string())).await?;
+            assert_eq!(next(&mut ws).await?["result"], expected);
+        }
+        Ok(())
+    }
+}
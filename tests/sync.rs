@@ -11,6 +11,7 @@ mod common;
 #[tokio::test]
 async fn test_sync_events() -> anyhow::Result<()> {
     use armada::db::Repo;
+    use yakvdb::typed::DB;
 
     let test = common::Test::new().await;
 
@@ -23,6 +24,17 @@ async fn test_sync_events() -> anyhow::Result<()> {
 
     test.ctx.db.blocks.put(latest_hash.as_ref(), latest).await?;
 
+    // A block blob without the index entries is pulled again
+    let ctx = std::sync::Arc::new(tokio::sync::Mutex::new(test.ctx.clone()));
+    let event = sync::poll_seq(ctx).await?;
+    assert!(
+        matches!(event, Some(Event::PullBlock(n, _)) if n == latest_number)
+    );
+    test.ctx.db.hashes_index.write().await.insert(
+        &armada::util::U256::from_hex(latest_hash.as_ref())?,
+        armada::util::U64::from_u64(latest_number),
+    )?;
+
     *test.ctx.eth.state().await = Some(armada::eth::State {
         state_block_number: 1,
         state_root: NumAsHex::try_new("0x2")?,
@@ -68,3 +80,825 @@ async fn get_file<T: DeserializeOwned>(path: &str) -> anyhow::Result<T> {
     let val: T = serde_json::from_str(&json)?;
     Ok(val)
 }
+
+#[tokio::test]
+async fn test_accept_on_l1() -> anyhow::Result<()> {
+    use armada::api::gen::{BlockStatus, GetBlockWithTxsResult};
+    use armada::db::Repo;
+    use armada::util::{U256, U64};
+    use yakvdb::typed::DB;
+
+    let test = common::Test::new().await;
+
+    let mut block: BlockWithTxs = get_file("etc/805543-block.json").await?;
+    let number = *block.block_header.block_number.as_ref() as u64;
+    let hash = block.block_header.block_hash.0.as_ref().clone();
+    block.status = BlockStatus::AcceptedOnL2;
+
+    test.ctx.db.blocks.put(&hash, block).await?;
+    test.ctx
+        .db
+        .blocks_index
+        .write()
+        .await
+        .insert(&U64::from_u64(number), U256::from_hex(&hash)?)?;
+
+    let mismatch = armada::eth::State {
+        state_block_number: number,
+        state_root: NumAsHex::try_new("0x1")?,
+        state_block_hash: NumAsHex::try_new("0x2")?,
+    };
+    assert_eq!(sync::accept_on_l1(&test.ctx.db, &mismatch).await?, 0);
+
+    let state = armada::eth::State {
+        state_block_number: number,
+        state_root: NumAsHex::try_new("0x1")?,
+        state_block_hash: NumAsHex::try_new(&hash)?,
+    };
+    assert_eq!(sync::accept_on_l1(&test.ctx.db, &state).await?, 1);
+    assert_eq!(sync::accept_on_l1(&test.ctx.db, &state).await?, 0);
+
+    let res: GetBlockWithTxsResult = test
+        .rpc(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "starknet_getBlockWithTxs",
+            "params": {"block_id": {"block_number": number}},
+            "id": 1
+        }))
+        .await?;
+    let block = match res {
+        GetBlockWithTxsResult::BlockWithTxs(block) => block,
+        unexpected => anyhow::bail!("Unexpected variant: {unexpected:?}"),
+    };
+    assert!(matches!(block.status, BlockStatus::AcceptedOnL1));
+
+    // A lower block stored after the higher one was accepted
+    let lower = number - 10;
+    test.ctx
+        .db
+        .blocks_index
+        .write()
+        .await
+        .insert(&U64::from_u64(lower), U256::from_hex("0x42")?)?;
+    // Not walked again: marked when saved
+    assert_eq!(sync::accept_on_l1(&test.ctx.db, &state).await?, 0);
+    assert!(sync::mark_accepted(&test.ctx.db, lower).await?);
+    assert!(!sync::mark_accepted(&test.ctx.db, lower).await?);
+    assert!(!sync::mark_accepted(&test.ctx.db, number + 1).await?);
+    let status = test
+        .ctx
+        .db
+        .statuses_index
+        .read()
+        .await
+        .lookup(&U64::from_u64(lower))?;
+    assert_eq!(
+        status.map(|status| status.into_u64()),
+        Some(armada::db::status::ACCEPTED_ON_L1)
+    );
+    assert_eq!(sync::accept_on_l1(&test.ctx.db, &state).await?, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_eth_logs() -> anyhow::Result<()> {
+    use armada::api::gen::L1Settlement;
+    use armada::util::U64;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    use yakvdb::typed::DB;
+
+    let test = common::Test::new().await;
+    let ctx = Arc::new(Mutex::new(test.ctx.clone()));
+
+    let number = 805543;
+    let update = armada::eth::Update {
+        state: armada::eth::State {
+            state_block_number: number,
+            state_root: NumAsHex::try_new("0x1")?,
+            state_block_hash: NumAsHex::try_new("0x2")?,
+        },
+        eth_block_number: 100,
+        eth_block_hash: NumAsHex::try_new("0xabc")?,
+        eth_tx_hash: NumAsHex::try_new("0xdef")?,
+    };
+    *test.ctx.eth.head().await = 112;
+    test.ctx.eth.updates().await.push(update);
+
+    let event = sync::poll_eth_logs(ctx.clone()).await?;
+    let (to, updates, messages) = match event {
+        Some(Event::Settle(to, updates, messages)) => (to, updates, messages),
+        unexpected => anyhow::bail!("Unexpected event: {unexpected:?}"),
+    };
+    assert_eq!(to, 100);
+    assert_eq!(updates.len(), 1);
+
+    let events =
+        sync::handler(ctx.clone(), Event::Settle(to, updates, messages))
+            .await?;
+    assert!(events.is_empty());
+    assert_eq!(test.ctx.shared.lock().await.sync.eth, Some(101));
+
+    let key = U64::from_u64(number);
+    let settlement = test.ctx.db.settlements_index.read().await.lookup(&key)?;
+    assert_eq!(settlement.map(|s| s.number().into_u64()), Some(100));
+
+    let res: L1Settlement = test
+        .rpc(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "armada_getL1Settlement",
+            "params": {"block_id": {"block_number": number - 1}},
+            "id": 1
+        }))
+        .await?;
+    assert_eq!(*res.settled_block_number.as_ref(), number as i64);
+    assert_eq!(res.l1_block_number, 100);
+    assert_eq!(res.l1_transaction_hash.as_ref(), "0xdef");
+
+    test.ctx.eth.updates().await[0].eth_block_hash =
+        NumAsHex::try_new("0xabd")?;
+    let event = sync::poll_eth_logs(ctx.clone()).await?;
+    assert!(matches!(event, Some(Event::EthReorg(100))));
+
+    sync::handler(ctx.clone(), Event::EthReorg(100)).await?;
+    assert!(test.ctx.db.settlements_index.read().await.max()?.is_none());
+    assert_eq!(test.ctx.shared.lock().await.sync.eth, Some(100));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_eth_logs_backfill() -> anyhow::Result<()> {
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    let test = common::Test::new().await;
+    *test.ctx.eth.head().await = 5000;
+
+    // Only the recent L1 blocks are scanned unless the deployment is known
+    let ctx = Arc::new(Mutex::new(test.ctx.clone()));
+    match sync::poll_eth_logs(ctx).await? {
+        Some(Event::Settle(to, _, _)) => assert_eq!(to, 4988),
+        unexpected => anyhow::bail!("Unexpected event: {unexpected:?}"),
+    }
+
+    let mut ctx = test.ctx.clone();
+    ctx.config = ctx.config.with_eth_deploy_block(10);
+    let ctx = Arc::new(Mutex::new(ctx));
+    match sync::poll_eth_logs(ctx).await? {
+        Some(Event::Settle(to, _, _)) => {
+            assert_eq!(to, 10 + sync::ETH_LOGS_RANGE - 1)
+        }
+        unexpected => anyhow::bail!("Unexpected event: {unexpected:?}"),
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_eth_reorg_depth() -> anyhow::Result<()> {
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    let test = common::Test::new().await;
+    let ctx = Arc::new(Mutex::new(test.ctx.clone()));
+
+    let update = |number: u64, eth: u64| -> anyhow::Result<_> {
+        Ok(armada::eth::Update {
+            state: armada::eth::State {
+                state_block_number: number,
+                state_root: NumAsHex::try_new("0x1")?,
+                state_block_hash: NumAsHex::try_new("0x2")?,
+            },
+            eth_block_number: eth,
+            eth_block_hash: NumAsHex::try_new(&format!("{eth:#x}"))?,
+            eth_tx_hash: NumAsHex::try_new("0xdef")?,
+        })
+    };
+    let updates = vec![update(10, 100)?, update(11, 105)?];
+    *test.ctx.eth.head().await = 120;
+    *test.ctx.eth.updates().await = updates.clone();
+    sync::handler(ctx.clone(), Event::Settle(108, updates, vec![])).await?;
+
+    // The earlier settlement is reorged while the last one is not
+    test.ctx.eth.updates().await[0].eth_block_hash =
+        NumAsHex::try_new("0xabd")?;
+    let event = sync::poll_eth_logs(ctx.clone()).await?;
+    assert!(matches!(event, Some(Event::EthReorg(100))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_eth_logs_quorum() -> anyhow::Result<()> {
+    use armada::ctx::{Context, Shared};
+    use armada::eth::EthPool;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    let test = common::Test::new().await;
+
+    let update = |hash: &str| -> anyhow::Result<armada::eth::Update> {
+        Ok(armada::eth::Update {
+            state: armada::eth::State {
+                state_block_number: 805543,
+                state_root: NumAsHex::try_new("0x1")?,
+                state_block_hash: NumAsHex::try_new(hash)?,
+            },
+            eth_block_number: 100,
+            eth_block_hash: NumAsHex::try_new("0xabc")?,
+            eth_tx_hash: NumAsHex::try_new("0xdef")?,
+        })
+    };
+    let providers = [
+        common::eth::TestEth::new(),
+        common::eth::TestEth::new(),
+        common::eth::TestEth::new(),
+    ];
+    for (eth, hash) in providers.iter().zip(["0x2", "0x2", "0x666"]) {
+        *eth.head().await = 112;
+        eth.updates().await.push(update(hash)?);
+    }
+
+    let poll = |quorum: usize| {
+        let eth = EthPool::new(providers.to_vec()).with_quorum(quorum);
+        let ctx = Context::new(
+            eth,
+            test.ctx.seq.clone(),
+            Shared::default(),
+            test.ctx.db.clone(),
+            test.ctx.config.clone(),
+        );
+        sync::poll_eth_logs(Arc::new(Mutex::new(ctx)))
+    };
+
+    // The update logged by a single provider is outvoted
+    let updates = match poll(2).await? {
+        Some(Event::Settle(_, updates, _)) => updates,
+        unexpected => anyhow::bail!("Unexpected event: {unexpected:?}"),
+    };
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0].state.state_block_hash.as_ref(), "0x2");
+
+    // Without a quorum nothing is settled
+    assert!(poll(3).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_messages() -> anyhow::Result<()> {
+    use armada::api::gen::{GetMessagesStatusResult, MessageStatus};
+    use armada::util::{U256, U64};
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    use yakvdb::typed::DB;
+
+    let test = common::Test::new().await;
+    let ctx = Arc::new(Mutex::new(test.ctx.clone()));
+
+    let mut db = test.ctx.db.clone();
+    for file in ["etc/805543-block.json", "etc/793846-block.json"] {
+        let block: BlockWithTxs = get_file(file).await?;
+        let number = *block.block_header.block_number.as_ref() as u64;
+        let hash = block.block_header.block_hash.0.clone();
+        db.hashes_index
+            .write()
+            .await
+            .insert(&U256::from_hex(hash.as_ref())?, U64::from_u64(number))?;
+        sync::save_block(&mut db, hash, block).await?;
+    }
+
+    // L1->L2 message consumed by L1 handler tx in block 805543
+    let message = armada::eth::Message {
+        hash: U256::from_hex(
+            "0x1d4217e8fb440d1ccd86a15bb5f2c81462a4780f0dcb6c95cc5581509a92a90f",
+        )?,
+        eth_block_number: 100,
+        eth_tx_hash: NumAsHex::try_new("0x123")?,
+        eth_log_index: 7,
+    };
+    // L1->L2 message that is not consumed on L2 yet
+    let pending = armada::eth::Message {
+        hash: U256::from_hex("0x42")?,
+        eth_block_number: 100,
+        eth_tx_hash: NumAsHex::try_new("0x123")?,
+        eth_log_index: 3,
+    };
+    *test.ctx.eth.head().await = 112;
+    test.ctx.eth.messages().await.push(message);
+    test.ctx.eth.messages().await.push(pending);
+
+    let event = sync::poll_eth_logs(ctx.clone()).await?;
+    let event = event.ok_or(anyhow::anyhow!("No event"))?;
+    sync::handler(ctx.clone(), event).await?;
+
+    let res: GetMessagesStatusResult = test
+        .rpc(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "starknet_getMessagesStatus",
+            "params": {"transaction_hash": "0x123"},
+            "id": 1
+        }))
+        .await?;
+    let res = res.0;
+    assert_eq!(res.len(), 2);
+    assert_eq!(res[0].message_hash.as_ref(), "0x42");
+    assert!(res[0].transaction_hash.is_none());
+    assert_eq!(
+        res[1]
+            .transaction_hash
+            .as_ref()
+            .map(|tx| tx.0.as_ref().as_str()),
+        Some(
+            "0x65c4eff17ea2bf2a738e88269df164531e8e869ecc664e5ef170bb4b98ddceb"
+        )
+    );
+    assert!(matches!(
+        res[1].finality_status,
+        Some(armada::api::gen::TxnStatus::AcceptedOnL2)
+    ));
+
+    // L2->L1 message sent by tx in block 793846
+    let res: MessageStatus = test
+        .rpc(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "armada_getMessage",
+            "params": {"message_hash": "0x03c9e8e2e9a8f6566289f37db8e0f859a140e05227e52bfed701c6ee812cf236"},
+            "id": 2
+        }))
+        .await?;
+    assert_eq!(
+        res.transaction_hash
+            .as_ref()
+            .map(|tx| tx.0.as_ref().as_str()),
+        Some(
+            "0x1d445c127aad2d6312b892d65b0fe4c67dba6bf43456abdbf06a0692559394"
+        )
+    );
+
+    // The same message sent again by another (not stored) tx
+    let key = armada::db::MessageAndTx::from(
+        U256::from_hex(
+            "0x03c9e8e2e9a8f6566289f37db8e0f859a140e05227e52bfed701c6ee812cf236",
+        )?,
+        U256::from_hex("0x1")?,
+    );
+    db.messages_index
+        .write()
+        .await
+        .insert(&key, U64::from_u64(1))?;
+    let res: MessageStatus = test
+        .rpc(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "armada_getMessage",
+            "params": {"message_hash": "0x03c9e8e2e9a8f6566289f37db8e0f859a140e05227e52bfed701c6ee812cf236"},
+            "id": 3
+        }))
+        .await?;
+    assert_eq!(
+        res.transaction_hash
+            .as_ref()
+            .map(|tx| tx.0.as_ref().as_str()),
+        Some(
+            "0x1d445c127aad2d6312b892d65b0fe4c67dba6bf43456abdbf06a0692559394"
+        )
+    );
+
+    // Messages sent in the reorged L1 blocks are dropped
+    sync::handler(ctx.clone(), Event::EthReorg(100)).await?;
+    let res = test
+        .rpc::<_, GetMessagesStatusResult>(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "starknet_getMessagesStatus",
+            "params": {"transaction_hash": "0x123"},
+            "id": 4
+        }))
+        .await;
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_notifications() -> anyhow::Result<()> {
+    use armada::{seq::dto, sub::Notification};
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    let test = common::Test::new().await;
+    let ctx = Arc::new(Mutex::new(test.ctx.clone()));
+    let mut rx = test.ctx.notify.subscribe();
+
+    let block: BlockWithTxs = get_file("etc/805543-block.json").await?;
+    let number = *block.block_header.block_number.as_ref() as u64;
+    let hash = block.block_header.block_hash.0.clone();
+    // No classes to fetch for the block
+    let mut state: dto::StateUpdate =
+        get_file("etc/805543-state-update.json").await?;
+    state.state_diff.deployed_contracts.clear();
+    state.state_diff.replaced_classes.clear();
+    state.state_diff.old_declared_contracts.clear();
+    state.state_diff.declared_classes.clear();
+    *test.ctx.seq.block().await = Some(block);
+    *test.ctx.seq.state().await = Some(state);
+
+    sync::handler(ctx.clone(), Event::PullBlock(number, hash.clone())).await?;
+    assert!(matches!(rx.try_recv(), Ok(Notification::NewHead(_))));
+    match rx.try_recv() {
+        Ok(Notification::Events(events)) => assert!(!events.is_empty()),
+        unexpected => anyhow::bail!("Unexpected: {unexpected:?}"),
+    }
+    assert!(rx.try_recv().is_err());
+
+    // A block below the highest synced one is not published
+    test.ctx.shared.lock().await.sync.hi = Some(number + 1);
+    sync::handler(ctx.clone(), Event::PullBlock(number, hash.clone())).await?;
+    assert!(rx.try_recv().is_err());
+
+    sync::handler(ctx.clone(), Event::PurgeBlock(number, hash)).await?;
+    match rx.try_recv() {
+        Ok(Notification::Reorg(block)) => {
+            assert_eq!(block.block_number, number)
+        }
+        unexpected => anyhow::bail!("Unexpected: {unexpected:?}"),
+    }
+
+    Ok(())
+}
+
+async fn failing_handler<ETH, SEQ>(
+    _ctx: std::sync::Arc<tokio::sync::Mutex<armada::ctx::Context<ETH, SEQ>>>,
+    _event: Event,
+) -> anyhow::Result<Vec<Event>> {
+    anyhow::bail!("Always fails")
+}
+
+#[tokio::test]
+async fn test_dead_letters() -> anyhow::Result<()> {
+    use armada::{api::gen::DeadLetter, cfg::Retry};
+
+    let test = common::Test::new().await;
+
+    let mut ctx = test.ctx.clone();
+    ctx.config = ctx.config.with_retry(Retry {
+        attempts: 3,
+        delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(10),
+    });
+    let src = Source::new(ctx);
+    let tx = src.tx();
+    let syncer = sync::sync(src, failing_handler).await;
+
+    let hash = armada::api::gen::Felt::try_new("0x42")?;
+    tx.send(Event::PullBlock(42, hash.clone())).await?;
+    for _ in 0..100 {
+        if !sync::dead_letter_ids(&test.ctx.db).await?.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    syncer.stop();
+
+    let res: Vec<DeadLetter> = test
+        .rpc(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "armada_getDeadLetters",
+            "params": [],
+            "id": 1
+        }))
+        .await?;
+    assert_eq!(res.len(), 1);
+    let letter = &res[0];
+    assert_eq!(letter.kind, "PullBlock");
+    assert_eq!(letter.attempts, 3);
+    assert_eq!(
+        letter.block_hash.as_ref().map(|hash| hash.as_ref()),
+        Some(hash.as_ref())
+    );
+    assert!(letter.reason.contains("Always fails"));
+
+    // Retry re-sends the event to the sync
+    let (events, mut rx) = tokio::sync::mpsc::channel(1);
+    test.ctx.shared.lock().await.events = Some(events);
+    let retried: bool = test
+        .rpc(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "armada_retryDeadLetter",
+            "params": {"id": letter.id},
+            "id": 2
+        }))
+        .await?;
+    assert!(retried);
+    assert!(matches!(rx.recv().await, Some(Event::PullBlock(42, _))));
+    assert!(sync::dead_letter_ids(&test.ctx.db).await?.is_empty());
+
+    let event = Event::Prune(7);
+    let id =
+        sync::save_dead_letter(&test.ctx.db, &event, 1, "?".into()).await?;
+
+    // Admin methods are refused unless enabled, and by read-only nodes
+    {
+        use armada::api::gen::Rpc;
+        use armada::db::Storage;
+
+        let mut ctx = test.ctx.clone();
+        ctx.config = ctx.config.with_admin(false);
+        let e = ctx.discardDeadLetter(id as i64).await.unwrap_err();
+        assert_eq!(e.code, 1000); // ADMIN_DISABLED
+        let e = ctx.getDeadLetters().await.unwrap_err();
+        assert_eq!(e.code, 1000); // ADMIN_DISABLED
+        ctx.config = ctx.config.with_admin(true);
+        let e = ctx.discardDeadLetter(-1).await.unwrap_err();
+        assert_eq!(e.code, -32602); // Invalid params
+        ctx.db = Storage::read_only(&test.ctx.db.base, 16).await?;
+        let e = ctx.retryDeadLetter(id as i64).await.unwrap_err();
+        assert_eq!(e.code, 1001); // READ_ONLY
+    }
+    let discarded: bool = test
+        .rpc(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "armada_discardDeadLetter",
+            "params": {"id": id},
+            "id": 3
+        }))
+        .await?;
+    assert!(discarded);
+    assert!(sync::dead_letter_ids(&test.ctx.db).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_retry_interrupted() -> anyhow::Result<()> {
+    use armada::cfg::Retry;
+
+    let test = common::Test::new().await;
+
+    let mut ctx = test.ctx.clone();
+    ctx.config = ctx.config.with_retry(Retry {
+        attempts: 10,
+        delay: Duration::from_secs(3600),
+        max_delay: Duration::from_secs(3600),
+    });
+    let src = Source::new(ctx);
+    let tx = src.tx();
+    let syncer = sync::sync(src, failing_handler).await;
+
+    // The event waiting for a retry on shutdown is not a dead letter
+    let hash = armada::api::gen::Felt::try_new("0x42")?;
+    tx.send(Event::PullBlock(42, hash)).await?;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    syncer.stop();
+    syncer.done().await;
+
+    let ids = sync::dead_letter_ids(&test.ctx.db).await?;
+    assert!(ids.is_empty());
+    Ok(())
+}
+
+static CALLS: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(0);
+static RUNNING: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(0);
+static MAX_RUNNING: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(0);
+
+async fn slow_handler<ETH, SEQ>(
+    _ctx: std::sync::Arc<tokio::sync::Mutex<armada::ctx::Context<ETH, SEQ>>>,
+    _event: Event,
+) -> anyhow::Result<Vec<Event>> {
+    use std::sync::atomic::Ordering;
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    let running = RUNNING.fetch_add(1, Ordering::SeqCst) + 1;
+    MAX_RUNNING.fetch_max(running, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    RUNNING.fetch_sub(1, Ordering::SeqCst);
+    Ok(vec![])
+}
+
+#[tokio::test]
+async fn test_inflight_pulls() -> anyhow::Result<()> {
+    use std::sync::atomic::Ordering;
+
+    let test = common::Test::new().await;
+    let mut ctx = test.ctx.clone();
+    ctx.config = ctx.config.with_max_handlers(2);
+    let src = Source::new(ctx);
+    let tx = src.tx();
+    let syncer = sync::sync(src, slow_handler).await;
+
+    // Duplicate pulls of the same block are coalesced, a pull of any hash
+    // ("0x0") joins a pull of the given hash, another hash is pulled
+    for hash in ["0x42", "0x42", "0x0", "0x43", "0x42"] {
+        let hash = armada::api::gen::Felt::try_new(hash)?;
+        tx.send(Event::PullBlock(42, hash)).await?;
+    }
+    // Distinct events are handled with bounded concurrency
+    for number in 0..4 {
+        tx.send(Event::Prune(number)).await?;
+    }
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    syncer.stop();
+
+    assert_eq!(CALLS.load(Ordering::SeqCst), 6);
+    assert_eq!(MAX_RUNNING.load(Ordering::SeqCst), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_range() -> anyhow::Result<()> {
+    use armada::{
+        api::gen::{Rpc, SyncingSyncing},
+        util::{U256, U64},
+    };
+    use std::sync::Arc;
+    use yakvdb::typed::DB;
+
+    let test = common::Test::new().await;
+
+    let latest: BlockWithTxs = get_file("etc/805543-block.json").await?;
+    let hash = latest.block_header.block_hash.0.clone();
+    *test.ctx.seq.latest().await = Some(latest);
+    test.ctx
+        .db
+        .blocks_index
+        .write()
+        .await
+        .insert(&U64::from_u64(805543), U256::from_hex(hash.as_ref())?)?;
+
+    let mut ctx = test.ctx.clone();
+    ctx.config = ctx.config.with_sync_range(Some(805000), Some(805500));
+    assert_eq!(ctx.floor().await, Some(805000));
+
+    // The head poller pulls the end of the range instead of the latest block
+    let event =
+        sync::poll_seq(Arc::new(tokio::sync::Mutex::new(ctx.clone()))).await?;
+    assert!(matches!(
+        event,
+        Some(Event::PullBlock(805500, hash)) if hash.as_ref() == "0x0"
+    ));
+
+    ctx.config = ctx.config.with_sync_range(Some(805000), Some(805543));
+    let status = match ctx.syncing().await? {
+        SyncingSyncing::SyncStatus(status) => status,
+        SyncingSyncing::Boolean(_) => anyhow::bail!("Sync status expected"),
+    };
+    assert_eq!(status.starting_block_num.as_ref(), "0xc4888");
+    assert_eq!(status.starting_block_hash.0.as_ref(), "0x0");
+    assert_eq!(status.highest_block_num.as_ref(), "0xc4aa7");
+    assert!(
+        U256::from_hex(status.highest_block_hash.0.as_ref())?
+            == U256::from_hex(hash.as_ref())?
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_forward_sync() -> anyhow::Result<()> {
+    use armada::cfg::Direction;
+    use std::sync::Arc;
+
+    let test = common::Test::new().await;
+
+    let latest: BlockWithTxs = get_file("etc/805543-block.json").await?;
+    *test.ctx.seq.latest().await = Some(latest);
+
+    let mut ctx = test.ctx.clone();
+    ctx.config = ctx.config.with_direction(Direction::Forward);
+
+    // Nothing is stored: the sync starts from genesis
+    let event =
+        sync::poll_seq(Arc::new(tokio::sync::Mutex::new(ctx.clone()))).await?;
+    assert!(matches!(
+        event,
+        Some(Event::PullBlock(0, hash)) if hash.as_ref() == "0x0"
+    ));
+    assert_eq!(ctx.shared.lock().await.sync.head, Some(805543));
+
+    // ...or from the beginning of the sync range
+    ctx.config = ctx.config.with_sync_range(Some(805000), None);
+    let event =
+        sync::poll_seq(Arc::new(tokio::sync::Mutex::new(ctx.clone()))).await?;
+    assert!(matches!(event, Some(Event::PullBlock(805000, _))));
+
+    // ...or from the block next to the highest stored one
+    ctx.shared.lock().await.sync.hi = Some(805542);
+    let event =
+        sync::poll_seq(Arc::new(tokio::sync::Mutex::new(ctx.clone()))).await?;
+    assert!(matches!(event, Some(Event::PullBlock(805543, _))));
+
+    // Once the head is reached, the head is reported
+    ctx.shared.lock().await.sync.hi = Some(805543);
+    let event =
+        sync::poll_seq(Arc::new(tokio::sync::Mutex::new(ctx.clone()))).await?;
+    assert!(matches!(event, Some(Event::Head(805543, _))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_verify_block() -> anyhow::Result<()> {
+    use armada::{
+        api::gen::{Felt, Txn},
+        cfg::encode_chain_id,
+        hash::verify_block,
+    };
+
+    let chain_id = encode_chain_id("SN_GOERLI")?;
+    for file in ["etc/793846-block.json", "etc/805543-block.json"] {
+        let block: BlockWithTxs = get_file(file).await?;
+        verify_block(&block, &chain_id)?;
+    }
+
+    let block: BlockWithTxs = get_file("etc/805543-block.json").await?;
+    assert!(verify_block(&block, &encode_chain_id("SN_MAIN")?).is_err());
+
+    // A tampered calldata does not match the transaction hash
+    let mut tampered = block.clone();
+    if let Some(Txn::L1HandlerTxn(txn)) = tampered
+        .block_body_with_txs
+        .transactions
+        .iter_mut()
+        .find(|tx| matches!(tx, Txn::L1HandlerTxn(_)))
+    {
+        txn.function_call.calldata.push(Felt::try_new("0x1")?);
+    }
+    let e = verify_block(&tampered, &chain_id).unwrap_err();
+    assert!(e.to_string().starts_with("Transaction hash mismatch"));
+
+    // A dropped event does not match the block hash
+    let mut tampered = block;
+    if let Some(receipt) = tampered
+        .receipts
+        .iter_mut()
+        .find(|receipt| !receipt.events.is_empty())
+    {
+        receipt.events.pop();
+    }
+    let e = verify_block(&tampered, &chain_id).unwrap_err();
+    assert!(e.to_string().starts_with("Block hash mismatch"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_verify_signature() -> anyhow::Result<()> {
+    use armada::{api::gen::Felt, hash::verify_signature, seq::dto};
+    use starknet_crypto::{
+        get_public_key, pedersen_hash, rfc6979_generate_k, sign, FieldElement,
+    };
+
+    let block: BlockWithTxs = get_file("etc/805543-block.json").await?;
+    let block_hash = block.block_header.block_hash.0;
+    let hash = FieldElement::from_hex_be(block_hash.as_ref())?;
+
+    let private_key = FieldElement::from(42u64);
+    let public_key = format!("{:#x}", get_public_key(&private_key));
+    let signed = |message: FieldElement| -> anyhow::Result<Vec<Felt>> {
+        let k = rfc6979_generate_k(&message, &private_key, None);
+        let signed = sign(&private_key, &message, &k)?;
+        Ok(vec![
+            Felt::try_new(&format!("{:#x}", signed.r))?,
+            Felt::try_new(&format!("{:#x}", signed.s))?,
+        ])
+    };
+    let signature = dto::BlockSignature {
+        block_hash: block_hash.clone(),
+        signature: signed(hash)?,
+        signature_input: None,
+    };
+    verify_signature(&public_key, &block_hash, &signature)?;
+
+    // Before 0.13.2 the state diff commitment is signed as well
+    let commitment = FieldElement::from(0x5dcu64);
+    let legacy = dto::BlockSignature {
+        block_hash: block_hash.clone(),
+        signature: signed(pedersen_hash(&hash, &commitment))?,
+        signature_input: Some(dto::SignatureInput {
+            block_hash: block_hash.clone(),
+            state_diff_commitment: Felt::try_new("0x5dc")?,
+        }),
+    };
+    verify_signature(&public_key, &block_hash, &legacy)?;
+    let bare = dto::BlockSignature {
+        signature_input: None,
+        ..legacy
+    };
+    assert!(verify_signature(&public_key, &block_hash, &bare).is_err());
+
+    // Signed by another key
+    let other = format!("{:#x}", get_public_key(&FieldElement::from(43u64)));
+    assert!(verify_signature(&other, &block_hash, &signature).is_err());
+
+    // Signature of another block
+    let parent = block.block_header.parent_hash.0;
+    assert!(verify_signature(&public_key, &parent, &signature).is_err());
+
+    // Unsigned block
+    let unsigned = dto::BlockSignature {
+        block_hash: block_hash.clone(),
+        signature: vec![],
+        signature_input: None,
+    };
+    let e = verify_signature(&public_key, &block_hash, &unsigned).unwrap_err();
+    assert!(e.to_string().starts_with("Block is not signed"));
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use armada::{
+    api::gen::{Proof, ProofNode},
+    db::StateRoots,
+    seq::dto,
+    trie::{self, Hasher},
+    util::{U256, U64},
+};
+use starknet_crypto::{pedersen_hash, FieldElement};
+use yakvdb::typed::DB;
+
+mod common;
+
+fn kv(key: u64, value: u64) -> (U256, U256) {
+    (U256::from_u64(key), U256::from_u64(value))
+}
+
+#[tokio::test]
+async fn test_trie_update() -> anyhow::Result<()> {
+    let test = common::Test::new().await;
+    let mut nodes = test.ctx.db.trie_nodes_index.write().await;
+    let zero = U256::default();
+
+    // Single leaf: an edge of full height from the root to the leaf
+    let root = trie::update(&mut nodes, Hasher::Pedersen, &zero, &[kv(5, 7)])?;
+    let expected =
+        pedersen_hash(&FieldElement::from(7u64), &FieldElement::from(5u64))
+            + FieldElement::from(trie::HEIGHT as u64);
+    assert!(root == trie::from_felt(expected));
+    assert!(
+        trie::get(&nodes, &root, &U256::from_u64(5))?
+            == Some(U256::from_u64(7))
+    );
+    assert!(trie::get(&nodes, &root, &U256::from_u64(4))?.is_none());
+
+    // The root does not depend on how the changes are batched
+    let changes = [kv(1, 10), kv(2, 20), kv(3, 30), kv(1 << 40, 40), kv(5, 7)];
+    let mut sorted = changes.to_vec();
+    sorted.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+    let batch = trie::update(&mut nodes, Hasher::Pedersen, &zero, &sorted)?;
+    let mut one_by_one = zero.clone();
+    for change in changes {
+        one_by_one =
+            trie::update(&mut nodes, Hasher::Pedersen, &one_by_one, &[change])?;
+    }
+    assert!(batch == one_by_one);
+    for (key, value) in &sorted {
+        assert!(trie::get(&nodes, &batch, key)? == Some(value.clone()));
+    }
+
+    // Removed leaves restore the previous root
+    let removed = [kv(1, 0), kv(2, 0), kv(3, 0), kv(1 << 40, 0)];
+    let root = trie::update(&mut nodes, Hasher::Pedersen, &batch, &removed)?;
+    assert!(root == trie::from_felt(expected));
+    let root = trie::update(&mut nodes, Hasher::Pedersen, &root, &[kv(5, 0)])?;
+    assert!(root == zero);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_state_commitment() -> anyhow::Result<()> {
+    let contracts = U256::from_u64(42);
+
+    // Without Sierra classes the contracts root is the state commitment
+    let roots = StateRoots::from(contracts.clone(), U256::default());
+    assert!(trie::state_commitment(&roots)? == contracts);
+
+    let roots = StateRoots::from(contracts.clone(), U256::from_u64(1));
+    assert!(trie::state_commitment(&roots)? != contracts);
+    Ok(())
+}
+
+fn felt(felt: &armada::api::gen::Felt) -> FieldElement {
+    FieldElement::from_hex_be(felt.as_ref()).expect("felt")
+}
+
+/// Check the hashes along the proof from the root down the key,
+/// returns the value at the end of the path.
+fn walk(proof: &[ProofNode], root: FieldElement, key: u64) -> FieldElement {
+    let mut expected = root;
+    let mut depth = 0;
+    for node in proof {
+        match node {
+            ProofNode::Binary { binary } => {
+                let (left, right) = (felt(&binary.left), felt(&binary.right));
+                assert_eq!(pedersen_hash(&left, &right), expected);
+                let shift = trie::HEIGHT - 1 - depth;
+                let bit = shift < 64 && (key >> shift) & 1 == 1;
+                expected = if bit { right } else { left };
+                depth += 1;
+            }
+            ProofNode::Edge { edge } => {
+                let child = felt(&edge.child);
+                let hash = pedersen_hash(&child, &felt(&edge.path.value))
+                    + FieldElement::from(edge.path.len as u64);
+                assert_eq!(hash, expected);
+                expected = child;
+                depth += edge.path.len as usize;
+            }
+        }
+    }
+    assert_eq!(depth, trie::HEIGHT);
+    expected
+}
+
+#[tokio::test]
+async fn test_get_proof() -> anyhow::Result<()> {
+    let test = common::Test::new().await;
+    let db = &test.ctx.db;
+
+    let state: dto::StateUpdate = serde_json::from_value(serde_json::json!({
+        "block_hash": "0x1",
+        "new_root": "0x0",
+        "old_root": "0x0",
+        "state_diff": {
+            "storage_diffs": {
+                "0x123": [
+                    {"key": "0x5", "value": "0x50"},
+                    {"key": "0x6", "value": "0x60"}
+                ],
+                "0x456": [{"key": "0x1", "value": "0x10"}]
+            },
+            "nonces": {"0x123": "0x1"},
+            "deployed_contracts": [
+                {"address": "0x123", "class_hash": "0xabc"},
+                {"address": "0x456", "class_hash": "0xabc"}
+            ],
+            "old_declared_contracts": [],
+            "declared_classes": [],
+            "replaced_classes": []
+        }
+    }))?;
+    let empty = StateRoots::from(U256::default(), U256::default());
+    let roots = trie::apply_state(db, &empty, &state).await?;
+    db.trie_roots_index
+        .write()
+        .await
+        .insert(&U64::from_u64(1), roots.clone())?;
+
+    let proof: Proof = test
+        .rpc(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "armada_getProof",
+            "params": [{"block_number": 1}, "0x123", ["0x5", "0x6", "0x7"]],
+            "id": 1
+        }))
+        .await?;
+
+    // Without Sierra classes the state commitment is the contracts root
+    let root = felt(proof.state_commitment.as_ref().expect("state commitment"));
+    let leaf = walk(&proof.contract_proof, root, 0x123);
+
+    let data = proof.contract_data.expect("contract data");
+    let expected = pedersen_hash(
+        &pedersen_hash(
+            &pedersen_hash(&felt(&data.class_hash), &felt(&data.root)),
+            &felt(&data.nonce),
+        ),
+        &FieldElement::ZERO,
+    );
+    assert_eq!(leaf, expected);
+    assert_eq!(data.nonce.as_ref(), "0x1");
+
+    let storage = felt(&data.root);
+    let value = walk(&data.storage_proofs[0], storage, 0x5);
+    assert_eq!(value, FieldElement::from(0x50u64));
+    let value = walk(&data.storage_proofs[1], storage, 0x6);
+    assert_eq!(value, FieldElement::from(0x60u64));
+    assert!(!data.storage_proofs[2].is_empty());
+
+    // The latest block is the synced head: its state is not applied yet
+    db.blocks_index
+        .write()
+        .await
+        .insert(&U64::from_u64(2), U256::from_u64(2))?;
+    let latest = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "armada_getProof",
+        "params": ["latest", "0x123", []],
+        "id": 2
+    });
+    let e = test.rpc::<_, Proof>(latest.clone()).await.unwrap_err();
+    assert!(e.to_string().contains("1004"));
+
+    db.trie_roots_index
+        .write()
+        .await
+        .insert(&U64::from_u64(2), roots)?;
+    let proof: Proof = test.rpc(latest).await?;
+    assert_eq!(felt(proof.state_commitment.as_ref().expect("root")), root);
+    Ok(())
+}